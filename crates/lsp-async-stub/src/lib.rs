@@ -67,7 +67,7 @@ impl CancelToken {
         self.cancelled.load(Ordering::SeqCst)
     }
 
-    pub fn as_err(&mut self) -> CancelTokenErr {
+    pub fn as_err(&mut self) -> CancelTokenErr<'_> {
         CancelTokenErr(self)
     }
 }
@@ -344,6 +344,7 @@ impl<W: Clone> Server<W> {
     }
 
     #[tracing::instrument(level = tracing::Level::TRACE, skip(data, writer))]
+    #[allow(clippy::arc_with_non_send_sync)]
     async fn handle_request(
         inner: Arc<AsyncMutex<Inner<W>>>,
         data: W,
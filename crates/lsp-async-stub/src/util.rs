@@ -2,7 +2,6 @@
 //! Utilities for mapping between offset:length bytes and col:row character positions.
 
 use rowan::{TextRange, TextSize};
-use std::collections::BTreeMap;
 
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Default)]
 pub struct Position {
@@ -39,17 +38,31 @@ pub struct CharacterRange(u64, u64);
 /// 1-based line:row characters.
 #[derive(Debug, Clone)]
 pub struct Mapper {
-    /// Mapping offsets to positions.
-    offset_to_position: BTreeMap<TextSize, Position>,
+    /// The offset of the first byte of each line.
+    line_starts: Vec<TextSize>,
 
-    /// Mapping positions to offsets.
-    position_to_offset: BTreeMap<Position, TextSize>,
+    /// The characters of each line that are not a single byte.
+    wide_chars: Vec<Vec<WideChar>>,
 
-    /// Line count.
-    lines: usize,
+    /// The length of the source in bytes.
+    len: TextSize,
 
-    /// Ending position.
-    end: Position,
+    /// Whether positions are in UTF-16 code units instead of characters.
+    utf16: bool,
+
+    /// The first line and character.
+    base: u64,
+}
+
+/// A character that takes more than one byte.
+#[derive(Debug, Clone, Copy)]
+struct WideChar {
+    /// Offset from the start of the line.
+    offset: u32,
+    /// Length in bytes.
+    len: u32,
+    /// Length in characters or UTF-16 code units.
+    size: u32,
 }
 
 impl Mapper {
@@ -70,7 +83,62 @@ impl Mapper {
 
     #[must_use]
     pub fn offset(&self, position: Position) -> Option<TextSize> {
-        self.position_to_offset.get(&position).copied()
+        let line = usize::try_from(position.line.checked_sub(self.base)?).ok()?;
+        if line >= self.line_starts.len() {
+            return None;
+        }
+        let character = position.character.checked_sub(self.base)?;
+
+        match self.line_offset(line, character) {
+            (offset, true) => Some(offset),
+            (_, false) => None,
+        }
+    }
+
+    /// The offset of the position like [`Mapper::offset`], but positions after
+    /// the end of a line are at the end of the line and positions after the last line
+    /// are at the end of the source, as the LSP specification requires.
+    #[must_use]
+    pub fn offset_clamped(&self, position: Position) -> TextSize {
+        let line = usize::try_from(position.line.saturating_sub(self.base)).unwrap_or(usize::MAX);
+        if line >= self.line_starts.len() {
+            return self.len;
+        }
+
+        self.line_offset(line, position.character.saturating_sub(self.base))
+            .0
+    }
+
+    /// The offset of the character in the line, and whether the character
+    /// is a valid position in the line.
+    ///
+    /// Characters after the end of the line are at the end of the line,
+    /// and characters within a wide character at its first byte.
+    fn line_offset(&self, line: usize, character: u64) -> (TextSize, bool) {
+        let line_start = self.line_starts[line];
+        let line_end = self.line_end(line);
+
+        // Characters and bytes only differ by the wide characters before the position.
+        let mut extra_bytes = 0;
+        for c in &self.wide_chars[line] {
+            let c_character = u64::from(c.offset) - extra_bytes;
+            if character <= c_character {
+                break;
+            }
+
+            if character < c_character + u64::from(c.size) {
+                // Positions only point to the first code unit of characters.
+                return (line_start + TextSize::from(c.offset), false);
+            }
+
+            extra_bytes += u64::from(c.len - c.size);
+        }
+
+        let offset = u64::from(u32::from(line_start)).saturating_add(character + extra_bytes);
+        match u32::try_from(offset).map(TextSize::from) {
+            Ok(offset) if offset <= line_end => (offset, true),
+            _ => (line_end, false),
+        }
     }
 
     #[must_use]
@@ -81,7 +149,32 @@ impl Mapper {
 
     #[must_use]
     pub fn position(&self, offset: TextSize) -> Option<Position> {
-        self.offset_to_position.get(&offset).copied()
+        if offset > self.len {
+            return None;
+        }
+
+        let line = self.line_index(offset);
+        let mut column = u32::from(offset - self.line_starts[line]);
+
+        let mut extra_bytes = 0;
+        for c in &self.wide_chars[line] {
+            if column <= c.offset {
+                break;
+            }
+
+            if column < c.offset + c.len {
+                // Offsets within multi-byte characters point to their first byte.
+                column = c.offset;
+                break;
+            }
+
+            extra_bytes += c.len - c.size;
+        }
+
+        Some(Position {
+            line: line as u64 + self.base,
+            character: u64::from(column - extra_bytes) + self.base,
+        })
     }
 
     #[must_use]
@@ -90,14 +183,9 @@ impl Mapper {
             .and_then(|start| self.position(range.end()).map(|end| Range { start, end }))
     }
 
-    #[must_use]
-    pub fn mappings(&self) -> (&BTreeMap<TextSize, Position>, &BTreeMap<Position, TextSize>) {
-        (&self.offset_to_position, &self.position_to_offset)
-    }
-
     #[must_use]
     pub fn line_count(&self) -> usize {
-        self.lines
+        self.line_starts.len() - 1 + self.base as usize
     }
 
     #[must_use]
@@ -107,60 +195,103 @@ impl Mapper {
                 line: 0,
                 character: 0,
             },
-            end: self.end,
+            end: self.position(self.len).unwrap_or_default(),
         }
     }
 
-    fn new_impl(source: &str, utf16: bool, base: u64) -> Self {
-        let mut offset_to_position = BTreeMap::new();
-        let mut position_to_offset = BTreeMap::new();
+    /// Updates the mapper after the range of the source was replaced,
+    /// only the lines that were changed are scanned again.
+    ///
+    /// The source is the whole text after the edit.
+    pub fn edit(&mut self, source: &str, range: TextRange, new_len: TextSize) {
+        let first_line = self.line_index(range.start());
+        let last_line = self.line_index(range.end());
+
+        let delta = i64::from(u32::from(new_len)) - i64::from(u32::from(range.len()));
+        let shift =
+            |offset: &TextSize| TextSize::from((i64::from(u32::from(*offset)) + delta) as u32);
+
+        let start = self.line_starts[first_line];
+        let next_line = self.line_starts.get(last_line + 1).map(shift);
+        let end = next_line.unwrap_or(TextSize::from(source.len() as u32));
+
+        let (mut line_starts, mut wide_chars) = scan(
+            &source[usize::from(start)..usize::from(end)],
+            start,
+            self.utf16,
+        );
 
-        let mut line: u64 = base;
-        let mut character: u64 = base;
-        let mut last_offset = 0;
+        // The line after the changed ones is not scanned.
+        if next_line.is_some() {
+            line_starts.pop();
+            wide_chars.pop();
+        }
 
-        for c in source.chars() {
-            let new_offset = last_offset + c.len_utf8();
+        let following: Vec<TextSize> = self.line_starts[last_line + 1..]
+            .iter()
+            .map(shift)
+            .collect();
 
-            let character_size = if utf16 { c.len_utf16() } else { 1 };
+        self.line_starts.truncate(first_line);
+        self.line_starts.extend(line_starts);
+        self.line_starts.extend(following);
+        self.wide_chars.splice(first_line..=last_line, wide_chars);
+        self.len = TextSize::from(source.len() as u32);
+    }
 
-            offset_to_position.extend(
-                (last_offset..new_offset)
-                    .map(|b| (TextSize::from(b as u32), Position { line, character })),
-            );
+    fn new_impl(source: &str, utf16: bool, base: u64) -> Self {
+        let (line_starts, wide_chars) = scan(source, TextSize::from(0), utf16);
 
-            position_to_offset.extend(
-                (last_offset..new_offset)
-                    .map(|b| (Position { line, character }, TextSize::from(b as u32))),
-            );
+        Self {
+            line_starts,
+            wide_chars,
+            len: TextSize::from(source.len() as u32),
+            utf16,
+            base,
+        }
+    }
 
-            last_offset = new_offset;
+    /// The line that contains the offset.
+    fn line_index(&self, offset: TextSize) -> usize {
+        self.line_starts.partition_point(|start| *start <= offset) - 1
+    }
 
-            character += character_size as u64;
-            if c == '\n' {
-                // LF is at the start of each line.
-                line += 1;
-                character = base;
-            }
+    /// The offset of the line feed that ends the line, or the end of the source.
+    fn line_end(&self, line: usize) -> TextSize {
+        match self.line_starts.get(line + 1) {
+            Some(next_line) => *next_line - TextSize::from(1),
+            None => self.len,
         }
+    }
+}
 
-        // Last imaginary character.
-        offset_to_position.insert(
-            TextSize::from(last_offset as u32),
-            Position { line, character },
-        );
-        position_to_offset.insert(
-            Position { line, character },
-            TextSize::from(last_offset as u32),
-        );
+/// The starts of the lines and the wide characters of each line in the text,
+/// the first line starts at the given offset.
+fn scan(text: &str, start: TextSize, utf16: bool) -> (Vec<TextSize>, Vec<Vec<WideChar>>) {
+    let mut line_starts = vec![start];
+    let mut wide_chars = vec![Vec::new()];
+    let mut line_offset = 0;
+
+    for (idx, c) in text.char_indices() {
+        let offset = idx as u32 - line_offset;
+
+        if c.len_utf8() > 1 {
+            wide_chars.last_mut().unwrap().push(WideChar {
+                offset,
+                len: c.len_utf8() as u32,
+                size: if utf16 { c.len_utf16() as u32 } else { 1 },
+            });
+        }
 
-        Self {
-            offset_to_position,
-            position_to_offset,
-            lines: line as usize,
-            end: Position { line, character },
+        if c == '\n' {
+            // LF is at the end of each line.
+            line_offset = idx as u32 + 1;
+            line_starts.push(start + TextSize::from(line_offset));
+            wide_chars.push(Vec::new());
         }
     }
+
+    (line_starts, wide_chars)
 }
 
 #[must_use]
@@ -251,7 +382,7 @@ line-3"#;
 
     let mapper = Mapper::new_utf16(s1, false);
 
-    assert!(s1.len() == mapper.mappings().0.len() - 1);
+    assert!((0..=s1.len()).all(|offset| mapper.position(TextSize::from(offset as u32)).is_some()));
 
     assert!(
         mapper.position(0.into()).unwrap()
@@ -271,3 +402,106 @@ line-3"#;
             }
     );
 }
+
+#[cfg(test)]
+#[test]
+fn test_mapper_multi_byte() {
+    let s1 = "é = '🦀'\nb = 1";
+
+    let mapper = Mapper::new_utf16(s1, false);
+
+    assert_eq!(
+        mapper.offset(Position {
+            line: 0,
+            character: 1
+        }),
+        Some(TextSize::from(2))
+    );
+
+    // The crab is 4 bytes in UTF-8 and 2 code units in UTF-16.
+    assert_eq!(
+        mapper.offset(Position {
+            line: 0,
+            character: 5
+        }),
+        Some(TextSize::from(6))
+    );
+    assert_eq!(
+        mapper.offset(Position {
+            line: 0,
+            character: 7
+        }),
+        Some(TextSize::from(10))
+    );
+    assert_eq!(
+        mapper.offset(Position {
+            line: 1,
+            character: 0
+        }),
+        Some(TextSize::from(12))
+    );
+    assert_eq!(
+        mapper.position(TextSize::from(12)),
+        Some(Position {
+            line: 1,
+            character: 0
+        })
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn test_mapper_offset_clamped() {
+    let s1 = "a = '🦀'\nb = 1";
+
+    let mapper = Mapper::new_utf16(s1, false);
+
+    let offset = |line, character| mapper.offset_clamped(Position { line, character });
+
+    assert_eq!(offset(0, 3), TextSize::from(3));
+    // Within the crab.
+    assert_eq!(offset(0, 6), TextSize::from(5));
+    assert_eq!(offset(0, 100), TextSize::from(10));
+    assert_eq!(offset(1, 5), TextSize::from(16));
+    assert_eq!(offset(1, u64::MAX), TextSize::from(16));
+    assert_eq!(offset(5, 0), TextSize::from(16));
+    assert_eq!(mapper.offset(Position::new(0, 100)), None);
+    assert_eq!(mapper.offset(Position::new(5, 0)), None);
+}
+
+#[cfg(test)]
+#[test]
+fn test_mapper_edit() {
+    let mut source = String::from("a = 1\nb = 'é'\n\nc = '🦀'");
+    let mut mapper = Mapper::new_utf16(&source, false);
+
+    let edits = [
+        (0..0, "# comment\n"),
+        (14..16, "🦀\nd = 2"),
+        (3..12, ""),
+        (20..24, "x\ny\nz"),
+        (0..5, "é"),
+        (12..12, "\n"),
+    ];
+
+    for (range, text) in edits {
+        let edit = TextRange::new(
+            TextSize::from(range.start as u32),
+            TextSize::from(range.end as u32),
+        );
+        source.replace_range(range, text);
+        mapper.edit(&source, edit, TextSize::from(text.len() as u32));
+
+        let expected = Mapper::new_utf16(&source, false);
+        for offset in 0..=source.len() + 1 {
+            let offset = TextSize::from(offset as u32);
+            assert_eq!(mapper.position(offset), expected.position(offset));
+
+            if let Some(position) = expected.position(offset) {
+                assert_eq!(mapper.offset(position), expected.offset(position));
+            }
+        }
+        assert_eq!(mapper.line_count(), expected.line_count());
+        assert_eq!(mapper.all_range(), expected.all_range());
+    }
+}
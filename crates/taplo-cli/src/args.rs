@@ -75,6 +75,18 @@ pub enum TaploCommand {
     /// Extract a value from the given TOML document.
     Get(GetCommand),
 
+    /// Set values in TOML documents.
+    ///
+    /// Files are modified in-place unless the input comes from the standard input, in which case the result is printed to the standard output.
+    /// Comments and formatting are preserved.
+    Set(SetCommand),
+
+    /// Remove values from TOML documents.
+    ///
+    /// Files are modified in-place unless the input comes from the standard input, in which case the result is printed to the standard output.
+    /// Comments and formatting are preserved.
+    Del(DelCommand),
+
     /// Start a decoder for `toml-test` (https://github.com/BurntSushi/toml-test).
     #[cfg(feature = "toml-test")]
     TomlTest {},
@@ -175,6 +187,10 @@ pub struct LintCommand {
     #[clap(long)]
     pub no_schema: bool,
 
+    /// Do not warn about inconsistently quoted keys.
+    #[clap(long)]
+    pub no_key_warnings: bool,
+
     /// Paths or glob patterns to TOML documents.
     ///
     /// If the only argument is "-", the standard input will be used.
@@ -244,6 +260,76 @@ pub struct GetCommand {
     pub separator: Option<String>,
 }
 
+#[derive(Clone, Args)]
+pub struct SetCommand {
+    #[clap(flatten)]
+    pub general: GeneralArgs,
+
+    /// Parse the value as JSON instead of TOML.
+    #[clap(long)]
+    pub json: bool,
+
+    /// Dry-run and report any files that would be modified.
+    #[clap(long)]
+    pub check: bool,
+
+    /// Print the differences in patch formatting to `stdout`
+    ///
+    /// With the standard input, the differences are printed instead of the edited document.
+    #[clap(long)]
+    pub diff: bool,
+
+    /// A dotted key pattern to the values within the TOML documents.
+    ///
+    /// The pattern supports the same syntax as the `get` command,
+    /// all the matching values are replaced.
+    ///
+    /// If the pattern yielded no values and it does not contain
+    /// glob patterns, the value is added along with any missing tables.
+    pub pattern: String,
+
+    /// The new value as a TOML value (e.g. `'"1.0.0"'`, `true` or `'{ version = "1" }'`).
+    ///
+    /// If `--json` is specified, the value is a JSON value instead.
+    pub value: String,
+
+    /// Paths or glob patterns to TOML documents, at least one is required.
+    ///
+    /// If the only argument is "-", the standard input will be used.
+    #[clap(required = true)]
+    pub files: Vec<String>,
+}
+
+#[derive(Clone, Args)]
+pub struct DelCommand {
+    #[clap(flatten)]
+    pub general: GeneralArgs,
+
+    /// Dry-run and report any files that would be modified.
+    #[clap(long)]
+    pub check: bool,
+
+    /// Print the differences in patch formatting to `stdout`
+    ///
+    /// With the standard input, the differences are printed instead of the edited document.
+    #[clap(long)]
+    pub diff: bool,
+
+    /// A dotted key pattern to the values within the TOML documents.
+    ///
+    /// The pattern supports the same syntax as the `get` command,
+    /// all the matching values are removed.
+    ///
+    /// If the pattern yielded no values in a document, the operation will fail.
+    pub pattern: String,
+
+    /// Paths or glob patterns to TOML documents, at least one is required.
+    ///
+    /// If the only argument is "-", the standard input will be used.
+    #[clap(required = true)]
+    pub files: Vec<String>,
+}
+
 #[derive(Clone, Copy, ValueEnum)]
 pub enum OutputFormat {
     /// Extract the value outputting it in a text format.
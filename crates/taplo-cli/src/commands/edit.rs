@@ -0,0 +1,276 @@
+use std::path::Path;
+
+use crate::{
+    args::{DelCommand, GeneralArgs, SetCommand},
+    Taplo,
+};
+use anyhow::anyhow;
+use codespan_reporting::files::SimpleFile;
+use taplo::{
+    dom::{node::Key, rewrite::Rewrite, Keys, Node},
+    parser,
+};
+use taplo_common::{config::Config, environment::Environment};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// The options shared by the commands that edit documents.
+struct EditOptions<'a> {
+    general: &'a GeneralArgs,
+    files: Vec<String>,
+    check: bool,
+    diff: bool,
+}
+
+impl<E: Environment> Taplo<E> {
+    pub async fn execute_set(&mut self, cmd: SetCommand) -> Result<(), anyhow::Error> {
+        let value = if cmd.json {
+            let value: serde_json::Value =
+                serde_json::from_str(&cmd.value).map_err(|err| anyhow!("invalid JSON: {err}"))?;
+            json_to_toml(&value)?
+        } else {
+            cmd.value
+        };
+
+        let pattern = cmd.pattern.trim_start_matches('.');
+
+        self.edit_documents(
+            EditOptions {
+                general: &cmd.general,
+                files: cmd.files,
+                check: cmd.check,
+                diff: cmd.diff,
+            },
+            |_, rewrite| {
+                rewrite
+                    .set_value(pattern, &value)
+                    .map_err(|err| anyhow!("failed to set the value: {err}"))?;
+                Ok(())
+            },
+        )
+        .await
+    }
+
+    pub async fn execute_del(&mut self, cmd: DelCommand) -> Result<(), anyhow::Error> {
+        let pattern = cmd.pattern.trim_start_matches('.');
+
+        let keys = pattern
+            .parse::<Keys>()
+            .map_err(|err| anyhow!("invalid pattern: {err}"))?;
+
+        self.edit_documents(
+            EditOptions {
+                general: &cmd.general,
+                files: cmd.files,
+                check: cmd.check,
+                diff: cmd.diff,
+            },
+            |node, rewrite| {
+                let mut nodes = node
+                    .find_all_matches(keys.clone(), false)
+                    .map_err(|err| anyhow!("invalid pattern: {err}"))?;
+
+                if nodes.next().is_none() {
+                    return Err(anyhow!("no values matched the pattern"));
+                }
+
+                rewrite
+                    .remove(pattern)
+                    .map_err(|err| anyhow!("failed to remove the values: {err}"))?;
+                Ok(())
+            },
+        )
+        .await
+    }
+
+    async fn edit_documents(
+        &mut self,
+        opts: EditOptions<'_>,
+        edit: impl Fn(&Node, &mut Rewrite) -> Result<(), anyhow::Error>,
+    ) -> Result<(), anyhow::Error> {
+        let config = self.load_config(opts.general).await?;
+
+        if matches!(opts.files.first().map(|it| it.as_str()), Some("-")) {
+            let mut source = String::new();
+            self.env.stdin().read_to_string(&mut source).await?;
+
+            let edited = self
+                .edit_source(&config, Path::new("-"), &source, &edit)
+                .await?;
+
+            // The differences are printed instead of the edited document.
+            if opts.diff {
+                if source != edited {
+                    self.print_diff(Path::new("-"), &source, &edited).await?;
+                }
+            } else if !opts.check {
+                let mut stdout = self.env.stdout();
+                stdout.write_all(edited.as_bytes()).await?;
+                stdout.flush().await?;
+            }
+
+            if opts.check && source != edited {
+                return Err(anyhow!("the input would be modified"));
+            }
+
+            return Ok(());
+        }
+
+        let cwd = self
+            .env
+            .cwd_normalized()
+            .ok_or_else(|| anyhow!("could not figure the current working directory"))?;
+
+        let files = self
+            .collect_files(&cwd, &config, opts.files.into_iter())
+            .await?;
+
+        let mut result = Ok(());
+
+        for path in files {
+            let source = String::from_utf8(self.env.read_file(&path).await?)?;
+
+            let edited = match self.edit_source(&config, &path, &source, &edit).await {
+                Ok(edited) => edited,
+                Err(error) => {
+                    tracing::error!(%error, ?path, "failed to edit the file");
+                    result = Err(anyhow!("some files could not be edited"));
+                    continue;
+                }
+            };
+
+            if source != edited {
+                if opts.diff {
+                    if let Err(e) = self.print_diff(&path, &source, &edited).await {
+                        self.env
+                            .stderr()
+                            .write_all(
+                                format!("Failed to write diff to stdout: {:?}", e)
+                                    .as_str()
+                                    .as_bytes(),
+                            )
+                            .await?;
+                    }
+                }
+
+                if opts.check {
+                    tracing::error!(?path, "the file would be modified");
+                    result = Err(anyhow!("some files would be modified"));
+                } else {
+                    self.env.write_file(&path, edited.as_bytes()).await?;
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Applies the edit to the source, documents with errors are never edited.
+    async fn edit_source(
+        &self,
+        config: &Config,
+        path: &Path,
+        source: &str,
+        edit: &impl Fn(&Node, &mut Rewrite) -> Result<(), anyhow::Error>,
+    ) -> Result<String, anyhow::Error> {
+        let file_path = path.to_string_lossy();
+
+        let parse = parser::parse_with(source, config.parse_options(path));
+
+        self.print_parse_errors(&SimpleFile::new(&file_path, source), &parse.errors)
+            .await?;
+
+        if !parse.errors.is_empty() {
+            return Err(anyhow!("syntax errors found"));
+        }
+
+        let node = parse.into_dom();
+
+        if let Err(errors) = node.validate() {
+            self.print_semantic_errors(&SimpleFile::new(&file_path, source), errors)
+                .await?;
+
+            return Err(anyhow!("semantic errors found"));
+        }
+
+        let mut rewrite = Rewrite::new(node.clone()).map_err(|err| anyhow!("{err}"))?;
+        edit(&node, &mut rewrite)?;
+        Ok(rewrite.apply())
+    }
+}
+
+/// Converts a JSON value into an inline TOML value.
+fn json_to_toml(value: &serde_json::Value) -> Result<String, anyhow::Error> {
+    Ok(match value {
+        serde_json::Value::Null => return Err(anyhow!("null values are not supported in TOML")),
+        serde_json::Value::Bool(b) => b.to_string(),
+        serde_json::Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                i.to_string()
+            } else if let Some(f) = n.as_f64().filter(|_| n.is_f64()) {
+                Node::from(f).to_toml(true, false)
+            } else {
+                return Err(anyhow!("the number {n} is out of range for TOML"));
+            }
+        }
+        serde_json::Value::String(s) => Node::from(s.as_str()).to_toml(true, false),
+        serde_json::Value::Array(items) => {
+            let items = items
+                .iter()
+                .map(json_to_toml)
+                .collect::<Result<Vec<_>, _>>()?;
+            format!("[{}]", items.join(", "))
+        }
+        serde_json::Value::Object(entries) => {
+            if entries.is_empty() {
+                return Ok("{}".into());
+            }
+
+            let entries = entries
+                .iter()
+                .map(|(key, value)| Ok(format!("{} = {}", Key::new(key), json_to_toml(value)?)))
+                .collect::<Result<Vec<_>, anyhow::Error>>()?;
+            format!("{{ {} }}", entries.join(", "))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::json_to_toml;
+    use serde_json::json;
+
+    #[test]
+    fn json_scalars_to_toml() {
+        assert_eq!(json_to_toml(&json!(true)).unwrap(), "true");
+        assert_eq!(json_to_toml(&json!(-42)).unwrap(), "-42");
+        assert_eq!(json_to_toml(&json!(1.5)).unwrap(), "1.5");
+        assert_eq!(json_to_toml(&json!(2.0)).unwrap(), "2.0");
+        assert_eq!(json_to_toml(&json!(1e20)).unwrap(), "1e20");
+        assert_eq!(json_to_toml(&json!(-2.5e-10)).unwrap(), "-2.5e-10");
+        assert_eq!(
+            json_to_toml(&json!("a \"quoted\"\nline")).unwrap(),
+            r#""a \"quoted\"\nline""#
+        );
+    }
+
+    #[test]
+    fn json_collections_to_toml() {
+        assert_eq!(json_to_toml(&json!([])).unwrap(), "[]");
+        assert_eq!(json_to_toml(&json!({})).unwrap(), "{}");
+        assert_eq!(
+            json_to_toml(&json!([1, "two", [false]])).unwrap(),
+            r#"[1, "two", [false]]"#
+        );
+        assert_eq!(
+            json_to_toml(&json!({ "a.b": { "c": [1] }, "d": "e" })).unwrap(),
+            r#"{ 'a.b' = { c = [1] }, d = "e" }"#
+        );
+    }
+
+    #[test]
+    fn json_unsupported_values() {
+        assert!(json_to_toml(&json!(null)).is_err());
+        assert!(json_to_toml(&json!([1, null])).is_err());
+        assert!(json_to_toml(&json!(u64::MAX)).is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use std::{
     mem,
+    ops::Range,
     path::{Path, PathBuf},
 };
 
@@ -7,12 +8,25 @@ use crate::{args::FormatCommand, Taplo};
 use anyhow::anyhow;
 use codespan_reporting::files::SimpleFile;
 
-use taplo::{formatter, parser};
+use taplo::{
+    dom::Node,
+    formatter::{
+        self,
+        diff::{Change, Diff},
+        KeyOrder,
+    },
+    parser::{self, ParseOptions},
+};
 use taplo_common::{config::Config, environment::Environment, util::Normalize};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use url::Url;
 
 impl<E: Environment> Taplo<E> {
     pub async fn execute_format(&mut self, cmd: FormatCommand) -> Result<(), anyhow::Error> {
+        self.schemas
+            .cache()
+            .set_cache_path(cmd.general.cache_path.clone());
+
         if matches!(cmd.files.first().map(|it| it.as_str()), Some("-")) {
             self.format_stdin(cmd).await
         } else {
@@ -26,6 +40,7 @@ impl<E: Environment> Taplo<E> {
         self.env.stdin().read_to_string(&mut source).await?;
 
         let config = self.load_config(&cmd.general).await?;
+        self.schemas.associations().add_from_config(&config);
         let display_path = match cmd.stdin_filepath.as_deref() {
             Some(filepath) if self.env.is_absolute(filepath.as_ref()) => {
                 PathBuf::from(filepath).normalize()
@@ -39,7 +54,14 @@ impl<E: Environment> Taplo<E> {
             }
             None => PathBuf::from("-"),
         };
-        let p = parser::parse(&source);
+        let format_opts = self.format_options(&config, &cmd, &display_path)?;
+
+        let p = parser::parse_with(
+            &source,
+            ParseOptions {
+                toml_version: format_opts.toml_version,
+            },
+        );
 
         if !p.errors.is_empty() {
             self.print_parse_errors(
@@ -53,17 +75,20 @@ impl<E: Environment> Taplo<E> {
             }
         }
 
-        let format_opts = self.format_options(&config, &cmd, &display_path)?;
-
         let error_ranges = p.errors.iter().map(|e| e.range).collect::<Vec<_>>();
 
         let dom = p.into_dom();
 
-        let formatted = formatter::format_with_path_scopes(
+        let key_order = self
+            .key_order(&config, &format_opts, &display_path, &dom)
+            .await;
+
+        let formatted = formatter::format_with_key_order(
             dom,
             format_opts,
             &error_ranges,
             config.format_scopes(&display_path),
+            key_order,
         )
         .map_err(|err| anyhow!("invalid key pattern: {err}"))?;
 
@@ -80,24 +105,14 @@ impl<E: Environment> Taplo<E> {
         Ok(())
     }
 
-    #[cfg(target_arch = "wasm32")]
-    async fn print_diff(
-        &self,
-        _path: impl AsRef<Path>,
-        _original: &str,
-        _formatted: &str,
-    ) -> Result<(), anyhow::Error> {
-        tracing::warn!("the `--diff` flag is not available in this build yet");
-        Ok(())
-    }
-
-    #[cfg(not(target_arch = "wasm32"))]
-    async fn print_diff(
+    pub(crate) async fn print_diff(
         &self,
         path: impl AsRef<Path>,
         original: &str,
         formatted: &str,
     ) -> Result<(), anyhow::Error> {
+        use ansi_term::Colour::{Green, Red};
+
         let path = path.as_ref();
         let mut stdout = self.env.stdout();
 
@@ -109,6 +124,12 @@ impl<E: Environment> Taplo<E> {
             }
         }
 
+        let diff = Diff::new(original, formatted);
+
+        if diff.is_empty() {
+            return Ok(());
+        }
+
         echo!("diff a/{path} b/{path}", path = path.display());
         echo!("--- a/{path}", path = path.display());
         echo!("+++ b/{path}", path = path.display());
@@ -116,84 +137,49 @@ impl<E: Environment> Taplo<E> {
         // How many lines of context to print:
         const CONTEXT_LINES: usize = 7;
 
-        let hunks = prettydiff::diff_lines(original, formatted);
-        let hunks = hunks.diff();
-        let hunkcount = hunks.len();
-        let mut acc = Vec::<String>::with_capacity(hunkcount);
-
-        let mut pre_line = 0_usize;
-        let mut post_line = 0_usize;
-
-        for (idx, diff_op) in hunks.into_iter().enumerate() {
-            use ansi_term::Colour::{self, Green, Red};
-            use prettydiff::basic::DiffOp;
-
-            // apply the given color and prefix to the set of strings `s`
-            fn apply_color<'a>(
-                s: &'a [&'a str],
-                prefix: &'a str,
-                color: Colour,
-            ) -> impl IntoIterator<Item = String> + 'a {
-                s.iter()
-                    .map(move |&s| color.paint(prefix.to_owned() + s).to_string())
-            }
+        // Hunk ranges are 1-based, empty ones point to the line before.
+        fn hunk_range(lines: &Range<usize>) -> String {
+            let start = if lines.is_empty() {
+                lines.start
+            } else {
+                lines.start + 1
+            };
+            format!("{},{}", start, lines.len())
+        }
 
-            let mut pre_length = 0_usize;
-            let mut post_length = 0_usize;
-
-            // length of a net diff op
-            match diff_op {
-                DiffOp::Equal(slices) => {
-                    if slices.len() < CONTEXT_LINES * 2 && idx > 0 && idx + 1 < hunkcount {
-                        acc.extend(slices[..].iter().map(|&s| s.to_owned()));
-                        pre_length += slices.len();
-                        post_length += slices.len();
-                    } else {
-                        if idx > 0 {
-                            let end = usize::min(CONTEXT_LINES, slices.len());
-                            acc.extend(slices[0..end].iter().map(|&s| s.to_owned()));
-                            pre_length += end;
-                            post_length += end;
-                        }
-                        // context before the hunk within the file
-
-                        // context after the hunk within the file
-                        if idx + 1 < hunkcount {
-                            let skip = slices.len().saturating_sub(CONTEXT_LINES);
-                            acc.extend(slices[skip..].iter().map(|&s| s.to_owned()));
-                            let delta = slices.len().saturating_sub(skip);
-                            pre_length += delta;
-                            post_length += delta;
-                        }
-                    }
-                }
-                DiffOp::Insert(ins) => {
-                    acc.extend(apply_color(ins, "+", Green));
-                    post_length += ins.len();
+        for hunk in diff.hunks(CONTEXT_LINES) {
+            echo!(
+                "@@ -{} +{} @@",
+                hunk_range(&hunk.original),
+                hunk_range(&hunk.modified)
+            );
+
+            let mut line = hunk.original.start;
+
+            for change in hunk.changes.iter().chain(Some(&Change {
+                original: hunk.original.end..hunk.original.end,
+                modified: hunk.modified.end..hunk.modified.end,
+            })) {
+                for equal in &diff.original[line..change.original.start] {
+                    echo!(" {}", equal.trim_end_matches(['\r', '\n']));
                 }
-                DiffOp::Remove(rem) => {
-                    acc.extend(apply_color(rem, "-", Red));
-                    pre_length += rem.len();
+
+                for removed in &diff.original[change.original.clone()] {
+                    echo!(
+                        "{}",
+                        Red.paint(format!("-{}", removed.trim_end_matches(['\r', '\n'])))
+                    );
                 }
-                DiffOp::Replace(rem, ins) => {
-                    acc.extend(apply_color(rem, "-", Red));
-                    acc.extend(apply_color(ins, "+", Green));
-                    pre_length += rem.len();
-                    post_length += ins.len();
+
+                for inserted in &diff.modified[change.modified.clone()] {
+                    echo!(
+                        "{}",
+                        Green.paint(format!("+{}", inserted.trim_end_matches(['\r', '\n'])))
+                    );
                 }
-            };
-            echo!(
-                "@@ -{},{} +{},{} @@",
-                pre_line,
-                pre_length,
-                post_line,
-                post_length
-            );
-            echo!("{}", acc.join("\n"));
 
-            pre_line += pre_length;
-            post_line += post_length;
-            acc.clear();
+                line = change.original.end;
+            }
         }
 
         stdout.flush().await?;
@@ -207,6 +193,7 @@ impl<E: Environment> Taplo<E> {
         }
 
         let config = self.load_config(&cmd.general).await?;
+        self.schemas.associations().add_from_config(&config);
 
         let cwd = self
             .env
@@ -225,7 +212,12 @@ impl<E: Environment> Taplo<E> {
             let f = self.env.read_file(&path).await?;
             let source = String::from_utf8_lossy(&f).into_owned();
 
-            let p = parser::parse(&source);
+            let p = parser::parse_with(
+                &source,
+                ParseOptions {
+                    toml_version: format_opts.toml_version,
+                },
+            );
 
             if !p.errors.is_empty() {
                 self.print_parse_errors(
@@ -246,11 +238,14 @@ impl<E: Environment> Taplo<E> {
 
             let dom = p.into_dom();
 
-            let formatted = formatter::format_with_path_scopes(
+            let key_order = self.key_order(&config, &format_opts, &path, &dom).await;
+
+            let formatted = formatter::format_with_key_order(
                 dom,
                 format_opts,
                 &error_ranges,
                 config.format_scopes(&path),
+                key_order,
             )
             .map_err(|err| anyhow!("invalid key pattern: {err}"))?;
 
@@ -280,6 +275,50 @@ impl<E: Environment> Taplo<E> {
         result
     }
 
+    /// The key order declared by the schema associated with the file
+    /// if the formatter reorders keys by schema anywhere in it.
+    async fn key_order(
+        &self,
+        config: &Config,
+        format_opts: &formatter::Options,
+        path: &Path,
+        dom: &Node,
+    ) -> KeyOrder {
+        let reorder = format_opts.reorder_by_schema
+            || config
+                .format_scopes(path)
+                .any(|(_, opts)| opts.reorder_by_schema == Some(true));
+
+        if !reorder || !config.is_schema_enabled(path) {
+            return KeyOrder::default();
+        }
+
+        let file_uri: Url = match format!("file://{}", path.display()).parse() {
+            Ok(uri) => uri,
+            Err(error) => {
+                tracing::warn!(%error, ?path, "invalid file path");
+                return KeyOrder::default();
+            }
+        };
+
+        self.schemas
+            .associations()
+            .add_from_document(&file_uri, dom);
+
+        let Some(schema_association) = self.schemas.associations().association_for(&file_uri)
+        else {
+            return KeyOrder::default();
+        };
+
+        match self.schemas.key_order(&schema_association.url, dom).await {
+            Ok(key_order) => key_order,
+            Err(error) => {
+                tracing::warn!(%error, ?path, "failed to get key order from schema");
+                KeyOrder::default()
+            }
+        }
+    }
+
     fn format_options(
         &self,
         config: &Config,
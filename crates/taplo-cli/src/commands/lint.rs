@@ -61,10 +61,10 @@ impl<E: Environment> Taplo<E> {
     }
 
     #[tracing::instrument(skip_all)]
-    async fn lint_stdin(&self, _cmd: LintCommand) -> Result<(), anyhow::Error> {
+    async fn lint_stdin(&self, cmd: LintCommand) -> Result<(), anyhow::Error> {
         let mut source = String::new();
         self.env.stdin().read_to_string(&mut source).await?;
-        self.lint_source("-", &source).await
+        self.lint_source("-", &source, !cmd.no_key_warnings).await
     }
 
     #[tracing::instrument(skip_all)]
@@ -76,6 +76,8 @@ impl<E: Environment> Taplo<E> {
             .cwd_normalized()
             .ok_or_else(|| anyhow!("could not figure the current working directory"))?;
 
+        let key_warnings = !cmd.no_key_warnings;
+
         let files = self
             .collect_files(&cwd, &config, cmd.files.into_iter())
             .await?;
@@ -83,7 +85,7 @@ impl<E: Environment> Taplo<E> {
         let mut result = Ok(());
 
         for file in files {
-            if let Err(error) = self.lint_file(&file).await {
+            if let Err(error) = self.lint_file(&file, key_warnings).await {
                 tracing::error!(%error, path = ?file, "invalid file");
                 result = Err(anyhow!("some files were not valid"));
             }
@@ -92,14 +94,26 @@ impl<E: Environment> Taplo<E> {
         result
     }
 
-    async fn lint_file(&self, file: &Path) -> Result<(), anyhow::Error> {
+    async fn lint_file(&self, file: &Path, key_warnings: bool) -> Result<(), anyhow::Error> {
         let source = self.env.read_file(file).await?;
         let source = String::from_utf8(source)?;
-        self.lint_source(&file.to_string_lossy(), &source).await
+        self.lint_source(&file.to_string_lossy(), &source, key_warnings)
+            .await
     }
 
-    async fn lint_source(&self, file_path: &str, source: &str) -> Result<(), anyhow::Error> {
-        let parse = parser::parse(source);
+    async fn lint_source(
+        &self,
+        file_path: &str,
+        source: &str,
+        key_warnings: bool,
+    ) -> Result<(), anyhow::Error> {
+        let parse_options = self
+            .config
+            .as_ref()
+            .map(|config| config.parse_options(Path::new(file_path)))
+            .unwrap_or_default();
+
+        let parse = parser::parse_with(source, parse_options);
 
         self.print_parse_errors(&SimpleFile::new(file_path, source), &parse.errors)
             .await?;
@@ -117,6 +131,15 @@ impl<E: Environment> Taplo<E> {
             return Err(anyhow!("semantic errors found"));
         }
 
+        // Inconsistently quoted keys are only warned about.
+        if key_warnings {
+            self.print_key_warnings(
+                &SimpleFile::new(file_path, source),
+                &dom.inconsistent_keys(),
+            )
+            .await?;
+        }
+
         let config = self.config.as_ref().unwrap();
 
         if !config.is_schema_enabled(Path::new(file_path)) {
@@ -6,6 +6,7 @@ use crate::{
 };
 
 mod config;
+mod edit;
 mod format;
 #[cfg(feature = "lint")]
 mod lint;
@@ -44,6 +45,8 @@ impl<E: Environment> Taplo<E> {
             TaploCommand::Config { cmd } => self.execute_config(cmd).await,
             TaploCommand::Format(fmt) => self.execute_format(fmt).await,
             TaploCommand::Get(cmd) => self.execute_get(cmd).await,
+            TaploCommand::Set(cmd) => self.execute_set(cmd).await,
+            TaploCommand::Del(cmd) => self.execute_del(cmd).await,
             #[cfg(feature = "lint")]
             TaploCommand::Lint(cmd) => self.execute_lint(cmd).await,
             #[cfg(feature = "lsp")]
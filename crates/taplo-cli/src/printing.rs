@@ -115,6 +115,39 @@ impl<E: Environment> Taplo<E> {
         Ok(())
     }
 
+    pub(crate) async fn print_key_warnings(
+        &self,
+        file: &SimpleFile<&str, &str>,
+        keys: &[dom::node::InconsistentKey],
+    ) -> Result<(), anyhow::Error> {
+        let mut out_diag = Vec::<u8>::new();
+
+        let config = codespan_reporting::term::Config::default();
+
+        for key in keys {
+            let diag = Diagnostic::warning()
+                .with_message("inconsistent key quotes")
+                .with_labels(Vec::from([
+                    Label::primary((), std_range(key.range))
+                        .with_message(format!("quoted differently than `{}`", key.first_text)),
+                    Label::secondary((), std_range(key.first_range))
+                        .with_message("first written here"),
+                ]));
+
+            if self.colors {
+                term::emit(&mut Ansi::new(&mut out_diag), &config, file, &diag)?;
+            } else {
+                term::emit(&mut NoColor::new(&mut out_diag), &config, file, &diag)?;
+            }
+        }
+
+        let mut stderr = self.env.stderr();
+        stderr.write_all(&out_diag).await?;
+        stderr.flush().await?;
+
+        Ok(())
+    }
+
     #[cfg(feature = "lint")]
     pub(crate) async fn print_schema_errors(
         &self,
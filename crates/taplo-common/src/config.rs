@@ -7,7 +7,10 @@ use anyhow::Context;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use taplo::formatter;
+use taplo::{
+    formatter,
+    parser::{ParseOptions, TomlVersion},
+};
 use url::Url;
 
 use crate::{
@@ -124,6 +127,24 @@ impl Config {
                 }
             }
         }
+
+        options.toml_version = self.parse_options(path).toml_version;
+    }
+
+    /// Parser options for the document at the given path.
+    #[must_use]
+    pub fn parse_options(&self, path: &Path) -> ParseOptions {
+        let mut toml_version = self.global_options.toml_version.unwrap_or_default();
+
+        for rule in self.rules_for(path) {
+            if rule.keys.is_none() {
+                if let Some(v) = rule.options.toml_version {
+                    toml_version = v;
+                }
+            }
+        }
+
+        ParseOptions { toml_version }
     }
 
     pub fn format_scopes<'s>(
@@ -232,6 +253,10 @@ impl Config {
 #[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Options {
+    /// The TOML version of the documents, either `"1.0"` or `"1.1"`.
+    ///
+    /// Defaults to `"1.0"`.
+    pub toml_version: Option<TomlVersion>,
     /// Schema validation options.
     pub schema: Option<SchemaOptions>,
     /// Formatting options.
@@ -45,8 +45,13 @@ pub trait Environment: Clone + Send + Sync + 'static {
 
     async fn write_file(&self, path: &Path, bytes: &[u8]) -> Result<(), anyhow::Error>;
 
+    async fn remove_file(&self, path: &Path) -> Result<(), anyhow::Error>;
+
     fn to_file_path(&self, url: &Url) -> Option<PathBuf>;
 
+    /// The `file://` URL of an absolute path.
+    fn to_file_url(&self, path: &Path) -> Option<Url>;
+
     fn is_absolute(&self, path: &Path) -> bool;
 
     /// Absolute current working dir.
@@ -95,10 +95,18 @@ impl Environment for NativeEnvironment {
         Ok(tokio::fs::write(path, bytes).await?)
     }
 
+    async fn remove_file(&self, path: &Path) -> Result<(), anyhow::Error> {
+        Ok(tokio::fs::remove_file(path).await?)
+    }
+
     fn to_file_path(&self, url: &url::Url) -> Option<std::path::PathBuf> {
         url.to_file_path().ok()
     }
 
+    fn to_file_url(&self, path: &Path) -> Option<url::Url> {
+        url::Url::from_file_path(path).ok()
+    }
+
     fn is_absolute(&self, base: &std::path::Path) -> bool {
         base.is_absolute()
     }
@@ -22,6 +22,7 @@ pub mod schema;
 pub mod util;
 
 pub type HashMap<K, V> = std::collections::HashMap<K, V, ahash::RandomState>;
+pub type HashSet<K> = std::collections::HashSet<K, ahash::RandomState>;
 pub type IndexMap<K, V> = indexmap::IndexMap<K, V, ahash::RandomState>;
 
 pub type AsyncMutex<T> = tokio::sync::Mutex<T>;
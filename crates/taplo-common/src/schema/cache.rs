@@ -4,7 +4,12 @@ use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use sha1::{Digest, Sha1};
-use std::{num::NonZeroUsize, path::PathBuf, sync::Arc, time::Duration};
+use std::{
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 use time::OffsetDateTime;
 use url::Url;
 
@@ -19,7 +24,8 @@ pub struct Cache<E: Environment> {
     expiration_times: Arc<ArcSwap<(Duration, Duration)>>,
     lru_expires_by: Arc<Mutex<OffsetDateTime>>,
     schemas: Arc<Mutex<LruCache<Url, Arc<Value>>>>,
-    cache_path: Arc<ArcSwap<Option<PathBuf>>>,
+    /// The directory of the cached files.
+    path: Arc<ArcSwap<Option<PathBuf>>>,
 }
 
 impl<E: Environment> Cache<E> {
@@ -35,7 +41,7 @@ impl<E: Environment> Cache<E> {
                 NonZeroUsize::new(10).unwrap(),
                 ahash::RandomState::new(),
             ))),
-            cache_path: Default::default(),
+            path: Default::default(),
         }
     }
 
@@ -48,7 +54,7 @@ impl<E: Environment> Cache<E> {
     }
 
     pub fn set_cache_path(&self, path: Option<PathBuf>) {
-        self.cache_path.swap(Arc::new(path));
+        self.path.swap(Arc::new(path));
     }
 
     pub async fn load(
@@ -67,7 +73,7 @@ impl<E: Environment> Cache<E> {
             return Ok(s.clone());
         }
 
-        match &**self.cache_path.load() {
+        match &**self.path.load() {
             Some(cache_path) => {
                 let file_name = cache_hash(value_url);
                 let p = cache_path.join(file_name);
@@ -93,7 +99,7 @@ impl<E: Environment> Cache<E> {
     pub async fn save(&self, url: Url, value: Arc<Value>) -> Result<(), anyhow::Error> {
         let expires_by = self.env.now() + self.expiration_times.load().1;
 
-        match &**self.cache_path.load() {
+        match &**self.path.load() {
             Some(cache_path) => {
                 let file_name = cache_hash(&url);
                 let p = cache_path.join(file_name);
@@ -109,8 +115,50 @@ impl<E: Environment> Cache<E> {
         }
     }
 
+    /// The path of the file the value at the URL is cached in on disk,
+    /// the value itself is stored in its `value` field.
+    pub fn cache_file_path(&self, url: &Url) -> Option<PathBuf> {
+        self.path
+            .load()
+            .as_ref()
+            .as_ref()
+            .map(|cache_path| cache_path.join(cache_hash(url)))
+    }
+
+    /// Removes every value from the in-memory cache.
+    pub fn clear_memory(&self) {
+        self.schemas.lock().clear();
+    }
+
+    /// Removes every value from both the in-memory and the disk cache.
+    pub async fn clear(&self) -> Result<(), anyhow::Error> {
+        self.clear_memory();
+
+        let Some(cache_path) = (**self.path.load()).clone() else {
+            return Ok(());
+        };
+
+        let pattern = Path::new(&glob::Pattern::escape(&cache_path.to_string_lossy())).join("*");
+
+        for path in self.env.glob_files(&pattern.to_string_lossy())? {
+            // Other files might share the directory with the cache.
+            let is_cache_file =
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| {
+                        name.len() == 40 && name.bytes().all(|b| b.is_ascii_hexdigit())
+                    });
+
+            if is_cache_file {
+                self.env.remove_file(&path).await?;
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn is_cache_path_set(&self) -> bool {
-        self.cache_path.load().is_some()
+        self.path.load().is_some()
     }
 
     pub fn set_expiration_times(&self, mem: Duration, disk: Duration) {
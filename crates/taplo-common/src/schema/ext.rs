@@ -11,6 +11,7 @@ pub struct TaploSchemaExt {
     pub links: Option<ExtLinks>,
     pub docs: Option<ExtDocs>,
     pub init_keys: Option<Vec<String>>,
+    pub key_order: Option<Vec<String>>,
     #[serde(default)]
     pub plugins: Vec<String>,
 }
@@ -1,4 +1,6 @@
-use self::{associations::SchemaAssociations, builtins::builtin_schema, cache::Cache};
+use self::{
+    associations::SchemaAssociations, builtins::builtin_schema, cache::Cache, ext::schema_ext_of,
+};
 use crate::{environment::Environment, util::ArcHashValue, LruCache};
 use anyhow::{anyhow, Context};
 use async_recursion::async_recursion;
@@ -7,11 +9,17 @@ use itertools::Itertools;
 use json_value_merge::Merge;
 use jsonschema::{error::ValidationErrorKind, JSONSchema, SchemaResolver, ValidationError};
 use parking_lot::Mutex;
+use percent_encoding::percent_decode_str;
 use regex::Regex;
 use serde_json::Value;
 use std::{borrow::Cow, num::NonZeroUsize, sync::Arc};
 use taplo::{
-    dom::{self, node::Key, KeyOrIndex, Keys},
+    dom::{
+        self,
+        node::{Key, TableKind},
+        KeyOrIndex, Keys,
+    },
+    formatter::KeyOrder,
     rowan::TextRange,
 };
 use thiserror::Error;
@@ -183,6 +191,19 @@ impl<E: Environment> Schemas<E> {
         }
     }
 
+    /// Forgets the schemas and validators kept in memory,
+    /// schemas cached on disk are still used.
+    pub fn clear_memory_cache(&self) {
+        self.validators.lock().clear();
+        self.cache.clear_memory();
+    }
+
+    /// Forgets every schema and validator, including the schemas cached on disk.
+    pub async fn clear_cache(&self) -> Result<(), anyhow::Error> {
+        self.validators.lock().clear();
+        self.cache.clear().await
+    }
+
     pub async fn add_schema(&self, schema_url: &Url, schema: Arc<Value>) {
         drop(self.cache.store(schema_url.clone(), schema).await);
     }
@@ -322,6 +343,49 @@ impl<E: Environment> Schemas<E> {
         Ok(schemas)
     }
 
+    /// The key orders of the tables in the document declared
+    /// with the `keyOrder` extension of their schemas.
+    pub async fn key_order(
+        &self,
+        schema_url: &Url,
+        root: &dom::Node,
+    ) -> Result<KeyOrder, anyhow::Error> {
+        let value = serde_json::to_value(root)?;
+
+        let tables = std::iter::once((Keys::empty(), root.clone()))
+            .chain(root.flat_iter())
+            .filter(|(_, node)| {
+                matches!(node, dom::Node::Table(table) if table.kind() != TableKind::Inline)
+            });
+
+        let mut key_order = KeyOrder::new();
+
+        for (keys, _) in tables {
+            let path: Vec<String> = keys
+                .iter()
+                .filter_map(KeyOrIndex::as_key)
+                .map(|key| key.value().to_string())
+                .collect();
+
+            // The tables of arrays of tables share their schemas.
+            if key_order.get(&path).is_some() {
+                continue;
+            }
+
+            let order = self
+                .schemas_at_path(schema_url, &value, &keys)
+                .await?
+                .into_iter()
+                .find_map(|(_, schema)| schema_ext_of(&schema)?.key_order);
+
+            if let Some(order) = order {
+                key_order.insert(path, order);
+            }
+        }
+
+        Ok(key_order)
+    }
+
     #[tracing::instrument(skip_all, fields(%path))]
     #[async_recursion(?Send)]
     #[must_use]
@@ -458,6 +522,127 @@ impl<E: Environment> Schemas<E> {
         Ok(())
     }
 
+    /// Returns the locations of the schemas at the given path in the schema documents
+    /// they are defined in, following references across documents.
+    ///
+    /// The schemas are collected the same way as [`Schemas::schemas_at_path`] does.
+    #[tracing::instrument(skip_all, fields(%schema_url, %path))]
+    pub async fn schema_locations_at_path(
+        &self,
+        schema_url: &Url,
+        path: &Keys,
+    ) -> Result<Vec<SchemaLocation>, anyhow::Error> {
+        let mut locations = Vec::new();
+        self.collect_schema_locations(SchemaLocation::from_url(schema_url), path, &mut locations)
+            .await?;
+        Ok(locations.into_iter().unique().collect())
+    }
+
+    #[tracing::instrument(skip_all, fields(%path))]
+    #[async_recursion(?Send)]
+    #[must_use]
+    async fn collect_schema_locations(
+        &self,
+        location: SchemaLocation,
+        path: &Keys,
+        locations: &mut Vec<SchemaLocation>,
+    ) -> Result<(), anyhow::Error> {
+        let document = self.resolve_schema(location.url.clone()).await?;
+
+        let Some(schema) = document.pointer(&location.pointer) else {
+            return Ok(());
+        };
+
+        if !schema.is_object() {
+            return Ok(());
+        }
+
+        if let Some(r) = schema.schema_ref() {
+            let url = reference_url(&location.url, r)
+                .ok_or_else(|| anyhow!("could not determine schema URL"))?;
+            return self
+                .collect_schema_locations(SchemaLocation::from_url(&url), path, locations)
+                .await;
+        }
+
+        for keyword in ["oneOf", "anyOf", "allOf"] {
+            if let Some(schemas) = schema[keyword].as_array() {
+                for idx in 0..schemas.len() {
+                    self.collect_schema_locations(
+                        location.join(keyword).join(&idx.to_string()),
+                        path,
+                        locations,
+                    )
+                    .await?;
+                }
+            }
+        }
+
+        let include_self = schema["allOf"].is_null();
+
+        let Some(key) = path.iter().next() else {
+            if include_self {
+                locations.push(location);
+            }
+            return Ok(());
+        };
+
+        let child_path = path.skip_left(1);
+
+        match key {
+            KeyOrIndex::Key(k) => {
+                // For array of tables.
+                self.collect_schema_locations(
+                    location.join("items").join(k.value()),
+                    &child_path,
+                    locations,
+                )
+                .await?;
+
+                self.collect_schema_locations(
+                    location.join("properties").join(k.value()),
+                    &child_path,
+                    locations,
+                )
+                .await?;
+
+                self.collect_schema_locations(
+                    location.join("additionalProperties"),
+                    &child_path,
+                    locations,
+                )
+                .await?;
+
+                if let Some(pattern_props) = schema["patternProperties"].as_object() {
+                    for pattern in pattern_props.keys() {
+                        if let Ok(re) = Regex::new(pattern) {
+                            if re.is_match(k.value()) {
+                                self.collect_schema_locations(
+                                    location.join("patternProperties").join(pattern),
+                                    &child_path,
+                                    locations,
+                                )
+                                .await?;
+                            }
+                        }
+                    }
+                }
+            }
+            KeyOrIndex::Index(idx) => {
+                let location = if schema["items"].is_array() {
+                    location.join("items").join(&idx.to_string())
+                } else {
+                    location.join("items")
+                };
+
+                self.collect_schema_locations(location, &child_path, locations)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
     #[tracing::instrument(skip_all, fields(%schema_url, %path))]
     pub async fn possible_schemas_from(
         &self,
@@ -625,14 +810,61 @@ impl<E: Environment> Schemas<E> {
 }
 
 fn reference_url(root_url: &Url, reference: &str) -> Option<Url> {
-    if !reference.starts_with('#') {
-        return Url::parse(reference).ok();
-    }
-    let mut url = root_url.clone();
-    url.set_fragment(Some(reference.trim_start_matches("#/")));
+    let mut url = if reference.starts_with('#') {
+        root_url.clone()
+    } else {
+        Url::parse(reference)
+            .or_else(|_| root_url.join(reference))
+            .ok()?
+    };
+
+    // Fragments are stored without the leading `/` of the JSON pointer.
+    let fragment = reference
+        .split_once('#')
+        .map(|(_, fragment)| fragment.trim_start_matches('/'))
+        .filter(|fragment| !fragment.is_empty());
+    url.set_fragment(fragment);
+
     Some(url)
 }
 
+/// The location of a schema inside a schema document.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SchemaLocation {
+    /// The URL of the schema document without a fragment.
+    pub url: Url,
+    /// JSON pointer to the schema in the document.
+    pub pointer: String,
+}
+
+impl SchemaLocation {
+    fn from_url(url: &Url) -> Self {
+        let mut document_url = url.clone();
+        document_url.set_fragment(None);
+
+        Self {
+            url: document_url,
+            pointer: url
+                .fragment()
+                .map(|fragment| {
+                    String::from("/") + &percent_decode_str(fragment).decode_utf8_lossy()
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    fn join(&self, token: &str) -> Self {
+        Self {
+            url: self.url.clone(),
+            pointer: format!(
+                "{}/{}",
+                self.pointer,
+                token.replace('~', "~0").replace('/', "~1")
+            ),
+        }
+    }
+}
+
 pub trait ValueExt {
     fn is_schema_ref(&self) -> bool;
     fn schema_ref(&self) -> Option<&str>;
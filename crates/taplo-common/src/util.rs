@@ -4,6 +4,7 @@ use serde_json::Value;
 use std::{
     borrow::Cow,
     hash::{Hash, Hasher},
+    ops::Range,
     path::{Path, PathBuf},
     sync::Arc,
 };
@@ -108,7 +109,7 @@ impl Normalize for PathBuf {
     }
 }
 
-pub(crate) fn normalize_str(s: &str) -> Cow<str> {
+pub(crate) fn normalize_str(s: &str) -> Cow<'_, str> {
     let Some(percent_decoded) = percent_decode_str(s).decode_utf8().ok() else {
         return s.into();
     };
@@ -120,6 +121,136 @@ pub(crate) fn normalize_str(s: &str) -> Cow<str> {
     }
 }
 
+/// Returns the byte range of the value a JSON pointer refers to in the given JSON text.
+///
+/// The text is expected to be valid JSON, `None` is returned if the pointer
+/// does not point to an existing value.
+#[must_use]
+pub fn json_pointer_range(json: &str, pointer: &str) -> Option<Range<usize>> {
+    let bytes = json.as_bytes();
+    let mut start = skip_json_whitespace(bytes, 0);
+
+    if pointer.is_empty() {
+        return Some(start..skip_json_value(bytes, start)?);
+    }
+
+    for token in pointer.strip_prefix('/')?.split('/') {
+        let token = token.replace("~1", "/").replace("~0", "~");
+
+        match *bytes.get(start)? {
+            b'{' => {
+                let mut pos = skip_json_whitespace(bytes, start + 1);
+                loop {
+                    if bytes.get(pos)? == &b'}' {
+                        return None;
+                    }
+
+                    let key_end = skip_json_value(bytes, pos)?;
+                    let key: String = serde_json::from_str(&json[pos..key_end]).ok()?;
+
+                    pos = skip_json_whitespace(bytes, key_end);
+                    if bytes.get(pos)? != &b':' {
+                        return None;
+                    }
+                    pos = skip_json_whitespace(bytes, pos + 1);
+
+                    if key == token {
+                        start = pos;
+                        break;
+                    }
+
+                    pos = skip_json_separator(bytes, skip_json_value(bytes, pos)?)?;
+                }
+            }
+            b'[' => {
+                let index: usize = token.parse().ok()?;
+                let mut pos = skip_json_whitespace(bytes, start + 1);
+                for _ in 0..index {
+                    if bytes.get(pos)? == &b']' {
+                        return None;
+                    }
+                    pos = skip_json_separator(bytes, skip_json_value(bytes, pos)?)?;
+                }
+
+                if bytes.get(pos)? == &b']' {
+                    return None;
+                }
+                start = pos;
+            }
+            _ => return None,
+        }
+    }
+
+    Some(start..skip_json_value(bytes, start)?)
+}
+
+fn skip_json_whitespace(bytes: &[u8], mut pos: usize) -> usize {
+    while bytes.get(pos).is_some_and(u8::is_ascii_whitespace) {
+        pos += 1;
+    }
+    pos
+}
+
+/// Skips the `,` after a value in an array or object.
+fn skip_json_separator(bytes: &[u8], pos: usize) -> Option<usize> {
+    let pos = skip_json_whitespace(bytes, pos);
+    match bytes.get(pos)? {
+        b',' => Some(skip_json_whitespace(bytes, pos + 1)),
+        b'}' | b']' => Some(pos),
+        _ => None,
+    }
+}
+
+/// Returns the end of the value starting at the given position.
+fn skip_json_value(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut depth = 0_usize;
+    let mut in_string = false;
+    let mut pos = start;
+
+    while let Some(&b) = bytes.get(pos) {
+        pos += 1;
+
+        if in_string {
+            match b {
+                b'\\' => pos += 1,
+                b'"' => {
+                    in_string = false;
+                    if depth == 0 {
+                        return Some(pos);
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        match b {
+            b'"' => in_string = true,
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    return Some(pos);
+                }
+            }
+            b',' | b':' if depth == 0 => return None,
+            _ if depth == 0 => {
+                // Numbers, booleans and null.
+                while bytes
+                    .get(pos)
+                    .is_some_and(|b| !matches!(b, b',' | b'}' | b']') && !b.is_ascii_whitespace())
+                {
+                    pos += 1;
+                }
+                return Some(pos);
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
 #[cfg(all(not(target_arch = "wasm32"), feature = "reqwest"))]
 #[tracing::instrument]
 pub fn get_reqwest_client(timeout: std::time::Duration) -> Result<reqwest::Client, reqwest::Error> {
@@ -168,3 +299,61 @@ pub fn get_reqwest_client(timeout: std::time::Duration) -> Result<reqwest::Clien
     }
     builder.build()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::json_pointer_range;
+
+    const JSON: &str = r#"{
+  "name": "taplo",
+  "a/b": 1,
+  "m~n": [true, null],
+  "key \"quoted\"": "x",
+  "nested": {
+    "items": [
+      { "id": 0 },
+      { "id": 1, "tags": ["a", "b,c"] }
+    ]
+  }
+}"#;
+
+    fn value_at(pointer: &str) -> Option<&'static str> {
+        json_pointer_range(JSON, pointer).map(|range| &JSON[range])
+    }
+
+    #[test]
+    fn json_pointer_root() {
+        assert_eq!(value_at(""), Some(JSON));
+    }
+
+    #[test]
+    fn json_pointer_object_keys() {
+        assert_eq!(value_at("/name"), Some(r#""taplo""#));
+        assert_eq!(value_at(r#"/key "quoted""#), Some(r#""x""#));
+        assert_eq!(value_at("/missing"), None);
+    }
+
+    #[test]
+    fn json_pointer_escaped_tokens() {
+        assert_eq!(value_at("/a~1b"), Some("1"));
+        assert_eq!(value_at("/m~0n"), Some("[true, null]"));
+        assert_eq!(value_at("/m~0n/1"), Some("null"));
+        assert_eq!(value_at("/a/b"), None);
+    }
+
+    #[test]
+    fn json_pointer_array_indices() {
+        assert_eq!(value_at("/m~0n/0"), Some("true"));
+        assert_eq!(value_at("/m~0n/2"), None);
+        assert_eq!(value_at("/m~0n/x"), None);
+    }
+
+    #[test]
+    fn json_pointer_nested_values() {
+        assert_eq!(value_at("/nested/items/0"), Some(r#"{ "id": 0 }"#));
+        assert_eq!(value_at("/nested/items/1/id"), Some("1"));
+        assert_eq!(value_at("/nested/items/1/tags/1"), Some(r#""b,c""#));
+        assert_eq!(value_at("/nested/items/1/tags/2"), None);
+        assert_eq!(value_at("/name/0"), None);
+    }
+}
@@ -37,6 +37,8 @@ pub struct LspConfig {
     pub schema: SchemaConfig,
     pub completion: CompletionConfig,
     pub syntax: SyntaxConfig,
+    pub inlay_hints: InlayHintsConfig,
+    pub lint: LintConfig,
     pub formatter: taplo::formatter::OptionsIncompleteCamel,
     pub rules: Vec<Rule>,
 }
@@ -77,6 +79,50 @@ impl Default for SyntaxConfig {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[allow(clippy::struct_excessive_bools)]
+pub struct InlayHintsConfig {
+    /// Show the type from the schema after values.
+    pub schema_types: bool,
+    /// Show the default values of properties missing from tables.
+    pub schema_defaults: bool,
+    /// Show the full path of table headers inside arrays of tables.
+    ///
+    /// Other headers already contain their full path, so they have no hint
+    /// regardless of how deeply they are nested.
+    pub table_paths: bool,
+    /// Show the index of array of tables elements.
+    pub array_indices: bool,
+}
+
+impl Default for InlayHintsConfig {
+    fn default() -> Self {
+        Self {
+            schema_types: false,
+            schema_defaults: true,
+            table_paths: true,
+            array_indices: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LintConfig {
+    /// Warn about keys that are quoted differently where they appear,
+    /// e.g. `a` in `a.b = 1` and `"a".c = 2`.
+    pub inconsistent_key_quotes: bool,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            inconsistent_key_quotes: true,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SchemaConfig {
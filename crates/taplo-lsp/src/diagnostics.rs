@@ -1,11 +1,19 @@
-use crate::world::{DocumentState, WorkspaceState, World};
+use crate::{
+    lsp_ext::request::WorkspaceDiagnosticRefresh,
+    world::{DocumentState, WorkspaceState, World},
+};
 use lsp_async_stub::{util::LspExt, Context, RequestWriter};
 use lsp_types::{
     notification, Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, Location,
     PublishDiagnosticsParams, Url,
 };
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    sync::atomic::Ordering,
+};
 use taplo::dom::Node;
-use taplo_common::environment::Environment;
+use taplo_common::{environment::Environment, util::HashValue, HashMap};
 
 #[tracing::instrument(skip_all)]
 pub(crate) async fn publish_diagnostics<E: Environment>(
@@ -13,6 +21,10 @@ pub(crate) async fn publish_diagnostics<E: Environment>(
     ws_url: Url,
     document_url: Url,
 ) {
+    if context.diagnostic_pull.load(Ordering::Relaxed) {
+        return;
+    }
+
     let mut diags = Vec::new();
 
     let workspaces = context.workspaces.read().await;
@@ -76,6 +88,7 @@ pub(crate) async fn publish_diagnostics<E: Environment>(
         return;
     };
 
+    collect_lint_warnings(ws, doc, &dom, &document_url, &mut diags);
     collect_schema_errors(ws, doc, &dom, &document_url, &mut diags).await;
     drop(workspaces);
 
@@ -89,11 +102,34 @@ pub(crate) async fn publish_diagnostics<E: Environment>(
         .unwrap_or_else(|err| tracing::error!("{err}"));
 }
 
+#[tracing::instrument(skip_all)]
+pub(crate) async fn publish_excluded<E: Environment>(
+    mut context: Context<World<E>>,
+    document_url: Url,
+) {
+    if context.diagnostic_pull.load(Ordering::Relaxed) {
+        return;
+    }
+
+    context
+        .write_notification::<notification::PublishDiagnostics, _>(Some(PublishDiagnosticsParams {
+            uri: document_url,
+            diagnostics: vec![excluded_diagnostic()],
+            version: None,
+        }))
+        .await
+        .unwrap_or_else(|err| tracing::error!("{err}"));
+}
+
 #[tracing::instrument(skip_all)]
 pub(crate) async fn clear_diagnostics<E: Environment>(
     mut context: Context<World<E>>,
     document_url: Url,
 ) {
+    if context.diagnostic_pull.load(Ordering::Relaxed) {
+        return;
+    }
+
     context
         .write_notification::<notification::PublishDiagnostics, _>(Some(PublishDiagnosticsParams {
             uri: document_url,
@@ -104,6 +140,108 @@ pub(crate) async fn clear_diagnostics<E: Environment>(
         .unwrap_or_else(|err| tracing::error!("{}", err));
 }
 
+/// Asks the client to pull diagnostics again, e.g. after the configuration has changed.
+#[tracing::instrument(skip_all)]
+pub(crate) async fn refresh_diagnostics<E: Environment>(context: Context<World<E>>) {
+    if !context.diagnostic_pull.load(Ordering::Relaxed)
+        || !context.diagnostic_refresh.load(Ordering::Relaxed)
+    {
+        return;
+    }
+
+    if let Err(error) = context
+        .clone()
+        .write_request::<WorkspaceDiagnosticRefresh, _>(None)
+        .await
+    {
+        tracing::error!(%error, "failed to refresh diagnostics");
+    }
+}
+
+/// Collects the diagnostics of the document, later stages only run
+/// if the previous ones found no errors.
+pub(crate) async fn collect_diagnostics<E: Environment>(
+    ws: &WorkspaceState<E>,
+    doc: &DocumentState,
+    document_url: &Url,
+) -> Vec<Diagnostic> {
+    let mut diags = Vec::new();
+
+    collect_syntax_errors(doc, &mut diags);
+    if !diags.is_empty() {
+        return diags;
+    }
+
+    collect_dom_errors(doc, &doc.dom, document_url, &mut diags);
+    if !diags.is_empty() {
+        return diags;
+    }
+
+    collect_lint_warnings(ws, doc, &doc.dom, document_url, &mut diags);
+    collect_schema_errors(ws, doc, &doc.dom, document_url, &mut diags).await;
+    diags
+}
+
+/// An identifier of everything the diagnostics of the document depend on,
+/// computed from its text so that it is known before the document is parsed.
+///
+/// The hashes of the schemas are reused for documents with the same schema.
+pub(crate) async fn result_id<E: Environment>(
+    ws: &WorkspaceState<E>,
+    text: &str,
+    document_url: &Url,
+    schema_hashes: &mut HashMap<Url, Option<u64>>,
+) -> String {
+    let mut hasher = DefaultHasher::new();
+
+    ws.root.hash(&mut hasher);
+    ws.revision.hash(&mut hasher);
+    document_url.hash(&mut hasher);
+    text.hash(&mut hasher);
+
+    if ws.config.schema.enabled {
+        if let Some(schema_association) = ws.schemas.associations().association_for(document_url) {
+            schema_association.url.hash(&mut hasher);
+
+            let schema_hash = match schema_hashes.get(&schema_association.url) {
+                Some(schema_hash) => *schema_hash,
+                None => {
+                    let schema_hash = ws
+                        .schemas
+                        .load_schema(&schema_association.url)
+                        .await
+                        .ok()
+                        .map(|schema| {
+                            let mut hasher = DefaultHasher::new();
+                            HashValue(&schema).hash(&mut hasher);
+                            hasher.finish()
+                        });
+                    schema_hashes.insert(schema_association.url.clone(), schema_hash);
+                    schema_hash
+                }
+            };
+
+            schema_hash.hash(&mut hasher);
+        }
+    }
+
+    format!("{:x}", hasher.finish())
+}
+
+pub(crate) fn excluded_diagnostic() -> Diagnostic {
+    Diagnostic {
+        range: Default::default(),
+        severity: Some(DiagnosticSeverity::HINT),
+        code: None,
+        code_description: None,
+        source: Some("Even Better TOML".into()),
+        message: "this document has been excluded".into(),
+        related_information: None,
+        tags: None,
+        data: None,
+    }
+}
+
 #[tracing::instrument(skip_all)]
 fn collect_syntax_errors(doc: &DocumentState, diags: &mut Vec<Diagnostic>) {
     diags.extend(doc.parse.errors.iter().map(|e| {
@@ -277,6 +415,47 @@ fn collect_dom_errors(
     }
 }
 
+/// Warnings about valid TOML that is written inconsistently.
+#[tracing::instrument(skip_all)]
+fn collect_lint_warnings<E: Environment>(
+    ws: &WorkspaceState<E>,
+    doc: &DocumentState,
+    dom: &Node,
+    document_url: &Url,
+    diags: &mut Vec<Diagnostic>,
+) {
+    if !ws.config.lint.inconsistent_key_quotes {
+        return;
+    }
+
+    for key in dom.inconsistent_keys() {
+        let (Some(range), Some(first_range)) = (
+            doc.mapper.range(key.range),
+            doc.mapper.range(key.first_range),
+        ) else {
+            continue;
+        };
+
+        diags.push(Diagnostic {
+            range: range.into_lsp(),
+            severity: Some(DiagnosticSeverity::WARNING),
+            source: Some("Even Better TOML".into()),
+            message: format!(
+                "the key `{}` is quoted differently than `{}`",
+                key.text, key.first_text
+            ),
+            related_information: Some(Vec::from([DiagnosticRelatedInformation {
+                location: Location {
+                    uri: document_url.clone(),
+                    range: first_range.into_lsp(),
+                },
+                message: "first written here".into(),
+            }])),
+            ..Default::default()
+        });
+    }
+}
+
 #[tracing::instrument(skip_all, fields(%document_url))]
 async fn collect_schema_errors<E: Environment>(
     ws: &WorkspaceState<E>,
@@ -10,15 +10,36 @@ pub(crate) use semantic_tokens::*;
 mod folding_ranges;
 pub(crate) use folding_ranges::*;
 
+mod selection_ranges;
+pub(crate) use selection_ranges::*;
+
 mod document_symbols;
 pub(crate) use document_symbols::*;
 
+mod workspace_symbols;
+pub(crate) use workspace_symbols::*;
+
 mod formatting;
 pub(crate) use formatting::*;
 
+mod code_actions;
+pub(crate) use code_actions::*;
+
 mod hover;
 pub(crate) use hover::*;
 
+mod inlay_hints;
+pub(crate) use inlay_hints::*;
+
+mod diagnostics;
+pub(crate) use diagnostics::*;
+
+mod code_lens;
+pub(crate) use code_lens::*;
+
+mod commands;
+pub(crate) use commands::*;
+
 mod completion;
 pub(crate) use completion::*;
 
@@ -37,5 +58,11 @@ pub(crate) use links::*;
 mod rename;
 pub(crate) use rename::*;
 
+mod references;
+pub(crate) use references::*;
+
+mod type_definition;
+pub(crate) use type_definition::*;
+
 mod conversion;
 pub(crate) use conversion::*;
@@ -0,0 +1,785 @@
+use std::collections::HashMap;
+
+use jsonschema::error::ValidationErrorKind;
+use lsp_async_stub::{
+    rpc::Error,
+    util::{LspExt, Position, Range},
+    Context, Params,
+};
+use lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionParams, CodeActionResponse,
+    Diagnostic, TextEdit, Url, WorkspaceEdit,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use taplo::{
+    dom::{
+        node::{Array, DomNode, Key, Table, TableKind},
+        rewrite::{PendingPatchKind, Rewrite},
+        KeyOrIndex, Keys, Node,
+    },
+    formatter::diff::Diff,
+    rowan::TextRange,
+    syntax::SyntaxKind::PERIOD,
+};
+use taplo_common::environment::Environment;
+
+use super::formatting::text_edits;
+use crate::{
+    query::Query,
+    world::{DocumentState, WorkspaceState, World},
+};
+
+#[tracing::instrument(skip_all)]
+pub async fn code_actions<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<CodeActionParams>,
+) -> Result<Option<CodeActionResponse>, Error> {
+    let p = params.required()?;
+    let document_uri = p.text_document.uri;
+
+    let workspaces = context.workspaces.read().await;
+    let ws = workspaces.by_document(&document_uri);
+    let doc = match ws.document(&document_uri) {
+        Ok(d) => d,
+        Err(error) => {
+            tracing::debug!(%error, "failed to get document from workspace");
+            return Ok(None);
+        }
+    };
+
+    let Some(range) = doc.mapper.text_range(Range::from_lsp(p.range)) else {
+        tracing::debug!(range = ?p.range, "range out of bounds");
+        return Ok(None);
+    };
+
+    // Nothing can be changed reliably with syntax errors.
+    if !doc.parse.errors.is_empty() {
+        return Ok(None);
+    }
+
+    let actions = Actions {
+        uri: &document_uri,
+        doc,
+        diagnostics: &p.context.diagnostics,
+    };
+
+    let mut result = Vec::new();
+
+    // Schemas are only validated and documents only restructured without DOM errors.
+    match doc.dom.validate() {
+        Err(errors) => {
+            result.extend(errors.filter_map(|error| actions.rename_conflicting_key(&error, range)));
+        }
+        Ok(()) => {
+            result.extend(actions.schema_fixes(ws, range).await);
+
+            let query = Query::at(&doc.dom, range.start());
+            result.extend(actions.refactors(&query, p.range.start));
+        }
+    }
+
+    if let Some(only) = &p.context.only {
+        result.retain(|action| {
+            action
+                .kind
+                .as_ref()
+                .is_some_and(|kind| only.iter().any(|k| kind.as_str().starts_with(k.as_str())))
+        });
+    }
+
+    Ok(Some(
+        result
+            .into_iter()
+            .map(CodeActionOrCommand::CodeAction)
+            .collect(),
+    ))
+}
+
+/// Computes the edits of a refactor that was returned without them.
+#[tracing::instrument(skip_all)]
+pub async fn code_action_resolve<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<CodeAction>,
+) -> Result<CodeAction, Error> {
+    let mut action = params.required()?;
+
+    let Some(data) = action.data.take() else {
+        return Ok(action);
+    };
+    let data: RefactorData = serde_json::from_value(data)
+        .map_err(|error| Error::invalid_params().with_data(error.to_string()))?;
+
+    let workspaces = context.workspaces.read().await;
+    let ws = workspaces.by_document(&data.uri);
+    let doc = ws.document(&data.uri)?;
+
+    action.edit = data.edit(doc)?;
+
+    Ok(action)
+}
+
+/// Refactors that are offered without edits, these are
+/// only computed once one of them is resolved or executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) enum Refactor {
+    ExpandDottedKeys,
+    ToStandardTable,
+    ToInlineTable,
+    SortKeys,
+}
+
+impl Refactor {
+    pub(super) fn title(self) -> &'static str {
+        match self {
+            Refactor::ExpandDottedKeys => "Expand dotted keys",
+            Refactor::ToStandardTable => "Convert to standard table",
+            Refactor::ToInlineTable => "Convert to inline table",
+            Refactor::SortKeys => "Sort keys in table",
+        }
+    }
+}
+
+/// The data of a refactor code action that is needed to resolve it.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RefactorData {
+    uri: Url,
+    /// The version of the document the refactor was offered for.
+    version: i32,
+    position: lsp_types::Position,
+    refactor: Refactor,
+}
+
+impl RefactorData {
+    /// The edit of the refactor, there is none if the document
+    /// was changed since the refactor was offered.
+    fn edit(&self, doc: &DocumentState) -> Result<Option<WorkspaceEdit>, Error> {
+        if doc.version != self.version {
+            return Ok(None);
+        }
+
+        let Some(offset) = doc.mapper.offset(Position::from_lsp(self.position)) else {
+            return Err(Error::invalid_params().with_data("position out of bounds"));
+        };
+
+        if !doc.parse.errors.is_empty() || doc.dom.validate().is_err() {
+            return Err(Error::invalid_request().with_data("the document has errors"));
+        }
+
+        let actions = Actions {
+            uri: &self.uri,
+            doc,
+            diagnostics: &[],
+        };
+
+        Ok(actions
+            .refactor_edits(self.refactor, &Query::at(&doc.dom, offset))
+            .map(|edits| actions.workspace_edit(edits)))
+    }
+}
+
+pub(super) struct Actions<'a> {
+    pub(super) uri: &'a Url,
+    pub(super) doc: &'a DocumentState,
+    pub(super) diagnostics: &'a [Diagnostic],
+}
+
+impl Actions<'_> {
+    /// Renames the key that conflicts with another one to an unused key.
+    fn rename_conflicting_key(
+        &self,
+        error: &taplo::dom::Error,
+        range: TextRange,
+    ) -> Option<CodeAction> {
+        let taplo::dom::Error::ConflictingKeys { key, .. } = error else {
+            return None;
+        };
+
+        let key_range = key.text_ranges().next()?;
+        key_range.intersect(range)?;
+
+        let (keys, _) = Query::at(&self.doc.dom, key_range.start())
+            .dom_node()
+            .cloned()?;
+
+        // The node at the key is either the other entry or the table of both.
+        let parent_keys = match keys.iter().next_back() {
+            Some(KeyOrIndex::Key(k)) if k.value() == key.value() => keys.skip_right(1),
+            _ => keys.clone(),
+        };
+        let parent = self.doc.dom.path(&parent_keys)?;
+        let parent = parent.as_table()?;
+
+        // One of these is always free as they are more than the entries.
+        let new_key = (2..parent.entries().read().len() + 3)
+            .map(|idx| Key::new(format!("{}_{idx}", key.value())))
+            .find(|k| parent.get(k.clone()).is_none())?;
+
+        Some(self.quick_fix(
+            format!("Rename to `{new_key}`"),
+            key_range,
+            vec![(key_range, new_key.to_string())],
+        ))
+    }
+
+    /// Fixes for missing required properties and invalid enum values.
+    async fn schema_fixes<E: Environment>(
+        &self,
+        ws: &WorkspaceState<E>,
+        range: TextRange,
+    ) -> Vec<CodeAction> {
+        let mut actions = Vec::new();
+
+        if !ws.config.schema.enabled {
+            return actions;
+        }
+
+        let Some(schema_association) = ws.schemas.associations().association_for(self.uri) else {
+            return actions;
+        };
+
+        let errors = match ws
+            .schemas
+            .validate_root(&schema_association.url, &self.doc.dom)
+            .await
+        {
+            Ok(errors) => errors,
+            Err(error) => {
+                tracing::error!(?error, "schema validation failed");
+                return actions;
+            }
+        };
+
+        let value = match serde_json::to_value(&self.doc.dom) {
+            Ok(v) => v,
+            Err(error) => {
+                tracing::warn!(%error, "unable to serialize DOM");
+                Value::Null
+            }
+        };
+
+        for error in errors {
+            let Some(error_range) = error.text_ranges().next() else {
+                continue;
+            };
+
+            if error_range.intersect(range).is_none() {
+                continue;
+            }
+
+            match &error.error.kind {
+                ValidationErrorKind::Required { property } => {
+                    let Some(property) = property.as_str() else {
+                        continue;
+                    };
+                    let key = Key::new(property);
+
+                    let schema = ws
+                        .schemas
+                        .schemas_at_path(
+                            &schema_association.url,
+                            &value,
+                            &error.keys.join(key.clone()),
+                        )
+                        .await
+                        .ok()
+                        .and_then(|schemas| schemas.into_iter().next())
+                        .map(|(_, schema)| schema);
+
+                    let node = schema
+                        .as_deref()
+                        .map_or_else(|| Table::new_inline().into(), default_value);
+
+                    let edits = self.dom_edits(|dom| {
+                        dom.path(&error.keys)?
+                            .as_table()?
+                            .insert(key.clone(), node.clone());
+                        Some(())
+                    });
+
+                    if let Some(edits) = edits {
+                        actions.push(self.quick_fix(
+                            format!("Add missing property `{key}`"),
+                            error_range,
+                            edits,
+                        ));
+                    }
+                }
+                ValidationErrorKind::Enum { options } => {
+                    let Some(options) = options.as_array() else {
+                        continue;
+                    };
+
+                    let value_range = error.node.text_ranges(true).next().unwrap_or(error_range);
+
+                    for option in options {
+                        let Ok(node) = serde_json::from_value::<Node>(option.clone()) else {
+                            continue;
+                        };
+                        let toml_value = node.to_toml(true, false);
+
+                        actions.push(self.quick_fix(
+                            format!("Replace with `{toml_value}`"),
+                            error_range,
+                            vec![(value_range, toml_value)],
+                        ));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        actions
+    }
+
+    /// Refactors of the tables and entries at the cursor, only the cheap
+    /// checks whether they apply are done here.
+    fn refactors(&self, query: &Query, position: lsp_types::Position) -> Vec<CodeAction> {
+        let keys = query_keys(query);
+        let mut refactors = Vec::new();
+
+        if self.dotted_keys(query, &keys).is_some() {
+            refactors.push(Refactor::ExpandDottedKeys);
+        }
+
+        // Dotted keys are expanded above instead.
+        match self.closest_table(&keys, &[TableKind::Inline, TableKind::Regular]) {
+            Some((table_keys, TableKind::Inline))
+                if !in_inline_value(&self.doc.dom, &table_keys) =>
+            {
+                refactors.push(Refactor::ToStandardTable);
+            }
+            Some((_, TableKind::Regular)) => refactors.push(Refactor::ToInlineTable),
+            _ => {}
+        }
+
+        if self.unsorted_table(&keys).is_some() {
+            refactors.push(Refactor::SortKeys);
+        }
+
+        refactors
+            .into_iter()
+            .map(|refactor| CodeAction {
+                title: refactor.title().into(),
+                kind: Some(CodeActionKind::REFACTOR_REWRITE),
+                data: serde_json::to_value(RefactorData {
+                    uri: self.uri.clone(),
+                    version: self.doc.version,
+                    position,
+                    refactor,
+                })
+                .ok(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    /// The edits of a refactor at the cursor.
+    pub(super) fn refactor_edits(
+        &self,
+        refactor: Refactor,
+        query: &Query,
+    ) -> Option<Vec<(TextRange, String)>> {
+        let keys = query_keys(query);
+
+        match refactor {
+            Refactor::ExpandDottedKeys => {
+                let dotted = self.dotted_keys(query, &keys)?;
+                self.dom_edits(|dom| {
+                    let (parent, key, table) = table_at(dom, &dotted)?;
+                    parent.insert(key, expand_dotted(&table));
+                    Some(())
+                })
+            }
+            Refactor::ToStandardTable => {
+                let (table_keys, _) = self.closest_table(&keys, &[TableKind::Inline])?;
+                self.to_standard_table(&table_keys)
+            }
+            Refactor::ToInlineTable => {
+                let (table_keys, _) = self.closest_table(&keys, &[TableKind::Regular])?;
+                self.to_inline_table(&table_keys)
+            }
+            Refactor::SortKeys => self.sort_keys(&keys),
+        }
+    }
+
+    /// The keys of the table of the dotted keys at the cursor that
+    /// can be expanded.
+    fn dotted_keys(&self, query: &Query, keys: &Keys) -> Option<Keys> {
+        let entry_key = query
+            .entry_key()
+            .filter(|key| key.children_with_tokens().any(|t| t.kind() == PERIOD))?;
+
+        // The dotted keys start with the table of the first key.
+        let first_key = entry_key.first_token().map(|t| t.text_range());
+        let idx = keys.iter().position(
+            |k| matches!(k, KeyOrIndex::Key(k) if k.text_ranges().any(|r| Some(r) == first_key)),
+        )?;
+
+        let dotted = keys.skip_right(keys.len() - idx - 1);
+        table_at(&self.doc.dom, &dotted)?;
+
+        (!in_inline_value(&self.doc.dom, &dotted)).then_some(dotted)
+    }
+
+    /// The keys and kind of the closest table at the keys with one of the kinds.
+    fn closest_table(&self, keys: &Keys, kinds: &[TableKind]) -> Option<(Keys, TableKind)> {
+        (1..=keys.len()).rev().find_map(|idx| {
+            let table_keys = keys.skip_right(keys.len() - idx);
+
+            match self.doc.dom.path(&table_keys) {
+                Some(Node::Table(table)) if kinds.contains(&table.kind()) => {
+                    Some((table_keys, table.kind()))
+                }
+                _ => None,
+            }
+        })
+    }
+
+    fn to_standard_table(&self, table_keys: &Keys) -> Option<Vec<(TextRange, String)>> {
+        self.dom_edits(|dom| {
+            let (parent, key, table) = table_at(dom, table_keys)?;
+            if in_inline_value(dom, table_keys) {
+                return None;
+            }
+            parent.insert(key, copy_entries(&table, Table::new()));
+            Some(())
+        })
+    }
+
+    fn to_inline_table(&self, table_keys: &Keys) -> Option<Vec<(TextRange, String)>> {
+        self.dom_edits(|dom| {
+            let (parent, key, table) = table_at(dom, table_keys)?;
+            parent.insert(key, copy_entries(&table, Table::new_inline()));
+            Some(())
+        })
+    }
+
+    /// The keys of the closest table that is not inline
+    /// if its keys are not sorted.
+    fn unsorted_table(&self, keys: &Keys) -> Option<Keys> {
+        let table_keys = (0..=keys.len()).map(|n| keys.skip_right(n)).find(|k| {
+            matches!(
+                self.doc.dom.path(k),
+                Some(Node::Table(t)) if t.kind() != TableKind::Inline
+            )
+        })?;
+
+        let table = self.doc.dom.path(&table_keys)?;
+        let entries = table.as_table()?.entries().read();
+        let sorted = entries
+            .iter()
+            .zip(entries.iter().skip(1))
+            .all(|((a, _), (b, _))| a.value() <= b.value());
+
+        (!sorted).then_some(table_keys)
+    }
+
+    /// Sorts the keys of the closest table that is not inline,
+    /// the entries swap their places in the source.
+    fn sort_keys(&self, keys: &Keys) -> Option<Vec<(TextRange, String)>> {
+        let table_keys = self.unsorted_table(keys)?;
+
+        let mut rewrite = Rewrite::new(self.doc.dom.clone()).ok()?;
+        rewrite.sort_entries(&table_keys.to_string()).ok()?;
+
+        let edits: Vec<(TextRange, String)> = rewrite
+            .patches()
+            .iter()
+            .filter_map(|patch| match &patch.kind {
+                PendingPatchKind::Replace(text) => Some((patch.range, text.to_string())),
+                _ => None,
+            })
+            .collect();
+        (!edits.is_empty()).then_some(edits)
+    }
+
+    /// Edits of the document after modifying a separate DOM of it.
+    fn dom_edits(
+        &self,
+        modify: impl FnOnce(&Node) -> Option<()>,
+    ) -> Option<Vec<(TextRange, String)>> {
+        let dom = self.doc.parse.clone().into_dom();
+        modify(&dom)?;
+
+        let original = self.doc.dom.syntax()?.to_string();
+        let edits = Diff::new(&original, &dom.to_toml_lossless()).text_edits();
+        (!edits.is_empty()).then_some(edits)
+    }
+
+    fn quick_fix(
+        &self,
+        title: String,
+        range: TextRange,
+        edits: Vec<(TextRange, String)>,
+    ) -> CodeAction {
+        let lsp_range = self.doc.mapper.range(range).map(LspExt::into_lsp);
+
+        CodeAction {
+            title,
+            kind: Some(CodeActionKind::QUICKFIX),
+            diagnostics: Some(
+                self.diagnostics
+                    .iter()
+                    .filter(|d| Some(d.range) == lsp_range)
+                    .cloned()
+                    .collect(),
+            ),
+            edit: Some(self.workspace_edit(edits)),
+            ..Default::default()
+        }
+    }
+
+    pub(super) fn workspace_edit(&self, edits: Vec<(TextRange, String)>) -> WorkspaceEdit {
+        let edits: Vec<TextEdit> = text_edits(self.doc, edits);
+
+        WorkspaceEdit {
+            changes: Some(HashMap::from([(self.uri.clone(), edits)])),
+            ..Default::default()
+        }
+    }
+}
+
+/// The keys of the node at the cursor, the root table if there is none.
+fn query_keys(query: &Query) -> Keys {
+    query
+        .dom_node()
+        .map_or_else(Keys::empty, |(keys, _)| keys.clone())
+}
+
+/// The table at the keys along with its parent table and key.
+fn table_at(root: &Node, keys: &Keys) -> Option<(Table, Key, Table)> {
+    let key = keys.iter().next_back()?.as_key()?.clone();
+    let parent = root.path(&keys.skip_right(1))?.as_table()?.clone();
+    let table = parent.get(key.clone())?.as_table()?.clone();
+    Some((parent, key, table))
+}
+
+/// Whether the node at the keys is inside an inline table or array,
+/// where it cannot become a separate section.
+fn in_inline_value(root: &Node, keys: &Keys) -> bool {
+    (1..keys.len()).any(|n| match root.path(&keys.skip_right(n)) {
+        Some(Node::Table(t)) => t.kind() == TableKind::Inline,
+        Some(Node::Array(_)) => true,
+        _ => false,
+    })
+}
+
+fn copy_entries(from: &Table, to: Table) -> Table {
+    for (key, node) in from.entries().read().iter() {
+        to.insert(key.clone(), node.clone());
+    }
+    to
+}
+
+/// Turns the tables of dotted keys into standard tables.
+fn expand_dotted(table: &Table) -> Table {
+    let expanded = Table::new();
+    for (key, node) in table.entries().read().iter() {
+        match node {
+            Node::Table(t) if t.kind() == TableKind::Pseudo => {
+                expanded.insert(key.clone(), expand_dotted(t));
+            }
+            _ => {
+                expanded.insert(key.clone(), node.clone());
+            }
+        }
+    }
+    expanded
+}
+
+/// A value for a new property based on its schema.
+fn default_value(schema: &Value) -> Node {
+    let value = ["const", "default"]
+        .iter()
+        .filter_map(|k| schema.get(*k))
+        .chain(schema["enum"].as_array().and_then(|e| e.first()))
+        .find(|v| !v.is_null());
+
+    if let Some(node) = value.and_then(|v| serde_json::from_value::<Node>(v.clone()).ok()) {
+        return node;
+    }
+
+    let ty = match &schema["type"] {
+        Value::String(s) => s.as_str(),
+        Value::Array(tys) => tys.first().and_then(Value::as_str).unwrap_or("object"),
+        _ => "object",
+    };
+
+    match ty {
+        "string" => "".into(),
+        "boolean" => false.into(),
+        "integer" | "number" => 0.into(),
+        "array" => Array::new().into(),
+        _ => Table::new_inline().into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::Url;
+    use taplo::{parser::ParseOptions, rowan::TextSize};
+
+    use super::{Actions, Refactor, RefactorData};
+    use crate::{query::Query, world::DocumentState};
+
+    fn document(toml: &str) -> DocumentState {
+        DocumentState::new(toml.into(), ParseOptions::default())
+    }
+
+    fn query_at(doc: &DocumentState, toml: &str, at: &str) -> Query {
+        let offset = toml.find(at).unwrap();
+        Query::at(&doc.dom, TextSize::try_from(offset).unwrap())
+    }
+
+    /// The titles of the refactors at the first occurrence of `at`.
+    fn refactors(toml: &str, at: &str) -> Vec<String> {
+        let uri = Url::parse("file:///test.toml").unwrap();
+        let doc = document(toml);
+        let actions = Actions {
+            uri: &uri,
+            doc: &doc,
+            diagnostics: &[],
+        };
+
+        actions
+            .refactors(&query_at(&doc, toml, at), Default::default())
+            .into_iter()
+            .map(|action| {
+                assert!(action.edit.is_none());
+                assert!(action.data.is_some());
+                action.title
+            })
+            .collect()
+    }
+
+    /// The document after the refactor at the first occurrence of `at`.
+    fn refactor(toml: &str, at: &str, refactor: Refactor) -> Option<String> {
+        let uri = Url::parse("file:///test.toml").unwrap();
+        let doc = document(toml);
+        let actions = Actions {
+            uri: &uri,
+            doc: &doc,
+            diagnostics: &[],
+        };
+
+        let edits = actions.refactor_edits(refactor, &query_at(&doc, toml, at))?;
+
+        let mut output = toml.to_string();
+        for (range, text) in edits.into_iter().rev() {
+            output.replace_range(std::ops::Range::<usize>::from(range), &text);
+        }
+        Some(output)
+    }
+
+    #[test]
+    fn refactors_at_cursor() {
+        let toml = "[b]\nz = 1\na.x = 2\ninline = { c = 1 }\n";
+
+        assert_eq!(
+            refactors(toml, ".x"),
+            [
+                Refactor::ExpandDottedKeys.title(),
+                Refactor::ToInlineTable.title(),
+            ]
+        );
+        assert_eq!(
+            refactors(toml, "c = 1"),
+            [
+                Refactor::ToStandardTable.title(),
+                Refactor::SortKeys.title(),
+            ]
+        );
+        assert_eq!(
+            refactors("[b]\na = 1\nz = 1\n", "z"),
+            [Refactor::ToInlineTable.title()]
+        );
+    }
+
+    #[test]
+    fn sort_keys() {
+        assert_eq!(
+            refactor(
+                "[b]\n# The last one.\nz = 1\na.x = 2\na.y = 3\ninline = { c = 1 }\n",
+                "z",
+                Refactor::SortKeys
+            )
+            .as_deref(),
+            Some("[b]\na.x = 2\na.y = 3\ninline = { c = 1 }\n# The last one.\nz = 1\n")
+        );
+        assert_eq!(
+            refactor("[b]\nc = 1\n\n[a]\nd = 1", "\n[a]", Refactor::SortKeys).as_deref(),
+            Some("[a]\nd = 1\n\n[b]\nc = 1")
+        );
+
+        // Values are always before tables.
+        assert_eq!(
+            refactor("b = 1\n\n[a]\nc = 1\n", "b", Refactor::SortKeys),
+            None
+        );
+        assert_eq!(refactor("[b]\na = 1\nz = 1", "z", Refactor::SortKeys), None);
+    }
+
+    #[test]
+    fn convert_tables() {
+        assert_eq!(
+            refactor("[b]\na.x = 2\na.y = 3\n", ".x", Refactor::ExpandDottedKeys).as_deref(),
+            Some("[b]\n[b.a]\nx = 2\ny = 3\n")
+        );
+        assert_eq!(
+            refactor("a = { b = 1 }\n", "b", Refactor::ToStandardTable).as_deref(),
+            Some("[a]\nb = 1\n")
+        );
+        assert_eq!(
+            refactor("[a]\nb = 1\n", "b", Refactor::ToInlineTable).as_deref(),
+            Some("a = { b = 1 }\n")
+        );
+
+        // Tables in arrays cannot become sections.
+        assert_eq!(
+            refactor("x = [{ a = { b = 1 } }]\n", "b", Refactor::ToStandardTable),
+            None
+        );
+    }
+
+    #[test]
+    fn resolve_only_same_version() {
+        let mut doc = document("[b]\nz = 1\na = 2\n");
+        doc.version = 3;
+
+        let data = |version| RefactorData {
+            uri: Url::parse("file:///test.toml").unwrap(),
+            version,
+            position: lsp_types::Position::new(1, 0),
+            refactor: Refactor::SortKeys,
+        };
+
+        assert!(data(3).edit(&doc).unwrap().is_some());
+        assert!(data(2).edit(&doc).unwrap().is_none());
+    }
+
+    #[test]
+    fn rename_conflicting_key() {
+        let toml = "a = 1\na = 2\n";
+        let uri = Url::parse("file:///test.toml").unwrap();
+        let doc = document(toml);
+        let actions = Actions {
+            uri: &uri,
+            doc: &doc,
+            diagnostics: &[],
+        };
+
+        let titles: Vec<String> = doc
+            .dom
+            .validate()
+            .unwrap_err()
+            .filter_map(|error| {
+                actions.rename_conflicting_key(&error, doc.parse.clone().into_syntax().text_range())
+            })
+            .map(|action| action.title)
+            .collect();
+        assert_eq!(titles, ["Rename to `a_2`"]);
+    }
+}
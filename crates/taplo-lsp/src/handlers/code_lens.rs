@@ -0,0 +1,137 @@
+use super::{OPEN_SCHEMA_COMMAND, VALIDATE_COMMAND};
+use crate::world::{DocumentState, World};
+use lsp_async_stub::{rpc::Error, util::LspExt, Context, Params};
+use lsp_types::{CodeLens, CodeLensParams, Command};
+use serde_json::{json, Value};
+use taplo::{
+    dom::{node::DomNode, KeyOrIndex, Node},
+    rowan::TextRange,
+    syntax::{
+        SyntaxElement,
+        SyntaxKind::{COMMENT, TABLE_ARRAY_HEADER, TABLE_HEADER},
+    },
+};
+use taplo_common::environment::Environment;
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn code_lens<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<CodeLensParams>,
+) -> Result<Option<Vec<CodeLens>>, Error> {
+    let p = params.required()?;
+    let document_uri = p.text_document.uri;
+
+    let workspaces = context.workspaces.read().await;
+    let ws = workspaces.by_document(&document_uri);
+    let doc = match ws.document(&document_uri) {
+        Ok(d) => d,
+        Err(error) => {
+            tracing::debug!(%error, "failed to get document from workspace");
+            return Ok(None);
+        }
+    };
+
+    let mut lenses = table_lenses(doc);
+
+    if ws.config.schema.enabled {
+        let schema_association = ws.schemas.associations().association_for(&document_uri);
+
+        if let (Some(range), Some(schema_association)) = (schema_directive(doc), schema_association)
+        {
+            let name = schema_association.meta["name"]
+                .as_str()
+                .map_or_else(|| schema_association.url.to_string(), ToString::to_string);
+
+            let title = match schema_association.meta["source"].as_str() {
+                Some(source) => format!("Schema: {name} (from {source})"),
+                None => format!("Schema: {name}"),
+            };
+
+            lenses.extend(lens(
+                doc,
+                range,
+                title,
+                OPEN_SCHEMA_COMMAND,
+                Some(vec![json!(schema_association.url)]),
+            ));
+
+            lenses.extend(lens(
+                doc,
+                range,
+                "Validate now".into(),
+                VALIDATE_COMMAND,
+                Some(vec![json!(document_uri)]),
+            ));
+        }
+    }
+
+    Ok(Some(lenses))
+}
+
+/// Lenses above table headers with the amount of entries and the places
+/// the table is defined in, and the position of array of tables elements.
+fn table_lenses(doc: &DocumentState) -> Vec<CodeLens> {
+    let mut lenses = Vec::new();
+
+    for (keys, node) in doc.dom.flat_iter() {
+        let (Some(syntax), Node::Table(table)) = (node.syntax(), &node) else {
+            continue;
+        };
+
+        let title = match (syntax.kind(), keys.iter().last()) {
+            (TABLE_HEADER, Some(KeyOrIndex::Key(key))) => {
+                let entries = match table.entries().read().len() {
+                    1 => "1 entry".into(),
+                    count => format!("{count} entries"),
+                };
+
+                match key.text_ranges().count() {
+                    0 | 1 => entries,
+                    places => format!("{entries} · defined in {places} places"),
+                }
+            }
+            (TABLE_ARRAY_HEADER, Some(KeyOrIndex::Index(idx))) => {
+                let Some(Node::Array(array)) = doc.dom.path(&keys.skip_right(1)) else {
+                    continue;
+                };
+
+                format!("element {} of {}", idx + 1, array.items().read().len())
+            }
+            _ => continue,
+        };
+
+        // Informational lenses have nothing to run.
+        lenses.extend(lens(doc, syntax.text_range(), title, "", None));
+    }
+
+    lenses
+}
+
+/// The range of the `#:schema` directive.
+fn schema_directive(doc: &DocumentState) -> Option<TextRange> {
+    doc.dom
+        .syntax()?
+        .as_node()?
+        .children_with_tokens()
+        .filter_map(SyntaxElement::into_token)
+        .find(|t| t.kind() == COMMENT && t.text().starts_with("#:schema"))
+        .map(|t| t.text_range())
+}
+
+fn lens(
+    doc: &DocumentState,
+    range: TextRange,
+    title: String,
+    command: &str,
+    arguments: Option<Vec<Value>>,
+) -> Option<CodeLens> {
+    Some(CodeLens {
+        range: doc.mapper.range(range)?.into_lsp(),
+        command: Some(Command {
+            title,
+            command: command.into(),
+            arguments,
+        }),
+        data: None,
+    })
+}
@@ -0,0 +1,362 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use anyhow::Context as AnyhowContext;
+use lsp_async_stub::{
+    rpc::Error,
+    util::{LspExt, Position, Range},
+    Context, Params, RequestWriter,
+};
+use lsp_types::{
+    request::{ApplyWorkspaceEdit, ShowDocument},
+    ApplyWorkspaceEditParams, ExecuteCommandParams, ShowDocumentParams, Url, WorkspaceEdit,
+};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use taplo::{dom::node::DomNode, parser::parse_with};
+use taplo_common::environment::Environment;
+
+use super::{
+    code_actions::{Actions, Refactor},
+    formatting::{document_edits, format_options, key_order},
+};
+use crate::{
+    diagnostics::{publish_diagnostics, refresh_diagnostics},
+    query::Query,
+    world::{read_text, DocumentState, WorkspaceState, World},
+};
+
+/// Opens the schema with the URL given as the only argument.
+pub const OPEN_SCHEMA_COMMAND: &str = "taplo.openSchema";
+
+/// Validates the document with the URI given as the only argument.
+pub const VALIDATE_COMMAND: &str = "taplo.validate";
+
+/// Sorts the keys of the table at the document URI and position arguments.
+pub const SORT_TABLE_KEYS_COMMAND: &str = "taplo.sortTableKeys";
+
+/// Converts the standard table at the document URI and position arguments
+/// to an inline table.
+pub const CONVERT_TO_INLINE_TABLE_COMMAND: &str = "taplo.convertToInlineTable";
+
+/// Converts the inline table at the document URI and position arguments
+/// to a standard table.
+pub const CONVERT_TO_STANDARD_TABLE_COMMAND: &str = "taplo.convertToStandardTable";
+
+/// Returns the document with the URI argument as JSON, or only
+/// the TOML in the range if one is given as the second argument.
+pub const CONVERT_TO_JSON_COMMAND: &str = "taplo.convertToJson";
+
+/// Loads the configuration and schemas of every workspace again,
+/// schemas cached on disk are still used.
+pub const RELOAD_SCHEMAS_COMMAND: &str = "taplo.reloadSchemas";
+
+/// Same as [`RELOAD_SCHEMAS_COMMAND`], but the disk cache is cleared as well
+/// so that remote schemas are fetched again.
+pub const CLEAR_SCHEMA_CACHE_COMMAND: &str = "taplo.clearSchemaCache";
+
+/// Formats every included file of every workspace.
+pub const FORMAT_WORKSPACE_COMMAND: &str = "taplo.formatWorkspace";
+
+/// All commands supported by [`execute_command`].
+pub const COMMANDS: &[&str] = &[
+    OPEN_SCHEMA_COMMAND,
+    VALIDATE_COMMAND,
+    SORT_TABLE_KEYS_COMMAND,
+    CONVERT_TO_INLINE_TABLE_COMMAND,
+    CONVERT_TO_STANDARD_TABLE_COMMAND,
+    CONVERT_TO_JSON_COMMAND,
+    RELOAD_SCHEMAS_COMMAND,
+    CLEAR_SCHEMA_CACHE_COMMAND,
+    FORMAT_WORKSPACE_COMMAND,
+];
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn execute_command<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<ExecuteCommandParams>,
+) -> Result<Option<Value>, Error> {
+    let p = params.required()?;
+    let args = &p.arguments;
+
+    match p.command.as_str() {
+        OPEN_SCHEMA_COMMAND => open_schema(context, argument(args, 0)?).await,
+        VALIDATE_COMMAND => {
+            let document_uri: Url = argument(args, 0)?;
+
+            let workspaces = context.workspaces.read().await;
+            let ws_root = workspaces.by_document(&document_uri).root.clone();
+            drop(workspaces);
+
+            publish_diagnostics(context.clone(), ws_root, document_uri).await;
+            refresh_diagnostics(context).await;
+        }
+        SORT_TABLE_KEYS_COMMAND
+        | CONVERT_TO_INLINE_TABLE_COMMAND
+        | CONVERT_TO_STANDARD_TABLE_COMMAND => {
+            edit_table(context, &p.command, argument(args, 0)?, argument(args, 1)?).await?;
+        }
+        CONVERT_TO_JSON_COMMAND => {
+            return convert_to_json(context, argument(args, 0)?, argument(args, 1)?).await;
+        }
+        RELOAD_SCHEMAS_COMMAND => reload_schemas(context, false).await,
+        CLEAR_SCHEMA_CACHE_COMMAND => reload_schemas(context, true).await,
+        FORMAT_WORKSPACE_COMMAND => format_workspace(context).await?,
+        _ => {
+            tracing::warn!(command = %p.command, "unknown command");
+            return Err(Error::invalid_params());
+        }
+    }
+
+    Ok(None)
+}
+
+/// The command argument at the index, missing arguments are `null`
+/// so that they can be optional.
+fn argument<T: DeserializeOwned>(arguments: &[Value], idx: usize) -> Result<T, Error> {
+    serde_json::from_value(arguments.get(idx).cloned().unwrap_or_default()).map_err(|error| {
+        Error::invalid_params().with_data(format!("invalid argument at index {idx}: {error}"))
+    })
+}
+
+async fn open_schema<E: Environment>(mut context: Context<World<E>>, url: Url) {
+    let external = url.scheme() != "file";
+
+    if let Err(error) = context
+        .write_request::<ShowDocument, _>(Some(ShowDocumentParams {
+            uri: url,
+            external: Some(external),
+            take_focus: Some(true),
+            selection: None,
+        }))
+        .await
+    {
+        tracing::error!(%error, "failed to show schema");
+    }
+}
+
+/// Sorts or converts the table at the position with the same
+/// edits as the refactoring code actions.
+async fn edit_table<E: Environment>(
+    context: Context<World<E>>,
+    command: &str,
+    document_uri: Url,
+    position: lsp_types::Position,
+) -> Result<(), Error> {
+    let workspaces = context.workspaces.read().await;
+    let ws = workspaces.by_document(&document_uri);
+    let doc = ws.document(&document_uri)?;
+
+    let Some(offset) = doc.mapper.offset(Position::from_lsp(position)) else {
+        return Err(Error::invalid_params().with_data("position out of bounds"));
+    };
+
+    if !doc.parse.errors.is_empty() || doc.dom.validate().is_err() {
+        return Err(Error::invalid_request().with_data("the document has errors"));
+    }
+
+    let actions = Actions {
+        uri: &document_uri,
+        doc,
+        diagnostics: &[],
+    };
+
+    let refactor = match command {
+        SORT_TABLE_KEYS_COMMAND => Refactor::SortKeys,
+        CONVERT_TO_INLINE_TABLE_COMMAND => Refactor::ToInlineTable,
+        _ => Refactor::ToStandardTable,
+    };
+    let edits = actions.refactor_edits(refactor, &Query::at(&doc.dom, offset));
+
+    // The table is already sorted or there is no such table.
+    let Some(edits) = edits else {
+        return Ok(());
+    };
+
+    let edit = actions.workspace_edit(edits);
+    drop(workspaces);
+
+    apply_edit(context, refactor.title(), edit).await;
+
+    Ok(())
+}
+
+async fn convert_to_json<E: Environment>(
+    context: Context<World<E>>,
+    document_uri: Url,
+    range: Option<lsp_types::Range>,
+) -> Result<Option<Value>, Error> {
+    let workspaces = context.workspaces.read().await;
+    let ws = workspaces.by_document(&document_uri);
+    let doc = ws.document(&document_uri)?;
+
+    let dom = match range {
+        Some(range) => {
+            let Some(range) = doc.mapper.text_range(Range::from_lsp(range)) else {
+                return Err(Error::invalid_params().with_data("range out of bounds"));
+            };
+
+            let source = doc.dom.syntax().unwrap().to_string();
+            let parse = parse_with(
+                &source[range],
+                ws.parse_options(&context.env, &document_uri),
+            );
+
+            if !parse.errors.is_empty() {
+                return Err(Error::invalid_request().with_data("the selection is not valid TOML"));
+            }
+
+            parse.into_dom()
+        }
+        None => {
+            if !doc.parse.errors.is_empty() {
+                return Err(Error::invalid_request().with_data("the document has errors"));
+            }
+
+            doc.dom.clone()
+        }
+    };
+
+    serde_json::to_string_pretty(&dom)
+        .map(|text| Some(Value::String(text)))
+        .map_err(|error| Error::internal_error().with_data(error.to_string()))
+}
+
+/// Clears the schema caches and initializes every workspace again,
+/// then lints the open documents with the reloaded schemas.
+async fn reload_schemas<E: Environment>(context: Context<World<E>>, clear_disk: bool) {
+    // Copies of the workspaces are initialized so that the workspaces
+    // are not locked while configuration files and catalogs are loaded.
+    let mut copies: Vec<WorkspaceState<E>> = context
+        .workspaces
+        .read()
+        .await
+        .values()
+        .map(WorkspaceState::detached_copy)
+        .collect();
+
+    for ws in &mut copies {
+        if clear_disk {
+            if let Err(error) = ws.schemas.clear_cache().await {
+                tracing::error!(%error, "failed to clear schema cache");
+            }
+        } else {
+            ws.schemas.clear_memory_cache();
+        }
+
+        if let Err(error) = ws.initialize(context.clone(), &context.env).await {
+            tracing::error!(%error, "failed to update workspace");
+        }
+    }
+
+    let mut workspaces = context.workspaces.write().await;
+
+    for copy in copies {
+        // The workspace might have been removed in the meantime.
+        if let Some(ws) = workspaces.get_mut(&copy.root) {
+            ws.update_from(copy);
+        }
+    }
+
+    let documents: Vec<(Url, Url)> = workspaces
+        .iter()
+        .flat_map(|(ws_url, ws)| {
+            ws.documents
+                .keys()
+                .map(|document_url| (ws_url.clone(), document_url.clone()))
+        })
+        .collect();
+
+    drop(workspaces);
+
+    for (ws_url, document_url) in documents {
+        publish_diagnostics(context.clone(), ws_url, document_url).await;
+    }
+
+    refresh_diagnostics(context).await;
+}
+
+/// Formats every file of the workspaces with a single edit,
+/// files that are not open are read from disk.
+async fn format_workspace<E: Environment>(context: Context<World<E>>) -> Result<(), Error> {
+    // The files are collected first so that the workspaces
+    // are not locked while the files are read.
+    let files = context.workspaces.read().await.files(&context.env);
+
+    let mut changes = HashMap::new();
+
+    for (ws_url, document_uri) in files {
+        let is_open = context
+            .workspaces
+            .read()
+            .await
+            .get(&ws_url)
+            .is_some_and(|ws| ws.documents.contains_key(&document_uri));
+
+        let text = if is_open {
+            None
+        } else {
+            read_text(&context.env, &document_uri).await
+        };
+
+        let workspaces = context.workspaces.read().await;
+        let Some(ws) = workspaces.get(&ws_url) else {
+            continue;
+        };
+
+        let doc = match (ws.documents.get(&document_uri), text) {
+            (Some(doc), _) => Cow::Borrowed(doc),
+            (None, Some(text)) => Cow::Owned(DocumentState::new(
+                text,
+                ws.parse_options(&context.env, &document_uri),
+            )),
+            (None, None) => continue,
+        };
+
+        let (format_opts, scopes) = format_options(&context, ws, &document_uri, None)?;
+        let key_order = key_order(ws, &document_uri, &doc, &format_opts, &scopes).await;
+        let edits = document_edits(&doc, format_opts, scopes, key_order)?;
+
+        if !edits.is_empty() {
+            changes.insert(document_uri, edits);
+        }
+    }
+
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    apply_edit(
+        context,
+        "Format workspace",
+        WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        },
+    )
+    .await;
+
+    Ok(())
+}
+
+async fn apply_edit<E: Environment>(
+    mut context: Context<World<E>>,
+    label: &str,
+    edit: WorkspaceEdit,
+) {
+    let res = context
+        .write_request::<ApplyWorkspaceEdit, _>(Some(ApplyWorkspaceEditParams {
+            label: Some(label.into()),
+            edit,
+        }))
+        .await
+        .context("failed to apply edit")
+        .and_then(|res| res.into_result().context("invalid apply edit response"));
+
+    match res {
+        Ok(res) if !res.applied => {
+            tracing::warn!(reason = ?res.failure_reason, "edit was not applied");
+        }
+        Ok(_) => {}
+        Err(error) => tracing::error!(?error, "failed to apply edit"),
+    }
+}
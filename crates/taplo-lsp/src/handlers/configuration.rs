@@ -1,4 +1,7 @@
-use crate::world::{World, DEFAULT_WORKSPACE_URL};
+use crate::{
+    diagnostics::refresh_diagnostics,
+    world::{World, DEFAULT_WORKSPACE_URL},
+};
 use anyhow::Context as AnyhowContext;
 use lsp_async_stub::{Context, Params, RequestWriter};
 use lsp_types::{
@@ -13,9 +16,8 @@ pub async fn configuration_change<E: Environment>(
     context: Context<World<E>>,
     params: Params<DidChangeConfigurationParams>,
 ) {
-    let p = match params.optional() {
-        None => return,
-        Some(p) => p,
+    let Some(p) = params.optional() else {
+        return;
     };
 
     let mut workspaces = context.workspaces.write().await;
@@ -29,6 +31,9 @@ pub async fn configuration_change<E: Environment>(
             tracing::error!(%error, "failed to update workspace");
         }
     }
+
+    drop(workspaces);
+    refresh_diagnostics(context).await;
 }
 
 #[tracing::instrument(skip_all)]
@@ -97,6 +102,10 @@ pub async fn update_configuration<E: Environment>(context: Context<World<E>>) {
         }
         Err(error) => {
             tracing::error!(?error, "failed to fetch configuration");
+            return;
         }
     }
+
+    drop(workspaces);
+    refresh_diagnostics(context).await;
 }
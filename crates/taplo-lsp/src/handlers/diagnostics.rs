@@ -0,0 +1,169 @@
+use std::borrow::Cow;
+
+use lsp_async_stub::{rpc::Error, Context, Params};
+use lsp_types::Url;
+use taplo_common::{environment::Environment, HashMap};
+
+use crate::{
+    diagnostics::{collect_diagnostics, excluded_diagnostic, result_id},
+    lsp_ext::request::{
+        DocumentDiagnosticParams, DocumentDiagnosticReport, WorkspaceDiagnosticParams,
+        WorkspaceDiagnosticReport, WorkspaceDocumentDiagnosticReport,
+    },
+    world::{read_text, DocumentState, WorkspaceState, World},
+};
+
+#[tracing::instrument(skip_all)]
+pub async fn document_diagnostic<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<DocumentDiagnosticParams>,
+) -> Result<DocumentDiagnosticReport, Error> {
+    let p = params.required()?;
+    let document_uri = p.text_document.uri;
+
+    let workspaces = context.workspaces.read().await;
+    let ws = workspaces.by_document(&document_uri);
+
+    if let Some(path) = context.env.to_file_path_normalized(&document_uri) {
+        if !ws.taplo_config.is_included(&path) {
+            return Ok(DocumentDiagnosticReport::Full {
+                result_id: None,
+                items: vec![excluded_diagnostic()],
+            });
+        }
+    }
+
+    let source = match ws.documents.get(&document_uri) {
+        Some(doc) => Source::Open(doc),
+        None => match read_text(&context.env, &document_uri).await {
+            Some(text) => Source::File(text),
+            None => {
+                return Ok(DocumentDiagnosticReport::Full {
+                    result_id: None,
+                    items: Vec::new(),
+                })
+            }
+        },
+    };
+
+    Ok(document_report(
+        &context.env,
+        ws,
+        source,
+        &document_uri,
+        p.previous_result_id.as_deref(),
+        &mut HashMap::default(),
+    )
+    .await)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn workspace_diagnostic<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<WorkspaceDiagnosticParams>,
+) -> Result<WorkspaceDiagnosticReport, Error> {
+    let p = params.required()?;
+
+    let previous_result_ids: HashMap<Url, String> = p
+        .previous_result_ids
+        .into_iter()
+        .map(|previous| (previous.uri, previous.value))
+        .collect();
+
+    // The files are collected first so that the workspaces
+    // are not locked while the files are read.
+    let files = context.workspaces.read().await.files(&context.env);
+
+    let mut schema_hashes = HashMap::default();
+    let mut items = Vec::new();
+
+    for (ws_url, document_uri) in files {
+        let is_open = context
+            .workspaces
+            .read()
+            .await
+            .get(&ws_url)
+            .is_some_and(|ws| ws.documents.contains_key(&document_uri));
+
+        let text = if is_open {
+            None
+        } else {
+            read_text(&context.env, &document_uri).await
+        };
+
+        let workspaces = context.workspaces.read().await;
+        let Some(ws) = workspaces.get(&ws_url) else {
+            continue;
+        };
+
+        let source = match (ws.documents.get(&document_uri), text) {
+            (Some(doc), _) => Source::Open(doc),
+            (None, Some(text)) => Source::File(text),
+            (None, None) => continue,
+        };
+
+        let report = document_report(
+            &context.env,
+            ws,
+            source,
+            &document_uri,
+            previous_result_ids.get(&document_uri).map(String::as_str),
+            &mut schema_hashes,
+        )
+        .await;
+
+        items.push(WorkspaceDocumentDiagnosticReport {
+            uri: document_uri,
+            version: None,
+            report,
+        });
+    }
+
+    Ok(WorkspaceDiagnosticReport { items })
+}
+
+/// A document that is open in the editor or the text of a file.
+enum Source<'d> {
+    Open(&'d DocumentState),
+    File(String),
+}
+
+impl Source<'_> {
+    fn text(&self) -> &str {
+        match self {
+            Source::Open(doc) => &doc.text,
+            Source::File(text) => text,
+        }
+    }
+}
+
+/// Lints the document unless the client already has
+/// the diagnostics for the same result ID, files are
+/// only parsed if they are linted.
+async fn document_report<E: Environment>(
+    env: &E,
+    ws: &WorkspaceState<E>,
+    source: Source<'_>,
+    document_uri: &Url,
+    previous_result_id: Option<&str>,
+    schema_hashes: &mut HashMap<Url, Option<u64>>,
+) -> DocumentDiagnosticReport {
+    let result_id = result_id(ws, source.text(), document_uri, schema_hashes).await;
+
+    if previous_result_id == Some(result_id.as_str()) {
+        return DocumentDiagnosticReport::Unchanged { result_id };
+    }
+
+    let doc = match source {
+        Source::Open(doc) => Cow::Borrowed(doc),
+        Source::File(text) => Cow::Owned(DocumentState::new(
+            text,
+            ws.parse_options(env, document_uri),
+        )),
+    };
+
+    DocumentDiagnosticReport::Full {
+        items: collect_diagnostics(ws, &doc, document_uri).await,
+        result_id: Some(result_id),
+    }
+}
@@ -1,8 +1,14 @@
-use lsp_async_stub::{util::Mapper, Context, Params, RequestWriter};
+use lsp_async_stub::{
+    util::{LspExt, Range},
+    Context, Params,
+};
 use lsp_types::{
-    notification, Diagnostic, DiagnosticSeverity, DidChangeTextDocumentParams,
-    DidCloseTextDocumentParams, DidOpenTextDocumentParams, DidSaveTextDocumentParams,
-    PublishDiagnosticsParams,
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    DidSaveTextDocumentParams, TextDocumentContentChangeEvent,
+};
+use taplo::{
+    parser::{reparse_with, ParseOptions},
+    rowan::{TextRange, TextSize},
 };
 use taplo_common::{
     environment::Environment,
@@ -16,12 +22,11 @@ use crate::{
 
 #[tracing::instrument(skip_all)]
 pub(crate) async fn document_open<E: Environment>(
-    mut context: Context<World<E>>,
+    context: Context<World<E>>,
     params: Params<DidOpenTextDocumentParams>,
 ) {
-    let p = match params.optional() {
-        None => return,
-        Some(p) => p,
+    let Some(p) = params.optional() else {
+        return;
     };
 
     let mut workspaces = context.workspaces.write().await;
@@ -30,34 +35,17 @@ pub(crate) async fn document_open<E: Environment>(
     if let Some(pth) = context.env.to_file_path_normalized(&p.text_document.uri) {
         if !ws.taplo_config.is_included(&pth) {
             drop(workspaces);
-            context
-                .write_notification::<notification::PublishDiagnostics, _>(Some(
-                    PublishDiagnosticsParams {
-                        uri: p.text_document.uri.clone(),
-                        diagnostics: vec![Diagnostic {
-                            range: Default::default(),
-                            severity: Some(DiagnosticSeverity::HINT),
-                            code: None,
-                            code_description: None,
-                            source: Some("Even Better TOML".into()),
-                            message: "this document has been excluded".into(),
-                            related_information: None,
-                            tags: None,
-                            data: None,
-                        }],
-                        version: None,
-                    },
-                ))
-                .await
-                .unwrap_or_else(|err| tracing::error!("{err}"));
+            diagnostics::publish_excluded(context.clone(), p.text_document.uri).await;
             return;
         }
     }
 
-    let parse = taplo::parser::parse(&p.text_document.text);
-    let mapper = Mapper::new_utf16(&p.text_document.text, false);
-
-    let dom = parse.clone().into_dom();
+    let mut doc = DocumentState::new(
+        p.text_document.text,
+        ws.parse_options(&context.env, &p.text_document.uri),
+    );
+    doc.version = p.text_document.version;
+    let dom = doc.dom.clone();
 
     if ws.config.schema.enabled {
         ws.schemas
@@ -76,10 +64,8 @@ pub(crate) async fn document_open<E: Environment>(
         ws.emit_associations(context.clone()).await;
     }
 
-    ws.documents.insert(
-        p.text_document.uri.clone(),
-        DocumentState { parse, dom, mapper },
-    );
+    ws.documents.insert(p.text_document.uri.clone(), doc);
+    ws.invalidate_symbols(&p.text_document.uri);
 
     let ws_root = ws.root.clone();
     drop(workspaces);
@@ -88,18 +74,11 @@ pub(crate) async fn document_open<E: Environment>(
 
 #[tracing::instrument(skip_all)]
 pub(crate) async fn document_change<E: Environment>(
-    mut context: Context<World<E>>,
+    context: Context<World<E>>,
     params: Params<DidChangeTextDocumentParams>,
 ) {
-    let mut p = match params.optional() {
-        None => return,
-        Some(p) => p,
-    };
-
-    // We expect one full change
-    let change = match p.content_changes.pop() {
-        None => return,
-        Some(c) => c,
+    let Some(p) = params.optional() else {
+        return;
     };
 
     let mut workspaces = context.workspaces.write().await;
@@ -108,34 +87,21 @@ pub(crate) async fn document_change<E: Environment>(
     if let Some(pth) = context.env.to_file_path_normalized(&p.text_document.uri) {
         if !ws.taplo_config.is_included(&pth) {
             drop(workspaces);
-            context
-                .write_notification::<notification::PublishDiagnostics, _>(Some(
-                    PublishDiagnosticsParams {
-                        uri: p.text_document.uri.clone(),
-                        diagnostics: vec![Diagnostic {
-                            range: Default::default(),
-                            severity: Some(DiagnosticSeverity::HINT),
-                            code: None,
-                            code_description: None,
-                            source: Some("Even Better TOML".into()),
-                            message: "this document has been excluded".into(),
-                            related_information: None,
-                            tags: None,
-                            data: None,
-                        }],
-                        version: None,
-                    },
-                ))
-                .await
-                .unwrap_or_else(|err| tracing::error!("{err}"));
+            diagnostics::publish_excluded(context.clone(), p.text_document.uri).await;
             return;
         }
     }
 
-    let parse = taplo::parser::parse(&change.text);
-    let mapper = Mapper::new_utf16(&change.text, false);
+    let options = ws.parse_options(&context.env, &p.text_document.uri);
+    let Some(doc) = ws.documents.get_mut(&p.text_document.uri) else {
+        tracing::error!("received a change for a document that is not open");
+        return;
+    };
+
+    apply_changes(doc, p.content_changes, options);
+    doc.version = p.text_document.version;
 
-    let dom = parse.clone().into_dom();
+    let dom = doc.dom.clone();
 
     if ws.config.schema.enabled {
         ws.schemas
@@ -144,16 +110,52 @@ pub(crate) async fn document_change<E: Environment>(
         ws.emit_associations(context.clone()).await;
     }
 
-    ws.documents.insert(
-        p.text_document.uri.clone(),
-        DocumentState { parse, dom, mapper },
-    );
+    ws.invalidate_symbols(&p.text_document.uri);
 
     let ws_root = ws.root.clone();
     drop(workspaces);
     diagnostics::publish_diagnostics(context.clone(), ws_root, p.text_document.uri).await;
 }
 
+/// Applies the changes to the document in order.
+///
+/// Changes with a range only reparse the edited parts of the document,
+/// a change without a range replaces the whole document.
+///
+/// Ranges outside the document are clamped to it, as the LSP specification
+/// requires, so the document stays usable even if the client sent them.
+fn apply_changes(
+    doc: &mut DocumentState,
+    changes: Vec<TextDocumentContentChangeEvent>,
+    options: ParseOptions,
+) {
+    if changes.is_empty() {
+        return;
+    }
+
+    for change in changes {
+        let Some(range) = change.range else {
+            *doc = DocumentState::new(change.text, options);
+            continue;
+        };
+
+        let range = Range::from_lsp(range);
+        let edit = doc.mapper.text_range(range).unwrap_or_else(|| {
+            tracing::warn!(?range, "change range is out of bounds");
+            let start = doc.mapper.offset_clamped(range.start);
+            TextRange::new(start, doc.mapper.offset_clamped(range.end).max(start))
+        });
+
+        doc.text
+            .replace_range(std::ops::Range::<usize>::from(edit), &change.text);
+        doc.parse = reparse_with(&doc.parse, &doc.text, edit, options);
+        doc.mapper
+            .edit(&doc.text, edit, TextSize::of(change.text.as_str()));
+    }
+
+    doc.dom = doc.parse.clone().into_dom();
+}
+
 #[tracing::instrument(skip_all)]
 pub(crate) async fn document_save<E: Environment>(
     _context: Context<World<E>>,
@@ -167,19 +169,93 @@ pub(crate) async fn document_close<E: Environment>(
     context: Context<World<E>>,
     params: Params<DidCloseTextDocumentParams>,
 ) {
-    let p = match params.optional() {
-        None => return,
-        Some(p) => p,
+    let Some(p) = params.optional() else {
+        return;
     };
 
     let mut workspaces = context.workspaces.write().await;
     let ws = workspaces.by_document_mut(&p.text_document.uri);
 
     ws.documents.remove(&p.text_document.uri);
+    ws.invalidate_symbols(&p.text_document.uri);
     drop(workspaces);
 
+    context
+        .semantic_tokens
+        .lock()
+        .await
+        .remove(&p.text_document.uri);
+    context
+        .key_modifiers
+        .lock()
+        .await
+        .remove(&p.text_document.uri);
+
     context.env.spawn_local(diagnostics::clear_diagnostics(
         context.clone(),
         p.text_document.uri,
     ));
 }
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{Position, Range, TextDocumentContentChangeEvent};
+    use taplo::parser::ParseOptions;
+
+    use super::apply_changes;
+    use crate::world::DocumentState;
+
+    fn change(start: (u32, u32), end: (u32, u32), text: &str) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: Some(Range::new(
+                Position::new(start.0, start.1),
+                Position::new(end.0, end.1),
+            )),
+            range_length: None,
+            text: text.into(),
+        }
+    }
+
+    fn assert_document(doc: &DocumentState, expected: &str) {
+        let fresh = DocumentState::new(expected.into(), ParseOptions::default());
+
+        assert_eq!(doc.text, expected);
+        assert_eq!(doc.parse.green_node, fresh.parse.green_node);
+        assert_eq!(doc.mapper.all_range(), fresh.mapper.all_range());
+        assert_eq!(doc.dom.to_string(), fresh.dom.to_string());
+    }
+
+    #[test]
+    fn incremental_changes() {
+        let mut doc = DocumentState::new("a = 1\nb = 2\n".into(), ParseOptions::default());
+
+        apply_changes(
+            &mut doc,
+            vec![change((0, 4), (0, 5), "10"), change((1, 0), (2, 0), "")],
+            ParseOptions::default(),
+        );
+
+        assert_document(&doc, "a = 10\n");
+    }
+
+    #[test]
+    fn out_of_range_change_is_clamped() {
+        let mut doc = DocumentState::new("a = 1\nb = 2".into(), ParseOptions::default());
+
+        // The range is after the end of the last line and after the last line.
+        apply_changes(
+            &mut doc,
+            vec![change((1, 10), (5, 0), "\nc = 3")],
+            ParseOptions::default(),
+        );
+        assert_document(&doc, "a = 1\nb = 2\nc = 3");
+
+        // Later incremental changes still apply to the same text as the client.
+        apply_changes(
+            &mut doc,
+            vec![change((0, 4), (0, 5), "42")],
+            ParseOptions::default(),
+        );
+        assert_document(&doc, "a = 42\nb = 2\nc = 3");
+    }
+}
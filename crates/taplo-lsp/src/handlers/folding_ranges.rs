@@ -102,53 +102,50 @@ pub fn create_folding_ranges(syntax: &SyntaxNode, mapper: &Mapper) -> Vec<Foldin
                     SyntaxElement::Node(n) => {
                         for d in n.descendants_with_tokens() {
                             match d.kind() {
-                                ARRAY => {
+                                ARRAY
                                     if d.as_node()
                                         .unwrap()
                                         .descendants_with_tokens()
-                                        .any(|t| t.kind() == NEWLINE)
-                                    {
-                                        let start =
-                                            mapper.position(d.text_range().start()).unwrap();
-                                        let end = mapper
-                                            .position(
-                                                d.text_range()
-                                                    .end()
-                                                    .checked_sub(1.into())
-                                                    .unwrap_or_default(),
-                                            )
-                                            .unwrap();
-
-                                        folding_ranges.push(FoldingRange {
-                                            start_line: start.line as u32,
-                                            start_character: Some(start.character as u32),
-                                            end_line: end.line as u32,
-                                            end_character: Some(end.character as u32),
-                                            kind: Some(FoldingRangeKind::Region),
-                                        });
-                                    }
+                                        .any(|t| t.kind() == NEWLINE) =>
+                                {
+                                    let start = mapper.position(d.text_range().start()).unwrap();
+                                    let end = mapper
+                                        .position(
+                                            d.text_range()
+                                                .end()
+                                                .checked_sub(1.into())
+                                                .unwrap_or_default(),
+                                        )
+                                        .unwrap();
+
+                                    folding_ranges.push(FoldingRange {
+                                        start_line: start.line as u32,
+                                        start_character: Some(start.character as u32),
+                                        end_line: end.line as u32,
+                                        end_character: Some(end.character as u32),
+                                        kind: Some(FoldingRangeKind::Region),
+                                    });
                                 }
-                                MULTI_LINE_STRING | MULTI_LINE_STRING_LITERAL => {
-                                    if d.as_token().unwrap().text().contains('\n') {
-                                        let start =
-                                            mapper.position(d.text_range().start()).unwrap();
-                                        let end = mapper
-                                            .position(
-                                                d.text_range()
-                                                    .end()
-                                                    .checked_sub(1.into())
-                                                    .unwrap_or_default(),
-                                            )
-                                            .unwrap();
-
-                                        folding_ranges.push(FoldingRange {
-                                            start_line: start.line as u32,
-                                            start_character: Some(start.character as u32),
-                                            end_line: end.line as u32,
-                                            end_character: Some(end.character as u32),
-                                            kind: Some(FoldingRangeKind::Region),
-                                        });
-                                    }
+                                MULTI_LINE_STRING | MULTI_LINE_STRING_LITERAL
+                                    if d.as_token().unwrap().text().contains('\n') =>
+                                {
+                                    let start = mapper.position(d.text_range().start()).unwrap();
+                                    let end = mapper
+                                        .position(
+                                            d.text_range()
+                                                .end()
+                                                .checked_sub(1.into())
+                                                .unwrap_or_default(),
+                                        )
+                                        .unwrap();
+
+                                    folding_ranges.push(FoldingRange {
+                                        start_line: start.line as u32,
+                                        start_character: Some(start.character as u32),
+                                        end_line: end.line as u32,
+                                        end_character: Some(end.character as u32),
+                                        kind: Some(FoldingRangeKind::Region),
+                                    });
                                 }
                                 _ => {}
                             }
@@ -162,11 +159,9 @@ pub fn create_folding_ranges(syntax: &SyntaxNode, mapper: &Mapper) -> Vec<Foldin
                             last_comment = Some(t.text_range());
                             is_comment = true;
                         }
-                        NEWLINE => {
-                            if was_comment && t.text().matches('\n').count() == 1 {
-                                // Ignore a single newline when counting comments
-                                is_comment = true;
-                            }
+                        NEWLINE if was_comment && t.text().matches('\n').count() == 1 => {
+                            // Ignore a single newline when counting comments
+                            is_comment = true;
                         }
                         _ => {}
                     },
@@ -1,9 +1,25 @@
-use lsp_async_stub::{rpc::Error, util::LspExt, Context, Params};
-use lsp_types::{DocumentFormattingParams, TextEdit};
-use taplo::formatter;
+use lsp_async_stub::{
+    rpc::Error,
+    util::{LspExt, Position, Range},
+    Context, Params,
+};
+use lsp_types::{
+    DocumentFormattingParams, DocumentOnTypeFormattingParams, DocumentRangeFormattingParams,
+    FormattingOptions, TextEdit, Url,
+};
+use taplo::{
+    dom::node::DomNode,
+    formatter::{self, KeyOrder},
+    rowan::{Direction, NodeOrToken, TextRange},
+    syntax::{SyntaxKind::*, SyntaxToken},
+};
 use taplo_common::environment::Environment;
 
-use crate::World;
+use crate::{
+    query::Query,
+    world::{DocumentState, WorkspaceState},
+    World,
+};
 
 #[tracing::instrument(skip_all)]
 pub(crate) async fn format<E: Environment>(
@@ -22,27 +38,316 @@ pub(crate) async fn format<E: Environment>(
         }
     };
 
-    let doc_path = context
-        .env
-        .to_file_path_normalized(&p.text_document.uri)
-        .ok_or_else(|| {
-            Error::invalid_request().with_data(format!(
-                "invalid (non-local) uri for file: {}",
-                p.text_document.uri
-            ))
-        })?;
-
-    let mut format_opts = formatter::Options {
-        indent_string: if p.options.insert_spaces {
-            " ".repeat(p.options.tab_size as usize)
+    let (format_opts, scopes) =
+        format_options(&context, ws, &p.text_document.uri, Some(&p.options))?;
+    let key_order = key_order(ws, &p.text_document.uri, doc, &format_opts, &scopes).await;
+
+    Ok(Some(document_edits(doc, format_opts, scopes, key_order)?))
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn format_range<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<DocumentRangeFormattingParams>,
+) -> Result<Option<Vec<TextEdit>>, Error> {
+    let p = params.required()?;
+
+    let workspaces = context.workspaces.read().await;
+    let ws = workspaces.by_document(&p.text_document.uri);
+    let doc = match ws.document(&p.text_document.uri) {
+        Ok(d) => d,
+        Err(error) => {
+            tracing::debug!(%error, "failed to get document from workspace");
+            return Ok(None);
+        }
+    };
+
+    let Some(range) = doc.mapper.text_range(Range::from_lsp(p.range)) else {
+        tracing::debug!(range = ?p.range, "range out of bounds");
+        return Ok(None);
+    };
+
+    let (format_opts, scopes) =
+        format_options(&context, ws, &p.text_document.uri, Some(&p.options))?;
+
+    let errors = doc
+        .parse
+        .errors
+        .iter()
+        .map(|err| err.range)
+        .collect::<Vec<_>>();
+
+    let edits = range_edits(doc, range, format_opts, &errors, scopes)?;
+
+    Ok(Some(text_edits(doc, edits)))
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn format_on_type<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<DocumentOnTypeFormattingParams>,
+) -> Result<Option<Vec<TextEdit>>, Error> {
+    let p = params.required()?;
+    let document_uri = &p.text_document_position.text_document.uri;
+
+    let workspaces = context.workspaces.read().await;
+    let ws = workspaces.by_document(document_uri);
+    let doc = match ws.document(document_uri) {
+        Ok(d) => d,
+        Err(error) => {
+            tracing::debug!(%error, "failed to get document from workspace");
+            return Ok(None);
+        }
+    };
+
+    let position = p.text_document_position.position;
+    let Some(offset) = doc.mapper.offset(Position::from_lsp(position)) else {
+        tracing::debug!(?position, "document position not found");
+        return Ok(None);
+    };
+
+    let (format_opts, scopes) = format_options(&context, ws, document_uri, Some(&p.options))?;
+
+    let query = Query::at(&doc.dom, offset);
+
+    let errors = doc.parse.errors.iter().map(|err| err.range);
+
+    let edits = match p.ch.as_str() {
+        "]" => {
+            let Some(header) = query.closed_header() else {
+                return Ok(None);
+            };
+
+            range_edits(
+                doc,
+                header.text_range(),
+                format_opts,
+                &errors.collect::<Vec<_>>(),
+                scopes,
+            )?
+        }
+        "=" => {
+            let Some(entry) = query.entry_after_eq() else {
+                return Ok(None);
+            };
+
+            // The value is usually not typed yet,
+            // so the missing value is not an error here.
+            let entry_range = entry.text_range();
+            let errors = errors
+                .filter(|r| !entry_range.contains_inclusive(r.start()))
+                .collect::<Vec<_>>();
+
+            range_edits(doc, entry_range, format_opts, &errors, scopes)?
+        }
+        "\n" => {
+            let Some(line_break) = query.array_line_break() else {
+                return Ok(None);
+            };
+
+            array_line_break_edits(&line_break, &format_opts)
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(text_edits(doc, edits)))
+}
+
+/// Formats the parts of the document that overlap the range.
+fn range_edits(
+    doc: &DocumentState,
+    range: TextRange,
+    options: formatter::Options,
+    errors: &[TextRange],
+    scopes: Vec<(String, formatter::OptionsIncomplete)>,
+) -> Result<Vec<(TextRange, String)>, Error> {
+    formatter::format_range(doc.dom.clone(), range, options, errors, scopes).map_err(|err| {
+        tracing::error!(error = %err, "invalid key pattern");
+        Error::internal_error().with_data("invalid Taplo configuration")
+    })
+}
+
+/// Indents the line after a line break typed inside an array,
+/// and separates the item before the line break with a comma
+/// if there is another item after it or trailing commas are enabled.
+fn array_line_break_edits(
+    line_break: &SyntaxToken,
+    options: &formatter::Options,
+) -> Vec<(TextRange, String)> {
+    let mut edits = Vec::new();
+
+    let last_item = line_break
+        .siblings_with_tokens(Direction::Prev)
+        .skip(1)
+        .find(|s| !matches!(s.kind(), WHITESPACE | NEWLINE | COMMENT));
+
+    if let Some(NodeOrToken::Node(item)) = last_item {
+        let has_next_item = line_break
+            .siblings_with_tokens(Direction::Next)
+            .any(|s| s.kind() == VALUE);
+
+        if item.kind() == VALUE && (has_next_item || options.array_trailing_comma) {
+            edits.push((TextRange::empty(item.text_range().end()), ",".into()));
+        }
+    }
+
+    let Some(bracket_start) = line_break.parent().and_then(|array| array.first_token()) else {
+        return edits;
+    };
+
+    let mut indent = line_indent(&bracket_start);
+
+    let whitespace = line_break.next_token().filter(|t| t.kind() == WHITESPACE);
+
+    let next = whitespace
+        .as_ref()
+        .map_or_else(|| line_break.next_token(), SyntaxToken::next_token);
+
+    // The closing bracket stays at the same level as the opening one.
+    if !next.is_some_and(|t| t.kind() == BRACKET_END) {
+        indent += &options.indent_string;
+    }
+
+    match whitespace {
+        Some(ws) if ws.text() == indent => {}
+        None if indent.is_empty() => {}
+        Some(ws) => edits.push((ws.text_range(), indent)),
+        None => edits.push((TextRange::empty(line_break.text_range().end()), indent)),
+    }
+
+    edits
+}
+
+/// The whitespace at the start of the line of the token.
+fn line_indent(token: &SyntaxToken) -> String {
+    let mut first = token.clone();
+
+    while let Some(prev) = first.prev_token() {
+        if prev.kind() == NEWLINE {
+            break;
+        }
+        first = prev;
+    }
+
+    if first.kind() == WHITESPACE {
+        first.text().to_string()
+    } else {
+        String::new()
+    }
+}
+
+/// The edits that format the whole document.
+pub(super) fn document_edits(
+    doc: &DocumentState,
+    format_opts: formatter::Options,
+    scopes: Vec<(String, formatter::OptionsIncomplete)>,
+    key_order: KeyOrder,
+) -> Result<Vec<TextEdit>, Error> {
+    let original = doc.dom.syntax().unwrap().to_string();
+    let formatted = taplo::formatter::format_with_key_order(
+        doc.dom.clone(),
+        format_opts,
+        &doc.parse
+            .errors
+            .iter()
+            .map(|err| err.range)
+            .collect::<Vec<_>>(),
+        scopes,
+        key_order,
+    )
+    .map_err(|err| {
+        tracing::error!(error = %err, "invalid key pattern");
+        Error::internal_error().with_data("invalid Taplo configuration")
+    })?;
+
+    // Only the changed parts are replaced so that editors can keep
+    // the cursor position and other state tied to the unchanged text.
+    let edits = formatter::diff::Diff::new(&original, &formatted).text_edits();
+
+    Ok(text_edits(doc, edits))
+}
+
+/// The key order declared by the schema of the document
+/// if the formatter reorders keys by schema anywhere in it.
+pub(super) async fn key_order<E: Environment>(
+    ws: &WorkspaceState<E>,
+    uri: &Url,
+    doc: &DocumentState,
+    format_opts: &formatter::Options,
+    scopes: &[(String, formatter::OptionsIncomplete)],
+) -> KeyOrder {
+    let reorder = format_opts.reorder_by_schema
+        || scopes
+            .iter()
+            .any(|(_, opts)| opts.reorder_by_schema == Some(true));
+
+    if !reorder || !ws.config.schema.enabled {
+        return KeyOrder::default();
+    }
+
+    let Some(schema_association) = ws.schemas.associations().association_for(uri) else {
+        return KeyOrder::default();
+    };
+
+    match ws
+        .schemas
+        .key_order(&schema_association.url, &doc.dom)
+        .await
+    {
+        Ok(key_order) => key_order,
+        Err(error) => {
+            tracing::warn!(%error, "failed to get key order from schema");
+            KeyOrder::default()
+        }
+    }
+}
+
+/// Converts formatter edits to LSP edits of the document.
+pub(super) fn text_edits(doc: &DocumentState, edits: Vec<(TextRange, String)>) -> Vec<TextEdit> {
+    edits
+        .into_iter()
+        .filter_map(|(range, new_text)| {
+            Some(TextEdit {
+                range: doc.mapper.range(range)?.into_lsp(),
+                new_text,
+            })
+        })
+        .collect()
+}
+
+/// The formatter options and scopes for a document
+/// based on the editor and the workspace configuration.
+///
+/// Without editor options, e.g. when formatting files that are not open,
+/// only the workspace configuration is used.
+pub(super) fn format_options<E: Environment>(
+    context: &Context<World<E>>,
+    ws: &WorkspaceState<E>,
+    uri: &Url,
+    options: Option<&FormattingOptions>,
+) -> Result<
+    (
+        formatter::Options,
+        Vec<(String, formatter::OptionsIncomplete)>,
+    ),
+    Error,
+> {
+    let doc_path = context.env.to_file_path_normalized(uri).ok_or_else(|| {
+        Error::invalid_request().with_data(format!("invalid (non-local) uri for file: {uri}"))
+    })?;
+
+    let mut format_opts = formatter::Options::default();
+
+    if let Some(options) = options {
+        format_opts.indent_string = if options.insert_spaces {
+            " ".repeat(options.tab_size as usize)
         } else {
             "\t".into()
-        },
-        ..Default::default()
-    };
+        };
 
-    if let Some(v) = p.options.insert_final_newline {
-        format_opts.trailing_newline = v;
+        if let Some(v) = options.insert_final_newline {
+            format_opts.trailing_newline = v;
+        }
     }
 
     format_opts.update_camel(ws.config.formatter.clone());
@@ -59,21 +364,8 @@ pub(crate) async fn format<E: Environment>(
         matched_rules = ?ws.taplo_config.rules_for(&doc_path).collect::<Vec<_>>(),
     );
 
-    Ok(Some(vec![TextEdit {
-        range: doc.mapper.all_range().into_lsp(),
-        new_text: taplo::formatter::format_with_path_scopes(
-            doc.dom.clone(),
-            format_opts,
-            &doc.parse
-                .errors
-                .iter()
-                .map(|err| err.range)
-                .collect::<Vec<_>>(),
-            scopes.into_iter(),
-        )
-        .map_err(|err| {
-            tracing::error!(error = %err, "invalid key pattern");
-            Error::internal_error().with_data("invalid Taplo configuration")
-        })?,
-    }]))
+    Ok((
+        format_opts,
+        scopes.map(|(keys, opts)| (keys.clone(), opts)).collect(),
+    ))
 }
@@ -1,18 +1,26 @@
-use std::sync::Arc;
+use std::sync::{atomic::Ordering, Arc};
 
-use super::{semantic_tokens, update_configuration};
+use super::{semantic_tokens, update_configuration, COMMANDS};
 use crate::config::InitConfig;
+use crate::lsp_ext::request::{
+    DiagnosticOptions, InitializeParams, InitializeResult, ServerCapabilities,
+};
 use crate::world::WorkspaceState;
 use crate::World;
-use lsp_async_stub::{rpc::Error, Context, Params};
+use lsp_async_stub::{rpc::Error, Context, Params, RequestWriter};
 use lsp_types::{
-    CompletionOptions, DocumentLinkOptions, FoldingRangeProviderCapability,
-    HoverProviderCapability, InitializedParams, OneOf, RenameOptions, SemanticTokensFullOptions,
-    SemanticTokensLegend, SemanticTokensOptions, SemanticTokensServerCapabilities,
-    ServerCapabilities, ServerInfo, TextDocumentSyncCapability, TextDocumentSyncKind,
-    WorkDoneProgressOptions, WorkspaceFoldersServerCapabilities, WorkspaceServerCapabilities,
+    notification::{DidChangeWatchedFiles, Notification},
+    request::RegisterCapability,
+    CodeActionKind, CodeActionOptions, CodeActionProviderCapability, CodeLensOptions,
+    CompletionOptions, DidChangeWatchedFilesRegistrationOptions, DocumentLinkOptions,
+    DocumentOnTypeFormattingOptions, ExecuteCommandOptions, FileSystemWatcher,
+    FoldingRangeProviderCapability, HoverProviderCapability, InitializedParams, OneOf,
+    Registration, RegistrationParams, RenameOptions, SelectionRangeProviderCapability,
+    SemanticTokensFullOptions, SemanticTokensLegend, SemanticTokensOptions,
+    SemanticTokensServerCapabilities, ServerInfo, TextDocumentSyncCapability, TextDocumentSyncKind,
+    TypeDefinitionProviderCapability, WorkDoneProgressOptions, WorkspaceFoldersServerCapabilities,
+    WorkspaceServerCapabilities,
 };
-use lsp_types::{InitializeParams, InitializeResult};
 use taplo_common::environment::Environment;
 
 #[tracing::instrument(skip_all)]
@@ -20,7 +28,29 @@ pub async fn initialize<E: Environment>(
     context: Context<World<E>>,
     params: Params<InitializeParams>,
 ) -> Result<InitializeResult, Error> {
-    let p = params.required()?;
+    let InitializeParams {
+        params: p,
+        diagnostic_pull,
+        diagnostic_refresh,
+    } = params.required()?;
+
+    context
+        .diagnostic_pull
+        .store(diagnostic_pull, Ordering::Relaxed);
+    context
+        .diagnostic_refresh
+        .store(diagnostic_refresh, Ordering::Relaxed);
+
+    let file_watching = p
+        .capabilities
+        .workspace
+        .as_ref()
+        .and_then(|ws| ws.did_change_watched_files.as_ref())
+        .and_then(|caps| caps.dynamic_registration)
+        .unwrap_or(false);
+    context
+        .file_watching
+        .store(file_watching, Ordering::Relaxed);
 
     if let Some(init_opts) = p.initialization_options {
         match serde_json::from_value::<InitConfig>(init_opts) {
@@ -52,58 +82,97 @@ pub async fn initialize<E: Environment>(
 
     Ok(InitializeResult {
         capabilities: ServerCapabilities {
-            workspace: Some(WorkspaceServerCapabilities {
-                workspace_folders: Some(WorkspaceFoldersServerCapabilities {
-                    supported: Some(true),
-                    change_notifications: Some(OneOf::Left(true)),
+            capabilities: lsp_types::ServerCapabilities {
+                workspace: Some(WorkspaceServerCapabilities {
+                    workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+                        supported: Some(true),
+                        change_notifications: Some(OneOf::Left(true)),
+                    }),
+                    ..Default::default()
                 }),
-                ..Default::default()
-            }),
-            text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
-            semantic_tokens_provider: Some(
-                SemanticTokensServerCapabilities::SemanticTokensOptions(SemanticTokensOptions {
-                    work_done_progress_options: WorkDoneProgressOptions {
-                        work_done_progress: false.into(),
-                    },
-                    legend: SemanticTokensLegend {
-                        token_types: semantic_tokens::TokenType::LEGEND.into(),
-                        token_modifiers: semantic_tokens::TokenModifier::MODIFIERS.into(),
+                text_document_sync: Some(TextDocumentSyncCapability::Kind(
+                    TextDocumentSyncKind::INCREMENTAL,
+                )),
+                semantic_tokens_provider: Some(
+                    SemanticTokensServerCapabilities::SemanticTokensOptions(
+                        SemanticTokensOptions {
+                            work_done_progress_options: WorkDoneProgressOptions {
+                                work_done_progress: false.into(),
+                            },
+                            legend: SemanticTokensLegend {
+                                token_types: semantic_tokens::TokenType::LEGEND.into(),
+                                token_modifiers: semantic_tokens::TokenModifier::MODIFIERS.into(),
+                            },
+                            full: Some(SemanticTokensFullOptions::Delta { delta: Some(true) }),
+                            range: Some(true),
+                        },
+                    ),
+                ),
+                rename_provider: Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: Default::default(),
+                })),
+                definition_provider: Some(OneOf::Left(true)),
+                references_provider: Some(OneOf::Left(true)),
+                document_highlight_provider: Some(OneOf::Left(true)),
+                type_definition_provider: Some(TypeDefinitionProviderCapability::Simple(true)),
+                folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+                document_symbol_provider: Some(OneOf::Left(true)),
+                workspace_symbol_provider: Some(OneOf::Left(true)),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                document_range_formatting_provider: Some(OneOf::Left(true)),
+                document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                    first_trigger_character: "]".into(),
+                    more_trigger_character: Some(vec!["=".into(), "\n".into()]),
+                }),
+                code_action_provider: Some(CodeActionProviderCapability::Options(
+                    CodeActionOptions {
+                        code_action_kinds: Some(vec![
+                            CodeActionKind::QUICKFIX,
+                            CodeActionKind::REFACTOR_REWRITE,
+                        ]),
+                        work_done_progress_options: WorkDoneProgressOptions::default(),
+                        resolve_provider: Some(true),
                     },
-                    full: Some(SemanticTokensFullOptions::Bool(true)),
-                    range: Some(false),
+                )),
+                code_lens_provider: Some(CodeLensOptions {
+                    resolve_provider: Some(false),
+                }),
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: COMMANDS.iter().map(|command| (*command).into()).collect(),
+                    work_done_progress_options: WorkDoneProgressOptions::default(),
+                }),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                inlay_hint_provider: Some(OneOf::Left(true)),
+                completion_provider: Some(CompletionOptions {
+                    resolve_provider: Some(false),
+                    trigger_characters: Some(vec![
+                        ".".into(),
+                        "=".into(),
+                        "[".into(),
+                        "{".into(),
+                        ",".into(),
+                        "\"".into(),
+                    ]),
+                    ..Default::default()
+                }),
+                document_link_provider: Some(DocumentLinkOptions {
+                    resolve_provider: None,
+                    work_done_progress_options: Default::default(),
                 }),
-            ),
-            rename_provider: Some(OneOf::Right(RenameOptions {
-                prepare_provider: Some(true),
-                work_done_progress_options: Default::default(),
-            })),
-            folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
-            document_symbol_provider: Some(OneOf::Left(true)),
-            document_formatting_provider: Some(OneOf::Left(true)),
-            hover_provider: Some(HoverProviderCapability::Simple(true)),
-            completion_provider: Some(CompletionOptions {
-                resolve_provider: Some(false),
-                trigger_characters: Some(vec![
-                    ".".into(),
-                    "=".into(),
-                    "[".into(),
-                    "{".into(),
-                    ",".into(),
-                    "\"".into(),
-                ]),
                 ..Default::default()
+            },
+            diagnostic_provider: Some(DiagnosticOptions {
+                identifier: None,
+                inter_file_dependencies: false,
+                workspace_diagnostics: true,
             }),
-            document_link_provider: Some(DocumentLinkOptions {
-                resolve_provider: None,
-                work_done_progress_options: Default::default(),
-            }),
-            ..Default::default()
         },
         server_info: Some(ServerInfo {
             name: "Taplo".into(),
             version: Some(env!("CARGO_PKG_VERSION").into()),
         }),
-        offset_encoding: None,
     })
 }
 
@@ -115,4 +184,34 @@ pub async fn initialized<E: Environment>(
     context
         .env
         .spawn_local(update_configuration(context.clone()));
+
+    if context.file_watching.load(Ordering::Relaxed) {
+        context.env.spawn_local(watch_files(context.clone()));
+    }
+}
+
+/// Asks the client to notify us about changes to TOML files,
+/// so that the symbol index stays up to date.
+#[tracing::instrument(skip_all)]
+async fn watch_files<E: Environment>(mut context: Context<World<E>>) {
+    let options = DidChangeWatchedFilesRegistrationOptions {
+        watchers: vec![FileSystemWatcher {
+            glob_pattern: "**/*.toml".into(),
+            kind: None,
+        }],
+    };
+
+    let res = context
+        .write_request::<RegisterCapability, _>(Some(RegistrationParams {
+            registrations: vec![Registration {
+                id: "taplo-watched-files".into(),
+                method: DidChangeWatchedFiles::METHOD.into(),
+                register_options: serde_json::to_value(options).ok(),
+            }],
+        }))
+        .await;
+
+    if let Err(error) = res {
+        tracing::error!(%error, "failed to register file watcher");
+    }
 }
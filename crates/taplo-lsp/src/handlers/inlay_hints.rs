@@ -0,0 +1,290 @@
+use std::fmt::Write;
+
+use itertools::Itertools;
+use lsp_async_stub::{
+    rpc::Error,
+    util::{LspExt, Range},
+    Context, Params,
+};
+use lsp_types::{InlayHint, InlayHintKind, InlayHintLabel, InlayHintParams, Url};
+use serde_json::Value;
+use taplo::{
+    dom::{node::DomNode, KeyOrIndex, Keys, Node},
+    rowan::TextSize,
+    syntax::{
+        SyntaxElement,
+        SyntaxKind::{IDENT, INLINE_TABLE, TABLE_ARRAY_HEADER, TABLE_HEADER},
+    },
+};
+use taplo_common::environment::Environment;
+
+use crate::{
+    config::InlayHintsConfig,
+    world::{DocumentState, WorkspaceState, World},
+};
+
+#[tracing::instrument(skip_all)]
+pub async fn inlay_hints<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<InlayHintParams>,
+) -> Result<Option<Vec<InlayHint>>, Error> {
+    let p = params.required()?;
+    let document_uri = p.text_document.uri;
+
+    let workspaces = context.workspaces.read().await;
+    let ws = workspaces.by_document(&document_uri);
+    let doc = match ws.document(&document_uri) {
+        Ok(d) => d,
+        Err(error) => {
+            tracing::debug!(%error, "failed to get document from workspace");
+            return Ok(None);
+        }
+    };
+
+    let Some(range) = doc.mapper.text_range(Range::from_lsp(p.range)) else {
+        tracing::debug!(range = ?p.range, "range out of bounds");
+        return Ok(None);
+    };
+
+    let config = &ws.config.inlay_hints;
+
+    // Every node with the offset its hints are placed at.
+    let nodes: Vec<(Keys, Node, TextSize)> = doc
+        .dom
+        .flat_iter()
+        .filter_map(|(keys, node)| {
+            let end = node.syntax()?.text_range().end();
+            range.contains_inclusive(end).then_some((keys, node, end))
+        })
+        .collect();
+
+    let mut hints = Vec::new();
+
+    if config.table_paths || config.array_indices {
+        header_hints(doc, config, &nodes, &mut hints);
+    }
+
+    if (config.schema_types || config.schema_defaults) && ws.config.schema.enabled {
+        if let Some(schema_association) = ws.schemas.associations().association_for(&document_uri) {
+            let value = match serde_json::to_value(&doc.dom) {
+                Ok(v) => v,
+                Err(error) => {
+                    tracing::warn!(%error, "cannot turn DOM into JSON");
+                    return Ok(Some(hints));
+                }
+            };
+
+            let schema = SchemaContext {
+                ws,
+                doc,
+                url: &schema_association.url,
+                value: &value,
+            };
+
+            if config.schema_types {
+                schema.type_hints(&nodes, &mut hints).await;
+            }
+
+            if config.schema_defaults {
+                schema.default_hints(&nodes, &mut hints).await;
+            }
+        }
+    }
+
+    Ok(Some(hints))
+}
+
+/// Paths of table headers and the indices of array of tables elements.
+fn header_hints(
+    doc: &DocumentState,
+    config: &InlayHintsConfig,
+    nodes: &[(Keys, Node, TextSize)],
+    hints: &mut Vec<InlayHint>,
+) {
+    for (keys, node, end) in nodes {
+        let Some(syntax) = node.syntax() else {
+            continue;
+        };
+
+        // Header paths are always written in full, they only
+        // need resolving when they are inside an array of tables.
+        let parent_in_array = keys.skip_right(1).iter().any(KeyOrIndex::is_index);
+
+        let label = match (syntax.kind(), keys.iter().last()) {
+            (TABLE_HEADER, Some(KeyOrIndex::Key(_)))
+            | (TABLE_ARRAY_HEADER, Some(KeyOrIndex::Index(_)))
+                if config.table_paths && parent_in_array =>
+            {
+                display_path(keys)
+            }
+            (TABLE_ARRAY_HEADER, Some(KeyOrIndex::Index(idx))) if config.array_indices => {
+                format!("[{idx}]")
+            }
+            _ => continue,
+        };
+
+        if let Some(hint) = hint(doc, *end, label, None) {
+            hints.push(hint);
+        }
+    }
+}
+
+struct SchemaContext<'a, E: Environment> {
+    ws: &'a WorkspaceState<E>,
+    doc: &'a DocumentState,
+    url: &'a Url,
+    value: &'a Value,
+}
+
+impl<E: Environment> SchemaContext<'_, E> {
+    /// The types of values from the schema.
+    async fn type_hints(&self, nodes: &[(Keys, Node, TextSize)], hints: &mut Vec<InlayHint>) {
+        for (keys, node, end) in nodes {
+            if !matches!(keys.iter().last(), Some(KeyOrIndex::Key(_))) {
+                continue;
+            }
+
+            // Tables from headers and dotted keys are not values.
+            if node.syntax().map_or(true, |s| {
+                matches!(s.kind(), TABLE_HEADER | TABLE_ARRAY_HEADER | IDENT)
+            }) {
+                continue;
+            }
+
+            let schemas = match self
+                .ws
+                .schemas
+                .schemas_at_path(self.url, self.value, keys)
+                .await
+            {
+                Ok(s) => s,
+                Err(error) => {
+                    tracing::debug!(?error, "schema resolution failed");
+                    continue;
+                }
+            };
+
+            let types = schemas
+                .iter()
+                .flat_map(|(_, schema)| match &schema["type"] {
+                    Value::String(ty) => vec![ty.as_str()],
+                    Value::Array(tys) => tys.iter().filter_map(Value::as_str).collect(),
+                    _ => Vec::new(),
+                })
+                .unique()
+                .join(" | ");
+
+            if types.is_empty() {
+                continue;
+            }
+
+            if let Some(hint) = hint(
+                self.doc,
+                *end,
+                format!(": {types}"),
+                Some(InlayHintKind::TYPE),
+            ) {
+                hints.push(hint);
+            }
+        }
+    }
+
+    /// The defaults of the properties that are missing from tables.
+    async fn default_hints(&self, nodes: &[(Keys, Node, TextSize)], hints: &mut Vec<InlayHint>) {
+        for (keys, node, end) in nodes {
+            let Node::Table(table) = node else {
+                continue;
+            };
+
+            let is_table = matches!(
+                (table.syntax().map(SyntaxElement::kind), keys.iter().last()),
+                (Some(TABLE_HEADER | INLINE_TABLE), Some(KeyOrIndex::Key(_)))
+                    | (Some(TABLE_ARRAY_HEADER), Some(KeyOrIndex::Index(_)))
+            );
+
+            if !is_table {
+                continue;
+            }
+
+            let schemas = match self
+                .ws
+                .schemas
+                .possible_schemas_from(self.url, self.value, keys, 2)
+                .await
+            {
+                Ok(s) => s,
+                Err(error) => {
+                    tracing::debug!(?error, "schema resolution failed");
+                    continue;
+                }
+            };
+
+            let entries = table.entries().read();
+
+            let defaults = schemas
+                .iter()
+                .filter_map(|(_, relative_keys, schema)| {
+                    let Ok(KeyOrIndex::Key(key)) = relative_keys.iter().exactly_one() else {
+                        return None;
+                    };
+
+                    if entries.iter().any(|(k, _)| k.value() == key.value()) {
+                        return None;
+                    }
+
+                    let default_value = schema.get("default").filter(|v| !v.is_null())?;
+                    let node: Node = serde_json::from_value(default_value.clone()).ok()?;
+
+                    Some((key.clone(), node.to_toml(true, false)))
+                })
+                .unique_by(|(key, _)| key.value().to_string());
+
+            for (key, toml_value) in defaults {
+                if let Some(hint) = hint(self.doc, *end, format!("{key} = {toml_value}"), None) {
+                    hints.push(hint);
+                }
+            }
+        }
+    }
+}
+
+fn hint(
+    doc: &DocumentState,
+    offset: TextSize,
+    label: String,
+    kind: Option<InlayHintKind>,
+) -> Option<InlayHint> {
+    let padding_left = kind != Some(InlayHintKind::TYPE);
+
+    Some(InlayHint {
+        position: doc.mapper.position(offset)?.into_lsp(),
+        label: InlayHintLabel::String(label),
+        kind,
+        text_edits: None,
+        tooltip: None,
+        padding_left: Some(padding_left),
+        padding_right: None,
+        data: None,
+    })
+}
+
+/// The path with indices in brackets, e.g. `fruit[1].variety`.
+fn display_path(keys: &Keys) -> String {
+    let mut path = String::new();
+
+    for key in keys.iter() {
+        match key {
+            KeyOrIndex::Key(k) => {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                let _ = write!(path, "{k}");
+            }
+            KeyOrIndex::Index(idx) => {
+                let _ = write!(path, "[{idx}]");
+            }
+        }
+    }
+
+    path
+}
@@ -0,0 +1,172 @@
+use crate::query::{PositionInfo, Query};
+use crate::world::{DocumentState, World};
+use lsp_async_stub::rpc::Error;
+use lsp_async_stub::util::{LspExt, Position};
+use lsp_async_stub::{Context, Params};
+use lsp_types::{
+    DocumentHighlight, DocumentHighlightKind, DocumentHighlightParams, GotoDefinitionParams,
+    GotoDefinitionResponse, Location, ReferenceParams, Url,
+};
+use taplo::dom::KeyOrIndex;
+use taplo::rowan::{TextRange, TextSize};
+use taplo::syntax::SyntaxKind;
+use taplo_common::environment::Environment;
+
+#[tracing::instrument(skip_all)]
+pub async fn goto_definition<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<GotoDefinitionParams>,
+) -> Result<Option<GotoDefinitionResponse>, Error> {
+    let p = params.required()?;
+    let document_uri = p.text_document_position_params.text_document.uri;
+
+    let workspaces = context.workspaces.read().await;
+    let ws = workspaces.by_document(&document_uri);
+    let doc = match ws.document(&document_uri) {
+        Ok(d) => d,
+        Err(error) => {
+            tracing::debug!(%error, "failed to get document from workspace");
+            return Ok(None);
+        }
+    };
+
+    let position = p.text_document_position_params.position;
+    let Some(offset) = doc.mapper.offset(Position::from_lsp(position)) else {
+        tracing::error!(?position, "document position not found");
+        return Ok(None);
+    };
+
+    let Some((_, ranges)) = key_ranges(doc, offset) else {
+        return Ok(None);
+    };
+
+    Ok(Some(GotoDefinitionResponse::Array(locations(
+        doc,
+        &document_uri,
+        ranges,
+    ))))
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn references<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<ReferenceParams>,
+) -> Result<Option<Vec<Location>>, Error> {
+    let p = params.required()?;
+    let document_uri = p.text_document_position.text_document.uri;
+
+    let workspaces = context.workspaces.read().await;
+    let ws = workspaces.by_document(&document_uri);
+    let doc = match ws.document(&document_uri) {
+        Ok(d) => d,
+        Err(error) => {
+            tracing::debug!(%error, "failed to get document from workspace");
+            return Ok(None);
+        }
+    };
+
+    let position = p.text_document_position.position;
+    let Some(offset) = doc.mapper.offset(Position::from_lsp(position)) else {
+        tracing::error!(?position, "document position not found");
+        return Ok(None);
+    };
+
+    let Some((_, mut ranges)) = key_ranges(doc, offset) else {
+        return Ok(None);
+    };
+
+    // The first occurrence of the key is where it is declared.
+    if !p.context.include_declaration && !ranges.is_empty() {
+        ranges.remove(0);
+    }
+
+    Ok(Some(locations(doc, &document_uri, ranges)))
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn document_highlight<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<DocumentHighlightParams>,
+) -> Result<Option<Vec<DocumentHighlight>>, Error> {
+    let p = params.required()?;
+    let document_uri = p.text_document_position_params.text_document.uri;
+
+    let workspaces = context.workspaces.read().await;
+    let ws = workspaces.by_document(&document_uri);
+    let doc = match ws.document(&document_uri) {
+        Ok(d) => d,
+        Err(error) => {
+            tracing::debug!(%error, "failed to get document from workspace");
+            return Ok(None);
+        }
+    };
+
+    let position = p.text_document_position_params.position;
+    let Some(offset) = doc.mapper.offset(Position::from_lsp(position)) else {
+        tracing::error!(?position, "document position not found");
+        return Ok(None);
+    };
+
+    let Some((_, ranges)) = key_ranges(doc, offset) else {
+        return Ok(None);
+    };
+
+    Ok(Some(
+        ranges
+            .into_iter()
+            .filter_map(|range| {
+                Some(DocumentHighlight {
+                    range: doc.mapper.range(range)?.into_lsp(),
+                    kind: Some(DocumentHighlightKind::TEXT),
+                })
+            })
+            .collect(),
+    ))
+}
+
+/// The range of the key at the offset, and the ranges of all
+/// the keys in the document that refer to the same node in order.
+fn key_ranges(doc: &DocumentState, offset: TextSize) -> Option<(TextRange, Vec<TextRange>)> {
+    let query = Query::at(&doc.dom, offset);
+
+    let is_key = |p: &&PositionInfo| {
+        !matches!(p.syntax.kind(), SyntaxKind::PERIOD | SyntaxKind::WHITESPACE)
+            && p.syntax
+                .parent()
+                .is_some_and(|parent| parent.kind() == SyntaxKind::KEY)
+    };
+
+    let position_info = query
+        .before
+        .as_ref()
+        .filter(is_key)
+        .or_else(|| query.after.as_ref().filter(is_key))?;
+
+    let key_range = position_info.syntax.text_range();
+    let (keys, _) = position_info.dom_node.as_ref()?;
+
+    // The node at the cursor can be a child of the one the key belongs to,
+    // e.g. in table headers and dotted keys.
+    let key = keys
+        .iter()
+        .filter_map(KeyOrIndex::as_key)
+        .find(|k| k.text_ranges().any(|r| r == key_range))?;
+
+    let mut ranges: Vec<TextRange> = key.text_ranges().collect();
+    ranges.sort_by_key(|r| r.start());
+    ranges.dedup();
+
+    Some((key_range, ranges))
+}
+
+fn locations(doc: &DocumentState, uri: &Url, ranges: Vec<TextRange>) -> Vec<Location> {
+    ranges
+        .into_iter()
+        .filter_map(|range| {
+            Some(Location {
+                uri: uri.clone(),
+                range: doc.mapper.range(range)?.into_lsp(),
+            })
+        })
+        .collect()
+}
@@ -1,5 +1,5 @@
 use crate::{
-    diagnostics::publish_diagnostics,
+    diagnostics::{publish_diagnostics, refresh_diagnostics},
     lsp_ext::{
         notification::{self, AssociateSchemaParams},
         request::{
@@ -116,6 +116,9 @@ pub async fn associate_schema<E: Environment>(
         }
         ws.emit_associations(context.clone()).await;
     }
+
+    drop(workspaces);
+    refresh_diagnostics(context).await;
 }
 
 #[tracing::instrument(skip_all)]
@@ -0,0 +1,299 @@
+use lsp_async_stub::{
+    rpc::Error,
+    util::{LspExt, Mapper, Position},
+    Context, Params,
+};
+use lsp_types::{SelectionRange, SelectionRangeParams};
+use taplo::{
+    dom::node::DomNode,
+    rowan::{TextRange, TextSize, TokenAtOffset},
+    syntax::{
+        SyntaxElement,
+        SyntaxKind::{
+            self, BRACE_END, BRACKET_END, COMMA, COMMENT, EQ, MULTI_LINE_STRING,
+            MULTI_LINE_STRING_LITERAL, NEWLINE, PERIOD, ROOT, STRING, STRING_LITERAL,
+            TABLE_ARRAY_HEADER, TABLE_HEADER, WHITESPACE,
+        },
+        SyntaxNode, SyntaxToken,
+    },
+};
+use taplo_common::environment::Environment;
+
+use crate::world::World;
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn selection_ranges<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<SelectionRangeParams>,
+) -> Result<Option<Vec<SelectionRange>>, Error> {
+    let p = params.required()?;
+
+    let workspaces = context.workspaces.read().await;
+    let ws = workspaces.by_document(&p.text_document.uri);
+    let doc = match ws.document(&p.text_document.uri) {
+        Ok(d) => d,
+        Err(error) => {
+            tracing::debug!(%error, "failed to get document from workspace");
+            return Ok(None);
+        }
+    };
+
+    let syntax = doc.dom.syntax().unwrap().as_node().unwrap();
+
+    Ok(Some(
+        p.positions
+            .into_iter()
+            .map(|position| {
+                let ranges = doc
+                    .mapper
+                    .offset(Position::from_lsp(position))
+                    .map(|offset| create_selection_range(syntax, offset))
+                    .unwrap_or_default();
+
+                to_selection_range(&ranges, &doc.mapper).unwrap_or(SelectionRange {
+                    range: lsp_types::Range::new(position, position),
+                    parent: None,
+                })
+            })
+            .collect(),
+    ))
+}
+
+/// Returns the ranges to select at the offset, from the innermost to the outermost.
+///
+/// The ranges grow from the token at the offset through its ancestors
+/// to the table section it is in, the section together with all the
+/// subtables that follow it, and finally the whole document.
+#[must_use]
+pub fn create_selection_range(syntax: &SyntaxNode, offset: TextSize) -> Vec<TextRange> {
+    let token = match syntax.token_at_offset(offset) {
+        TokenAtOffset::None => return Vec::new(),
+        TokenAtOffset::Single(token) => token,
+        TokenAtOffset::Between(left, right) => {
+            if token_weight(right.kind()) >= token_weight(left.kind()) {
+                right
+            } else {
+                left
+            }
+        }
+    };
+
+    let mut ranges = Vec::new();
+
+    if let Some(content) = string_content_range(&token) {
+        if content.contains_inclusive(offset) {
+            ranges.push(content);
+        }
+    }
+
+    let mut top_level = SyntaxElement::Token(token.clone());
+
+    if !matches!(token.kind(), WHITESPACE | NEWLINE) {
+        ranges.push(token.text_range());
+    }
+
+    for ancestor in token.parent_ancestors() {
+        if ancestor.kind() == ROOT {
+            break;
+        }
+
+        // Keys and values also contain the whitespace and comments after them.
+        let range = match ranges.last() {
+            Some(inner) => trimmed_range(&ancestor).cover(*inner),
+            None => trimmed_range(&ancestor),
+        };
+        ranges.push(range);
+
+        top_level = SyntaxElement::Node(ancestor);
+    }
+
+    ranges.extend(table_section_ranges(syntax, &top_level));
+    ranges.push(syntax.text_range());
+
+    ranges.dedup();
+    ranges
+}
+
+/// The range of the table section the top-level element is in,
+/// and the range of the section with its subtables that directly follow it.
+fn table_section_ranges(syntax: &SyntaxNode, element: &SyntaxElement) -> Vec<TextRange> {
+    let children: Vec<SyntaxElement> = syntax.children_with_tokens().collect();
+    let element_range = element.text_range();
+
+    let Some(header_idx) = children.iter().rposition(|c| {
+        matches!(c.kind(), TABLE_HEADER | TABLE_ARRAY_HEADER)
+            && c.text_range().start() <= element_range.start()
+    }) else {
+        return Vec::new();
+    };
+
+    let header = &children[header_idx];
+    let key = header_key(header);
+
+    let (mut end, mut next_header_idx) = section_end(&children, header_idx);
+    end = end.max(element_range.end());
+
+    let section = TextRange::new(header.text_range().start(), end);
+    let mut ranges = vec![section];
+
+    while let Some(idx) = next_header_idx {
+        let is_subtable = key
+            .as_ref()
+            .zip(header_key(&children[idx]))
+            .is_some_and(|(key, sub_key)| sub_key.starts_with(&format!("{key}.")));
+
+        if !is_subtable {
+            break;
+        }
+
+        let (sub_end, next) = section_end(&children, idx);
+        end = end.max(sub_end);
+        next_header_idx = next;
+    }
+
+    if end > section.end() {
+        ranges.push(TextRange::new(section.start(), end));
+    }
+
+    ranges
+}
+
+/// The end of the last entry in the section starting with the header at the given
+/// index, and the index of the next header.
+fn section_end(children: &[SyntaxElement], header_idx: usize) -> (TextSize, Option<usize>) {
+    let mut end = children[header_idx].text_range().end();
+
+    for (idx, child) in children.iter().enumerate().skip(header_idx + 1) {
+        match child.kind() {
+            TABLE_HEADER | TABLE_ARRAY_HEADER => return (end, Some(idx)),
+            WHITESPACE | NEWLINE | COMMENT => {}
+            _ => end = child.text_range().end(),
+        }
+    }
+
+    (end, None)
+}
+
+/// The range of the node without leading and trailing whitespace and comments.
+fn trimmed_range(node: &SyntaxNode) -> TextRange {
+    let mut tokens = node
+        .descendants_with_tokens()
+        .filter_map(SyntaxElement::into_token)
+        .filter(|t| !matches!(t.kind(), WHITESPACE | NEWLINE | COMMENT));
+
+    match (tokens.next(), tokens.last()) {
+        (Some(first), Some(last)) => first.text_range().cover(last.text_range()),
+        (Some(first), None) => first.text_range(),
+        _ => node.text_range(),
+    }
+}
+
+fn header_key(header: &SyntaxElement) -> Option<String> {
+    Some(header.as_node()?.first_child()?.text().to_string())
+}
+
+/// Prefer tokens with content over punctuation and whitespace
+/// when the offset is between two tokens.
+fn token_weight(kind: SyntaxKind) -> u8 {
+    match kind {
+        WHITESPACE | NEWLINE => 0,
+        PERIOD | COMMA | EQ | BRACKET_END | BRACE_END => 1,
+        _ => 2,
+    }
+}
+
+/// The range of the contents of a string without the quotes.
+fn string_content_range(token: &SyntaxToken) -> Option<TextRange> {
+    let quote_len: u32 = match token.kind() {
+        STRING | STRING_LITERAL => 1,
+        MULTI_LINE_STRING | MULTI_LINE_STRING_LITERAL => 3,
+        _ => return None,
+    };
+
+    let range = token.text_range();
+    if range.len() < TextSize::from(quote_len * 2) {
+        return None;
+    }
+
+    Some(TextRange::new(
+        range.start() + TextSize::from(quote_len),
+        range.end() - TextSize::from(quote_len),
+    ))
+}
+
+fn to_selection_range(ranges: &[TextRange], mapper: &Mapper) -> Option<SelectionRange> {
+    ranges.iter().rev().try_fold(None, |parent, range| {
+        Some(Some(SelectionRange {
+            range: mapper.range(*range)?.into_lsp(),
+            parent: parent.map(Box::new),
+        }))
+    })?
+}
+
+#[cfg(test)]
+mod tests {
+    use taplo::{parser::parse, rowan::TextSize};
+
+    use super::create_selection_range;
+
+    /// The selected texts at the first occurrence of `at`, from the innermost to the outermost.
+    fn selections<'t>(toml: &'t str, at: &str) -> Vec<&'t str> {
+        let offset = toml.find(at).unwrap();
+        let syntax = parse(toml).into_syntax();
+
+        create_selection_range(&syntax, TextSize::try_from(offset).unwrap())
+            .into_iter()
+            .map(|range| &toml[range])
+            .collect()
+    }
+
+    #[test]
+    fn string_value() {
+        let toml = "[a]\nb = \"hello world\"\n";
+
+        assert_eq!(
+            selections(toml, "world"),
+            [
+                "hello world",
+                "\"hello world\"",
+                "b = \"hello world\"",
+                "[a]\nb = \"hello world\"",
+                toml,
+            ]
+        );
+    }
+
+    #[test]
+    fn dotted_key() {
+        let toml = "a.b.c = 1\n";
+
+        assert_eq!(selections(toml, "b."), ["b", "a.b.c", "a.b.c = 1", toml]);
+    }
+
+    #[test]
+    fn comment() {
+        let toml = "[a]\n# some comment\nb = 1\n";
+
+        assert_eq!(
+            selections(toml, "some"),
+            ["# some comment", "[a]\n# some comment\nb = 1", toml]
+        );
+    }
+
+    #[test]
+    fn array_of_tables_element() {
+        let toml = "[[a]]\nb = 1\n\n[[a]]\nc = [1, 2]\n\n[a.d]\ne = 3\n\n[f]\n";
+
+        assert_eq!(
+            selections(toml, "2]"),
+            [
+                "2",
+                "[1, 2]",
+                "c = [1, 2]",
+                "[[a]]\nc = [1, 2]",
+                "[[a]]\nc = [1, 2]\n\n[a.d]\ne = 3",
+                toml,
+            ]
+        );
+    }
+}
@@ -1,22 +1,30 @@
+use crate::world::{DocumentState, WorkspaceState};
 use crate::World;
 use lsp_async_stub::{
     rpc::Error,
-    util::{relative_range, LspExt, Mapper},
+    util::{LspExt, Mapper, Range},
     Context, Params,
 };
 use lsp_types::{
-    Range, SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokens,
-    SemanticTokensParams, SemanticTokensResult,
+    SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokens, SemanticTokensDelta,
+    SemanticTokensDeltaParams, SemanticTokensEdit, SemanticTokensFullDeltaResult,
+    SemanticTokensParams, SemanticTokensRangeParams, SemanticTokensRangeResult,
+    SemanticTokensResult, Url,
 };
+use std::sync::Arc;
 use taplo::{
-    dom::node::DomNode,
+    dom::{node::DomNode, KeyOrIndex},
+    rowan::{TextRange, TextSize},
     syntax::{
         SyntaxElement,
-        SyntaxKind::{ARRAY, IDENT, INLINE_TABLE},
+        SyntaxKind::{
+            ARRAY, COMMENT, DATE, DATE_TIME_LOCAL, DATE_TIME_OFFSET, IDENT, INLINE_TABLE,
+            MULTI_LINE_STRING, STRING, TABLE_ARRAY_HEADER, TABLE_HEADER, TIME,
+        },
         SyntaxNode, SyntaxToken,
     },
 };
-use taplo_common::environment::Environment;
+use taplo_common::{environment::Environment, HashMap};
 
 #[tracing::instrument(skip_all)]
 pub(crate) async fn semantic_tokens<E: Environment>(
@@ -40,24 +48,113 @@ pub(crate) async fn semantic_tokens<E: Environment>(
         }
     };
 
-    Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
+    let data = document_tokens(&context, ws, doc, &p.text_document.uri, None).await;
+    let tokens = cache_tokens(&context, &p.text_document.uri, data).await;
+
+    Ok(Some(SemanticTokensResult::Tokens(tokens)))
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn semantic_tokens_delta<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<SemanticTokensDeltaParams>,
+) -> Result<Option<SemanticTokensFullDeltaResult>, Error> {
+    let p = params.required()?;
+
+    let workspaces = context.workspaces.read().await;
+    let ws = workspaces.by_document(&p.text_document.uri);
+
+    if !ws.config.syntax.semantic_tokens {
+        return Ok(None);
+    }
+
+    let doc = match ws.document(&p.text_document.uri) {
+        Ok(d) => d,
+        Err(error) => {
+            tracing::debug!(%error, "failed to get document from workspace");
+            return Ok(None);
+        }
+    };
+
+    let data = document_tokens(&context, ws, doc, &p.text_document.uri, None).await;
+
+    let previous = context
+        .semantic_tokens
+        .lock()
+        .await
+        .get(&p.text_document.uri)
+        .filter(|previous| previous.result_id.as_deref() == Some(&p.previous_result_id))
+        .map(|previous| previous.data.clone());
+
+    let edits = previous.map(|previous| diff_tokens(&previous, &data));
+    let tokens = cache_tokens(&context, &p.text_document.uri, data).await;
+
+    Ok(Some(match edits {
+        Some(edits) => SemanticTokensFullDeltaResult::TokensDelta(SemanticTokensDelta {
+            result_id: tokens.result_id,
+            edits,
+        }),
+        None => SemanticTokensFullDeltaResult::Tokens(tokens),
+    }))
+}
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn semantic_tokens_range<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<SemanticTokensRangeParams>,
+) -> Result<Option<SemanticTokensRangeResult>, Error> {
+    let p = params.required()?;
+
+    let workspaces = context.workspaces.read().await;
+    let ws = workspaces.by_document(&p.text_document.uri);
+
+    if !ws.config.syntax.semantic_tokens {
+        return Ok(None);
+    }
+
+    let doc = match ws.document(&p.text_document.uri) {
+        Ok(d) => d,
+        Err(error) => {
+            tracing::debug!(%error, "failed to get document from workspace");
+            return Ok(None);
+        }
+    };
+
+    let Some(range) = doc.mapper.text_range(Range::from_lsp(p.range)) else {
+        tracing::debug!(range = ?p.range, "range out of bounds");
+        return Ok(None);
+    };
+
+    Ok(Some(SemanticTokensRangeResult::Tokens(SemanticTokens {
         result_id: None,
-        data: create_tokens(doc.dom.syntax().unwrap().as_node().unwrap(), &doc.mapper),
+        data: document_tokens(&context, ws, doc, &p.text_document.uri, Some(range)).await,
     })))
 }
 
-#[allow(dead_code)]
-#[derive(Debug, Copy, Clone)]
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(u32)]
 pub enum TokenType {
     TomlArrayKey,
     TomlTableKey,
+    TomlKey,
+    TomlEscape,
+    TomlDate,
+    TomlTime,
+    TomlTimeOffset,
+    TomlDirective,
 }
 
 impl TokenType {
     pub const LEGEND: &'static [SemanticTokenType] = &[
         SemanticTokenType::new("tomlArrayKey"),
         SemanticTokenType::new("tomlTableKey"),
+        SemanticTokenType::new("tomlKey"),
+        SemanticTokenType::new("tomlEscape"),
+        SemanticTokenType::new("tomlDate"),
+        SemanticTokenType::new("tomlTime"),
+        SemanticTokenType::new("tomlTimeOffset"),
+        SemanticTokenType::new("tomlDirective"),
     ];
 }
 
@@ -66,55 +163,427 @@ impl TokenType {
 #[repr(u32)]
 pub enum TokenModifier {
     ReadOnly,
+    Deprecated,
+    /// The key is defined by the schema.
+    TomlDefined,
+    /// The key is not defined by the schema of its table.
+    TomlUnknown,
 }
 
 impl TokenModifier {
-    pub const MODIFIERS: &'static [SemanticTokenModifier] = &[SemanticTokenModifier::READONLY];
+    pub const MODIFIERS: &'static [SemanticTokenModifier] = &[
+        SemanticTokenModifier::READONLY,
+        SemanticTokenModifier::DEPRECATED,
+        SemanticTokenModifier::new("tomlDefined"),
+        SemanticTokenModifier::new("tomlUnknown"),
+    ];
+
+    fn bitset(modifiers: &[TokenModifier]) -> u32 {
+        modifiers
+            .iter()
+            .fold(0, |bitset, modifier| bitset | 1 << *modifier as u32)
+    }
+}
+
+/// Tokens of the document or the given range of it,
+/// keys are looked up in the associated schema for their modifiers.
+async fn document_tokens<E: Environment>(
+    context: &Context<World<E>>,
+    ws: &WorkspaceState<E>,
+    doc: &DocumentState,
+    document_url: &Url,
+    range: Option<TextRange>,
+) -> Vec<SemanticToken> {
+    let key_modifiers = cached_key_modifiers(context, ws, doc, document_url, range).await;
+
+    create_tokens(
+        doc.dom.syntax().unwrap().as_node().unwrap(),
+        &doc.mapper,
+        range,
+        &key_modifiers,
+    )
+}
+
+/// The schema modifiers of all the keys of a document version.
+pub(crate) struct KeyModifiers {
+    version: i32,
+    revision: u64,
+    schema_url: Option<Url>,
+    modifiers: Arc<HashMap<TextRange, Vec<TokenModifier>>>,
+}
+
+/// The modifiers of the keys of the whole document are cached until
+/// the document or its schema changes, ranges are only resolved
+/// on their own while there are none.
+async fn cached_key_modifiers<E: Environment>(
+    context: &Context<World<E>>,
+    ws: &WorkspaceState<E>,
+    doc: &DocumentState,
+    document_url: &Url,
+    range: Option<TextRange>,
+) -> Arc<HashMap<TextRange, Vec<TokenModifier>>> {
+    let schema_url = ws
+        .config
+        .schema
+        .enabled
+        .then(|| ws.schemas.associations().association_for(document_url))
+        .flatten()
+        .map(|association| association.url);
+
+    let is_current = |cached: &KeyModifiers| {
+        cached.version == doc.version
+            && cached.revision == ws.revision
+            && cached.schema_url == schema_url
+    };
+
+    if let Some(cached) = context.key_modifiers.lock().await.get(document_url) {
+        if is_current(cached) {
+            return cached.modifiers.clone();
+        }
+    }
+
+    let Some(schema_url) = &schema_url else {
+        return Arc::default();
+    };
+
+    let modifiers = Arc::new(schema_key_modifiers(ws, doc, schema_url, range).await);
+
+    if range.is_none() {
+        context.key_modifiers.lock().await.insert(
+            document_url.clone(),
+            KeyModifiers {
+                version: doc.version,
+                revision: ws.revision,
+                schema_url: Some(schema_url.clone()),
+                modifiers: modifiers.clone(),
+            },
+        );
+    }
+
+    modifiers
+}
+
+/// Stores the tokens for later delta requests with a new result ID.
+async fn cache_tokens<E: Environment>(
+    context: &Context<World<E>>,
+    document_url: &Url,
+    data: Vec<SemanticToken>,
+) -> SemanticTokens {
+    let mut cache = context.semantic_tokens.lock().await;
+
+    let result_id = cache
+        .get(document_url)
+        .and_then(|previous| previous.result_id.as_deref())
+        .and_then(|id| id.parse::<u64>().ok())
+        .map_or(0, |id| id.wrapping_add(1));
+
+    let tokens = SemanticTokens {
+        result_id: Some(result_id.to_string()),
+        data,
+    };
+
+    cache.insert(document_url.clone(), tokens.clone());
+    tokens
+}
+
+/// A single edit that replaces the tokens between the
+/// common prefix and suffix of the previous and the current tokens.
+fn diff_tokens(previous: &[SemanticToken], current: &[SemanticToken]) -> Vec<SemanticTokensEdit> {
+    let prefix = previous
+        .iter()
+        .zip(current)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let suffix = previous[prefix..]
+        .iter()
+        .rev()
+        .zip(current[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let deleted = previous.len() - prefix - suffix;
+    let inserted = &current[prefix..current.len() - suffix];
+
+    if deleted == 0 && inserted.is_empty() {
+        return Vec::new();
+    }
+
+    // Edits are in the flattened integer array with 5 integers per token.
+    #[allow(clippy::cast_possible_truncation)]
+    Vec::from([SemanticTokensEdit {
+        start: 5 * prefix as u32,
+        delete_count: 5 * deleted as u32,
+        data: Some(inserted.to_vec()),
+    }])
+}
+
+/// Modifiers of the keys based on the schema by their text ranges.
+async fn schema_key_modifiers<E: Environment>(
+    ws: &WorkspaceState<E>,
+    doc: &DocumentState,
+    schema_url: &Url,
+    range: Option<TextRange>,
+) -> HashMap<TextRange, Vec<TokenModifier>> {
+    let mut key_modifiers = HashMap::default();
+
+    let value = match serde_json::to_value(&doc.dom) {
+        Ok(v) => v,
+        Err(error) => {
+            tracing::warn!(%error, "cannot turn DOM into JSON");
+            return key_modifiers;
+        }
+    };
+
+    // Whether there are any schemas for the tables, keys that
+    // are not defined are only unknown if their table is.
+    let mut table_schemas: HashMap<String, bool> = HashMap::default();
+
+    for (keys, _) in doc.dom.flat_iter() {
+        let Some(KeyOrIndex::Key(key)) = keys.iter().last() else {
+            continue;
+        };
+
+        let ranges: Vec<TextRange> = key
+            .text_ranges()
+            .filter(|r| range.map_or(true, |range| range.intersect(*r).is_some()))
+            .collect();
+
+        if ranges.is_empty() {
+            continue;
+        }
+
+        let schemas = match ws.schemas.schemas_at_path(schema_url, &value, &keys).await {
+            Ok(s) => s,
+            Err(error) => {
+                tracing::debug!(?error, "schema resolution failed");
+                continue;
+            }
+        };
+
+        let mut modifiers = Vec::new();
+
+        if schemas.is_empty() {
+            let table_keys = keys.skip_right(1);
+
+            let table_has_schema = match table_schemas.get(table_keys.dotted()) {
+                Some(has_schema) => *has_schema,
+                None => {
+                    let has_schema = ws
+                        .schemas
+                        .schemas_at_path(schema_url, &value, &table_keys)
+                        .await
+                        .is_ok_and(|schemas| !schemas.is_empty());
+                    table_schemas.insert(table_keys.dotted().into(), has_schema);
+                    has_schema
+                }
+            };
+
+            if table_has_schema {
+                modifiers.push(TokenModifier::TomlUnknown);
+            }
+        } else {
+            modifiers.push(TokenModifier::TomlDefined);
+
+            if schemas
+                .iter()
+                .any(|(_, schema)| schema["deprecated"] == true)
+            {
+                modifiers.push(TokenModifier::Deprecated);
+            }
+        }
+
+        for r in ranges {
+            key_modifiers.insert(r, modifiers.clone());
+        }
+    }
+
+    key_modifiers
 }
 
 #[tracing::instrument(skip_all)]
-pub fn create_tokens(syntax: &SyntaxNode, mapper: &Mapper) -> Vec<SemanticToken> {
+pub fn create_tokens(
+    syntax: &SyntaxNode,
+    mapper: &Mapper,
+    range: Option<TextRange>,
+    key_modifiers: &HashMap<TextRange, Vec<TokenModifier>>,
+) -> Vec<SemanticToken> {
     let mut builder = SemanticTokensBuilder::new(mapper);
 
     for element in syntax.descendants_with_tokens() {
-        match element {
-            SyntaxElement::Node(_node) => {}
-            SyntaxElement::Token(token) => {
-                if let IDENT = token.kind() {
-                    // look for an inline table value
-                    let is_table_key = token
-                        .parent()
-                        .and_then(|p| p.next_sibling())
-                        .and_then(|t| t.first_child())
-                        .is_some_and(|t| t.kind() == INLINE_TABLE);
-
-                    if is_table_key {
-                        builder.add_token(&token, TokenType::TomlTableKey, &[]);
-                        continue;
-                    }
-
-                    // look for an array
-                    let is_array_key = token
-                        .parent()
-                        .and_then(|p| p.next_sibling())
-                        .and_then(|t| t.first_child())
-                        .is_some_and(|t| t.kind() == ARRAY);
-
-                    if is_array_key {
-                        builder.add_token(&token, TokenType::TomlArrayKey, &[]);
-                    }
+        let SyntaxElement::Token(token) = element else {
+            continue;
+        };
+
+        if let Some(range) = range {
+            if range.intersect(token.text_range()).is_none() {
+                continue;
+            }
+        }
+
+        match token.kind() {
+            IDENT => {
+                let modifiers = key_modifiers
+                    .get(&token.text_range())
+                    .map_or(&[] as &[_], Vec::as_slice);
+
+                builder.add(token.text_range(), key_token_type(&token), modifiers);
+            }
+            STRING | MULTI_LINE_STRING => {
+                for escape in escape_ranges(&token) {
+                    builder.add(escape, TokenType::TomlEscape, &[]);
+                }
+            }
+            DATE_TIME_OFFSET | DATE_TIME_LOCAL | DATE | TIME => {
+                for (component, ty) in date_time_ranges(&token) {
+                    builder.add(component, ty, &[]);
                 }
             }
+            COMMENT => {
+                if let Some(directive) = directive_range(&token) {
+                    builder.add(directive, TokenType::TomlDirective, &[]);
+                }
+            }
+            _ => {}
         }
     }
 
     builder.build()
 }
 
+fn key_token_type(token: &SyntaxToken) -> TokenType {
+    let Some(key) = token.parent() else {
+        return TokenType::TomlKey;
+    };
+
+    match key.parent().map(|p| p.kind()) {
+        Some(TABLE_HEADER) => return TokenType::TomlTableKey,
+        Some(TABLE_ARRAY_HEADER) => return TokenType::TomlArrayKey,
+        _ => {}
+    }
+
+    // Look for the value of the entry.
+    match key
+        .next_sibling()
+        .and_then(|t| t.first_child())
+        .map(|t| t.kind())
+    {
+        Some(INLINE_TABLE) => TokenType::TomlTableKey,
+        Some(ARRAY) => TokenType::TomlArrayKey,
+        _ => TokenType::TomlKey,
+    }
+}
+
+/// The ranges of escape sequences in a basic string.
+#[allow(clippy::cast_possible_truncation)]
+fn escape_ranges(token: &SyntaxToken) -> Vec<TextRange> {
+    let start = token.text_range().start();
+    let text = token.text();
+
+    let mut ranges = Vec::new();
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((idx, c)) = chars.next() {
+        if c != '\\' {
+            continue;
+        }
+
+        let len = match chars.next() {
+            Some((_, 'u')) => 1 + 1 + 4,
+            Some((_, 'U')) => 1 + 1 + 8,
+            Some((_, 'x')) => 1 + 1 + 2,
+            // Line ending backslashes only cover the backslash itself,
+            // semantic tokens cannot span multiple lines.
+            Some((_, c)) if c.is_whitespace() => 1,
+            Some((_, c)) => 1 + c.len_utf8(),
+            None => 1,
+        };
+
+        let end = (idx + len).min(text.len());
+
+        // Skip the digits of unicode escapes.
+        while chars.peek().is_some_and(|(i, _)| *i < end) {
+            chars.next();
+        }
+
+        ranges.push(TextRange::new(
+            start + TextSize::from(idx as u32),
+            start + TextSize::from(end as u32),
+        ));
+    }
+
+    ranges
+}
+
+/// The date, time and offset components of a date-time.
+#[allow(clippy::cast_possible_truncation)]
+fn date_time_ranges(token: &SyntaxToken) -> Vec<(TextRange, TokenType)> {
+    let start = token.text_range().start();
+    let text = token.text();
+
+    let range = |from: usize, to: usize| {
+        TextRange::new(
+            start + TextSize::from(from as u32),
+            start + TextSize::from(to as u32),
+        )
+    };
+
+    let mut components = Vec::new();
+
+    let time_start = if token.kind() == TIME {
+        0
+    } else {
+        components.push((range(0, text.len().min(10)), TokenType::TomlDate));
+
+        if text.len() <= 11 {
+            return components;
+        }
+
+        // Skip the `T` or space separator.
+        11
+    };
+
+    let offset_start = if token.kind() == DATE_TIME_OFFSET {
+        text[time_start..]
+            .find(['Z', 'z', '+', '-'])
+            .map_or(text.len(), |idx| time_start + idx)
+    } else {
+        text.len()
+    };
+
+    components.push((range(time_start, offset_start), TokenType::TomlTime));
+
+    if offset_start < text.len() {
+        components.push((range(offset_start, text.len()), TokenType::TomlTimeOffset));
+    }
+
+    components
+}
+
+/// The `#:name` part of a directive comment, e.g. `#:schema`.
+#[allow(clippy::cast_possible_truncation)]
+fn directive_range(token: &SyntaxToken) -> Option<TextRange> {
+    let text = token.text();
+    let directive = text.strip_prefix("#:")?;
+
+    let name_len = directive
+        .find(char::is_whitespace)
+        .unwrap_or(directive.len());
+
+    if name_len == 0 {
+        return None;
+    }
+
+    let start = token.text_range().start();
+    Some(TextRange::new(
+        start,
+        start + TextSize::from((2 + name_len) as u32),
+    ))
+}
+
 struct SemanticTokensBuilder<'b> {
-    tokens: Vec<SemanticToken>,
+    tokens: Vec<(TextRange, TokenType, u32)>,
     mapper: &'b Mapper,
-    last_range: Option<Range>,
 }
 
 impl<'b> SemanticTokensBuilder<'b> {
@@ -122,39 +591,51 @@ impl<'b> SemanticTokensBuilder<'b> {
         Self {
             tokens: Vec::new(),
             mapper,
-            last_range: None,
         }
     }
 
-    fn add_token(
-        &mut self,
-        token: &SyntaxToken,
-        ty: TokenType,
-        modifiers: &[SemanticTokenModifier],
-    ) {
-        let range = self.mapper.range(token.text_range()).unwrap();
+    fn add(&mut self, range: TextRange, ty: TokenType, modifiers: &[TokenModifier]) {
+        if !range.is_empty() {
+            self.tokens
+                .push((range, ty, TokenModifier::bitset(modifiers)));
+        }
+    }
 
-        let relative = relative_range(
-            range,
-            lsp_async_stub::util::Range::from_lsp(self.last_range.unwrap_or_default()),
-        );
+    #[allow(clippy::cast_possible_truncation)]
+    fn build(mut self) -> Vec<SemanticToken> {
+        self.tokens.sort_by_key(|(range, ..)| range.start());
 
-        #[allow(clippy::cast_possible_truncation)]
-        self.tokens.push(SemanticToken {
-            delta_line: relative.start.line as u32,
-            delta_start: relative.start.character as u32,
-            length: (relative.end.character - relative.start.character) as u32,
-            token_type: ty as u32,
-            token_modifiers_bitset: modifiers.iter().enumerate().fold(0, |mut total, (i, _)| {
-                total += 1 << i;
-                total
-            }),
-        });
+        let mut data = Vec::with_capacity(self.tokens.len());
+        let mut last_start = lsp_types::Position::default();
 
-        self.last_range = Some(range.into_lsp());
-    }
+        for (range, ty, modifiers) in self.tokens {
+            let Some(range) = self.mapper.range(range) else {
+                continue;
+            };
+            let range = range.into_lsp();
+
+            if range.start.line != range.end.line {
+                continue;
+            }
+
+            let delta_line = range.start.line - last_start.line;
+            let delta_start = if delta_line == 0 {
+                range.start.character - last_start.character
+            } else {
+                range.start.character
+            };
+
+            data.push(SemanticToken {
+                delta_line,
+                delta_start,
+                length: range.end.character - range.start.character,
+                token_type: ty as u32,
+                token_modifiers_bitset: modifiers,
+            });
+
+            last_start = range.start;
+        }
 
-    fn build(self) -> Vec<SemanticToken> {
-        self.tokens
+        data
     }
 }
@@ -0,0 +1,157 @@
+use crate::{
+    query::{lookup_keys, Query},
+    world::World,
+};
+use lsp_async_stub::{
+    rpc::Error,
+    util::{LspExt, Mapper, Position},
+    Context, Params,
+};
+use lsp_types::{
+    request::{GotoTypeDefinitionParams, GotoTypeDefinitionResponse},
+    Location,
+};
+use taplo::{
+    dom::{KeyOrIndex, Keys},
+    rowan::{TextRange, TextSize},
+    syntax::SyntaxKind,
+};
+use taplo_common::{
+    environment::Environment,
+    schema::{SchemaLocation, Schemas},
+    util::json_pointer_range,
+};
+
+#[tracing::instrument(skip_all)]
+pub async fn type_definition<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<GotoTypeDefinitionParams>,
+) -> Result<Option<GotoTypeDefinitionResponse>, Error> {
+    let p = params.required()?;
+    let document_uri = p.text_document_position_params.text_document.uri;
+
+    let workspaces = context.workspaces.read().await;
+    let ws = workspaces.by_document(&document_uri);
+    let doc = match ws.document(&document_uri) {
+        Ok(d) => d,
+        Err(error) => {
+            tracing::debug!(%error, "failed to get document from workspace");
+            return Ok(None);
+        }
+    };
+
+    let Some(schema_association) = ws.schemas.associations().association_for(&document_uri) else {
+        return Ok(None);
+    };
+
+    let position = p.text_document_position_params.position;
+    let Some(offset) = doc.mapper.offset(Position::from_lsp(position)) else {
+        tracing::error!(?position, "document position not found");
+        return Ok(None);
+    };
+
+    let query = Query::at(&doc.dom, offset);
+
+    let Some(position_info) = query
+        .before
+        .as_ref()
+        .filter(|p| p.syntax.kind() == SyntaxKind::IDENT)
+        .or_else(|| {
+            query
+                .after
+                .as_ref()
+                .filter(|p| p.syntax.kind() == SyntaxKind::IDENT)
+        })
+    else {
+        return Ok(None);
+    };
+
+    let Some((keys, _)) = &position_info.dom_node else {
+        return Ok(None);
+    };
+
+    let mut keys = keys.clone();
+
+    if let Some(header_key) = query.header_key() {
+        let Some(key_idx) = header_key
+            .descendants_with_tokens()
+            .filter(|t| t.kind() == SyntaxKind::IDENT)
+            .position(|t| t.as_token() == Some(&position_info.syntax))
+        else {
+            return Ok(None);
+        };
+
+        keys = Keys::new(keys.into_iter().take(key_idx + 1));
+    }
+
+    keys = lookup_keys(doc.dom.clone(), &keys);
+
+    // We're interested in the array itself, not its item type.
+    while let Some(KeyOrIndex::Index(_)) = keys.iter().last() {
+        keys = keys.skip_right(1);
+    }
+
+    let schema_locations = match ws
+        .schemas
+        .schema_locations_at_path(&schema_association.url, &keys)
+        .await
+    {
+        Ok(s) => s,
+        Err(error) => {
+            tracing::error!(?error, "schema resolution failed");
+            return Ok(None);
+        }
+    };
+
+    let mut locations = Vec::with_capacity(schema_locations.len());
+
+    for schema_location in &schema_locations {
+        match source_location(&context.env, &ws.schemas, schema_location).await {
+            Some(location) => locations.push(location),
+            None => {
+                tracing::debug!(?schema_location, "schema is not available in a file");
+            }
+        }
+    }
+
+    Ok(Some(GotoTypeDefinitionResponse::Array(locations)))
+}
+
+/// The location of the schema in the file it was loaded from.
+async fn source_location<E: Environment>(
+    env: &E,
+    schemas: &Schemas<E>,
+    schema_location: &SchemaLocation,
+) -> Option<Location> {
+    let (path, pointer) = match schema_location.url.scheme() {
+        "file" => (
+            env.to_file_path_normalized(&schema_location.url)?,
+            schema_location.pointer.clone(),
+        ),
+        // Remote schemas point to their cached copy on disk.
+        "http" | "https" => (
+            schemas.cache().cache_file_path(&schema_location.url)?,
+            format!("/value{}", schema_location.pointer),
+        ),
+        _ => return None,
+    };
+
+    let text = String::from_utf8(env.read_file(&path).await.ok()?).ok()?;
+    let range = json_pointer_range(&text, &pointer)?;
+
+    let uri = if schema_location.url.scheme() == "file" {
+        schema_location.url.clone()
+    } else {
+        env.to_file_url(&path)?
+    };
+
+    let mapper = Mapper::new_utf16(&text, false);
+    let range = mapper
+        .range(TextRange::new(
+            TextSize::from(u32::try_from(range.start).ok()?),
+            TextSize::from(u32::try_from(range.end).ok()?),
+        ))?
+        .into_lsp();
+
+    Some(Location { uri, range })
+}
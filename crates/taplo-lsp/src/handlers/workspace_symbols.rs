@@ -0,0 +1,25 @@
+use crate::{symbols::matches_query, world::World};
+use lsp_async_stub::{rpc::Error, Context, Params};
+use lsp_types::{SymbolInformation, WorkspaceSymbolParams};
+use taplo_common::environment::Environment;
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn workspace_symbols<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<WorkspaceSymbolParams>,
+) -> Result<Option<Vec<SymbolInformation>>, Error> {
+    let p = params.required()?;
+
+    context.update_symbols().await;
+
+    let workspaces = context.workspaces.read().await;
+
+    Ok(Some(
+        workspaces
+            .values()
+            .flat_map(|ws| ws.symbols.values().flatten())
+            .filter(|symbol| matches_query(symbol, &p.query))
+            .cloned()
+            .collect(),
+    ))
+}
@@ -1,16 +1,15 @@
 use super::update_configuration;
 use crate::world::{WorkspaceState, World};
 use lsp_async_stub::{Context, Params};
-use lsp_types::DidChangeWorkspaceFoldersParams;
+use lsp_types::{DidChangeWatchedFilesParams, DidChangeWorkspaceFoldersParams};
 use taplo_common::environment::Environment;
 
 pub async fn workspace_change<E: Environment>(
     context: Context<World<E>>,
     params: Params<DidChangeWorkspaceFoldersParams>,
 ) {
-    let p = match params.optional() {
-        None => return,
-        Some(p) => p,
+    let Some(p) = params.optional() else {
+        return;
     };
 
     let mut workspaces = context.workspaces.write().await;
@@ -37,3 +36,22 @@ pub async fn workspace_change<E: Environment>(
     drop(workspaces);
     update_configuration(context).await;
 }
+
+/// Keeps the symbol index up to date with the files on disk.
+#[tracing::instrument(skip_all)]
+pub async fn watched_files_change<E: Environment>(
+    context: Context<World<E>>,
+    params: Params<DidChangeWatchedFilesParams>,
+) {
+    let Some(p) = params.optional() else {
+        return;
+    };
+
+    let mut workspaces = context.workspaces.write().await;
+
+    for change in p.changes {
+        workspaces
+            .by_document_mut(&change.uri)
+            .invalidate_symbols(&change.uri);
+    }
+}
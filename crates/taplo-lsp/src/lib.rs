@@ -20,6 +20,7 @@ use world::{World, WorldState};
 
 mod diagnostics;
 mod handlers;
+mod symbols;
 
 pub mod config;
 pub mod lsp_ext;
@@ -29,16 +30,35 @@ pub mod world;
 #[must_use]
 pub fn create_server<E: Environment>() -> Server<World<E>> {
     Server::new()
-        .on_request::<request::Initialize, _>(handlers::initialize)
+        .on_request::<lsp_ext::request::Initialize, _>(handlers::initialize)
         .on_request::<request::FoldingRangeRequest, _>(handlers::folding_ranges)
+        .on_request::<request::SelectionRangeRequest, _>(handlers::selection_ranges)
         .on_request::<request::DocumentSymbolRequest, _>(handlers::document_symbols)
+        .on_request::<request::WorkspaceSymbol, _>(handlers::workspace_symbols)
         .on_request::<request::Formatting, _>(handlers::format)
+        .on_request::<request::RangeFormatting, _>(handlers::format_range)
+        .on_request::<request::OnTypeFormatting, _>(handlers::format_on_type)
+        .on_request::<request::CodeActionRequest, _>(handlers::code_actions)
+        .on_request::<request::CodeActionResolveRequest, _>(handlers::code_action_resolve)
+        .on_request::<request::CodeLensRequest, _>(handlers::code_lens)
+        .on_request::<request::ExecuteCommand, _>(handlers::execute_command)
         .on_request::<request::Completion, _>(handlers::completion)
         .on_request::<request::HoverRequest, _>(handlers::hover)
+        .on_request::<request::InlayHintRequest, _>(handlers::inlay_hints)
         .on_request::<request::DocumentLinkRequest, _>(handlers::links)
         .on_request::<request::SemanticTokensFullRequest, _>(handlers::semantic_tokens)
+        .on_request::<request::SemanticTokensFullDeltaRequest, _>(handlers::semantic_tokens_delta)
+        .on_request::<request::SemanticTokensRangeRequest, _>(handlers::semantic_tokens_range)
         .on_request::<request::PrepareRenameRequest, _>(handlers::prepare_rename)
         .on_request::<request::Rename, _>(handlers::rename)
+        .on_request::<request::GotoDefinition, _>(handlers::goto_definition)
+        .on_request::<request::References, _>(handlers::references)
+        .on_request::<request::DocumentHighlightRequest, _>(handlers::document_highlight)
+        .on_request::<request::GotoTypeDefinition, _>(handlers::type_definition)
+        .on_request::<lsp_ext::request::DocumentDiagnosticRequest, _>(handlers::document_diagnostic)
+        .on_request::<lsp_ext::request::WorkspaceDiagnosticRequest, _>(
+            handlers::workspace_diagnostic,
+        )
         .on_notification::<notification::Initialized, _>(handlers::initialized)
         .on_notification::<notification::DidOpenTextDocument, _>(handlers::document_open)
         .on_notification::<notification::DidChangeTextDocument, _>(handlers::document_change)
@@ -46,6 +66,7 @@ pub fn create_server<E: Environment>() -> Server<World<E>> {
         .on_notification::<notification::DidCloseTextDocument, _>(handlers::document_close)
         .on_notification::<notification::DidChangeConfiguration, _>(handlers::configuration_change)
         .on_notification::<notification::DidChangeWorkspaceFolders, _>(handlers::workspace_change)
+        .on_notification::<notification::DidChangeWatchedFiles, _>(handlers::watched_files_change)
         .on_request::<lsp_ext::request::ConvertToJsonRequest, _>(handlers::convert_to_json)
         .on_request::<lsp_ext::request::ConvertToTomlRequest, _>(handlers::convert_to_toml)
         .on_request::<lsp_ext::request::ListSchemasRequest, _>(handlers::list_schemas)
@@ -1,7 +1,173 @@
-use lsp_types::{request::Request, Url};
-use serde::{Deserialize, Serialize};
+use lsp_types::{request::Request, Diagnostic, TextDocumentIdentifier, Url};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
 use serde_json::Value;
 
+/// The initialize request with the parts of LSP 3.17
+/// that are not supported by `lsp_types` yet.
+pub enum Initialize {}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InitializeParams {
+    #[serde(flatten)]
+    pub params: lsp_types::InitializeParams,
+
+    /// The client supports pulling diagnostics.
+    #[serde(skip)]
+    pub diagnostic_pull: bool,
+
+    /// The client supports refreshing pulled diagnostics.
+    #[serde(skip)]
+    pub diagnostic_refresh: bool,
+}
+
+impl<'de> Deserialize<'de> for InitializeParams {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+
+        let diagnostic_pull = value
+            .pointer("/capabilities/textDocument/diagnostic")
+            .is_some_and(Value::is_object);
+
+        let diagnostic_refresh = value
+            .pointer("/capabilities/workspace/diagnostics/refreshSupport")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+
+        Ok(Self {
+            params: serde_json::from_value(value).map_err(D::Error::custom)?,
+            diagnostic_pull,
+            diagnostic_refresh,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InitializeResult {
+    pub capabilities: ServerCapabilities,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_info: Option<lsp_types::ServerInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerCapabilities {
+    #[serde(flatten)]
+    pub capabilities: lsp_types::ServerCapabilities,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diagnostic_provider: Option<DiagnosticOptions>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identifier: Option<String>,
+
+    /// Whether changes in one document can affect the diagnostics of others.
+    pub inter_file_dependencies: bool,
+
+    /// Whether the server supports `workspace/diagnostic`.
+    pub workspace_diagnostics: bool,
+}
+
+impl Request for Initialize {
+    type Params = InitializeParams;
+    type Result = InitializeResult;
+    const METHOD: &'static str = lsp_types::request::Initialize::METHOD;
+}
+
+/// Pull the diagnostics of a document.
+pub enum DocumentDiagnosticRequest {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentDiagnosticParams {
+    pub text_document: TextDocumentIdentifier,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identifier: Option<String>,
+
+    /// The result ID of the diagnostics the client has for the document.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_result_id: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum DocumentDiagnosticReport {
+    #[serde(rename_all = "camelCase")]
+    Full {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        result_id: Option<String>,
+        items: Vec<Diagnostic>,
+    },
+    /// The diagnostics the client has are still valid.
+    #[serde(rename_all = "camelCase")]
+    Unchanged { result_id: String },
+}
+
+impl Request for DocumentDiagnosticRequest {
+    type Params = DocumentDiagnosticParams;
+    type Result = DocumentDiagnosticReport;
+    const METHOD: &'static str = "textDocument/diagnostic";
+}
+
+/// Pull the diagnostics of all the documents in the workspace.
+pub enum WorkspaceDiagnosticRequest {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceDiagnosticParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identifier: Option<String>,
+
+    /// The result IDs of the diagnostics the client has.
+    pub previous_result_ids: Vec<PreviousResultId>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviousResultId {
+    pub uri: Url,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceDiagnosticReport {
+    pub items: Vec<WorkspaceDocumentDiagnosticReport>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceDocumentDiagnosticReport {
+    pub uri: Url,
+
+    /// The version of the document if it is known.
+    pub version: Option<i32>,
+
+    #[serde(flatten)]
+    pub report: DocumentDiagnosticReport,
+}
+
+impl Request for WorkspaceDiagnosticRequest {
+    type Params = WorkspaceDiagnosticParams;
+    type Result = WorkspaceDiagnosticReport;
+    const METHOD: &'static str = "workspace/diagnostic";
+}
+
+/// Ask the client to pull all diagnostics again.
+pub enum WorkspaceDiagnosticRefresh {}
+
+impl Request for WorkspaceDiagnosticRefresh {
+    type Params = ();
+    type Result = ();
+    const METHOD: &'static str = "workspace/diagnostic/refresh";
+}
+
 /// Serialize a TOML text to JSON.
 pub enum ConvertToJsonRequest {}
 
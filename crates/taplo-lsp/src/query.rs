@@ -406,6 +406,51 @@ impl Query {
         }
     }
 
+    /// The table or array of tables header that is closed
+    /// by the bracket right before the cursor.
+    #[must_use]
+    pub fn closed_header(&self) -> Option<SyntaxNode> {
+        let before = self.before.as_ref()?;
+
+        if before.syntax.kind() != BRACKET_END {
+            return None;
+        }
+
+        before
+            .syntax
+            .parent()
+            .filter(|p| matches!(p.kind(), TABLE_HEADER | TABLE_ARRAY_HEADER))
+    }
+
+    /// The entry with the `=` right before the cursor.
+    #[must_use]
+    pub fn entry_after_eq(&self) -> Option<SyntaxNode> {
+        let before = self.before.as_ref()?;
+
+        if before.syntax.kind() != EQ {
+            return None;
+        }
+
+        before.syntax.parent().filter(|p| p.kind() == ENTRY)
+    }
+
+    /// The line break inside an array that is right before the cursor,
+    /// only whitespace is allowed between them.
+    #[must_use]
+    pub fn array_line_break(&self) -> Option<SyntaxToken> {
+        let mut token = self.before.as_ref()?.syntax.clone();
+
+        if token.kind() == WHITESPACE {
+            token = token.prev_token()?;
+        }
+
+        if token.kind() != NEWLINE {
+            return None;
+        }
+
+        token.parent().filter(|p| p.kind() == ARRAY).map(|_| token)
+    }
+
     pub fn entry_keys(&self) -> Keys {
         self.entry_key()
             .map_or_else(Keys::empty, |keys| Keys::from_syntax(keys.into()))
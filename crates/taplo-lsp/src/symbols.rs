@@ -0,0 +1,75 @@
+use crate::world::DocumentState;
+use lsp_async_stub::util::LspExt;
+use lsp_types::{Location, SymbolInformation, SymbolKind, Url};
+use taplo::dom::{KeyOrIndex, Node};
+
+/// Symbols for every key in the document for the workspace symbol index.
+///
+/// The name of a symbol is its key, and the container name is
+/// the dotted path of the table it is in, e.g. `dependencies`
+/// for `[dependencies.serde]`.
+#[allow(deprecated)]
+pub(crate) fn document_symbol_information(
+    doc: &DocumentState,
+    document_url: &Url,
+) -> Vec<SymbolInformation> {
+    doc.dom
+        .flat_iter()
+        .filter_map(|(keys, node)| {
+            // Array items are found by the keys inside them.
+            let KeyOrIndex::Key(key) = keys.iter().last()? else {
+                return None;
+            };
+
+            let range = doc.mapper.range(key.text_ranges().next()?)?.into_lsp();
+
+            let parent_keys = keys.skip_right(1);
+            let container_name = (!parent_keys.is_empty()).then(|| parent_keys.to_string());
+
+            Some(SymbolInformation {
+                name: if key.value().is_empty() {
+                    r"''".into()
+                } else {
+                    key.value().to_string()
+                },
+                kind: symbol_kind(&node)?,
+                tags: None,
+                deprecated: None,
+                location: Location {
+                    uri: document_url.clone(),
+                    range,
+                },
+                container_name,
+            })
+        })
+        .collect()
+}
+
+/// Whether the symbol matches a workspace symbol query.
+///
+/// The query is matched case-insensitively against the full dotted path,
+/// so both `serde` and `dependencies.serde` find `[dependencies.serde]`.
+pub(crate) fn matches_query(symbol: &SymbolInformation, query: &str) -> bool {
+    if query.is_empty() {
+        return true;
+    }
+
+    let path = match &symbol.container_name {
+        Some(container) => format!("{container}.{}", symbol.name),
+        None => symbol.name.clone(),
+    };
+
+    path.to_lowercase().contains(&query.to_lowercase())
+}
+
+fn symbol_kind(node: &Node) -> Option<SymbolKind> {
+    Some(match node {
+        Node::Table(_) => SymbolKind::OBJECT,
+        Node::Array(_) => SymbolKind::ARRAY,
+        Node::Bool(_) => SymbolKind::BOOLEAN,
+        Node::Str(_) => SymbolKind::STRING,
+        Node::Integer(_) | Node::Float(_) => SymbolKind::NUMBER,
+        Node::Date(_) => SymbolKind::FIELD,
+        Node::Invalid(_) => return None,
+    })
+}
@@ -1,16 +1,26 @@
 use crate::{
     config::{InitConfig, LspConfig},
+    handlers::KeyModifiers,
     lsp_ext::notification::{DidChangeSchemaAssociation, DidChangeSchemaAssociationParams},
+    symbols::document_symbol_information,
 };
 use anyhow::anyhow;
 use arc_swap::ArcSwap;
+use itertools::Itertools;
 use lsp_async_stub::{rpc, util::Mapper, Context, RequestWriter};
-use lsp_types::Url;
+use lsp_types::{SemanticTokens, SymbolInformation, Url};
 use once_cell::sync::Lazy;
 use regex::Regex;
 use serde_json::json;
-use std::{str, sync::Arc, time::Duration};
-use taplo::{dom::Node, parser::Parse};
+use std::{
+    str,
+    sync::{atomic::AtomicBool, Arc},
+    time::Duration,
+};
+use taplo::{
+    dom::Node,
+    parser::{Parse, ParseOptions},
+};
 use taplo_common::{
     config::Config,
     environment::Environment,
@@ -18,7 +28,7 @@ use taplo_common::{
         associations::{priority, source, AssociationRule, SchemaAssociation},
         Schemas,
     },
-    AsyncRwLock, HashMap, IndexMap,
+    AsyncMutex, AsyncRwLock, HashMap, HashSet, IndexMap,
 };
 
 pub type World<E> = Arc<WorldState<E>>;
@@ -72,6 +82,21 @@ impl<E: Environment> Workspaces<E> {
             })
             .unwrap()
     }
+
+    /// The included files of every workspace along with the workspace
+    /// they belong to, files of nested workspaces only belong to those.
+    pub(crate) fn files(&self, env: &E) -> Vec<(Url, Url)> {
+        self.0
+            .iter()
+            .filter(|(ws_url, _)| **ws_url != *DEFAULT_WORKSPACE_URL)
+            .flat_map(|(ws_url, ws)| {
+                ws.files(env)
+                    .into_iter()
+                    .filter(|document_uri| self.by_document(document_uri).root == *ws_url)
+                    .map(|document_uri| (ws_url.clone(), document_uri))
+            })
+            .collect()
+    }
 }
 
 pub struct WorldState<E: Environment> {
@@ -79,6 +104,17 @@ pub struct WorldState<E: Environment> {
     pub(crate) env: E,
     pub(crate) workspaces: AsyncRwLock<Workspaces<E>>,
     pub(crate) default_config: ArcSwap<Config>,
+    /// The client pulls diagnostics instead of receiving them.
+    pub(crate) diagnostic_pull: AtomicBool,
+    /// The client can be asked to pull diagnostics again.
+    pub(crate) diagnostic_refresh: AtomicBool,
+    /// The client can watch files for us.
+    pub(crate) file_watching: AtomicBool,
+    /// The last semantic tokens of the documents for delta requests.
+    pub(crate) semantic_tokens: AsyncMutex<HashMap<Url, SemanticTokens>>,
+    /// The schema modifiers of the keys of the documents for semantic tokens,
+    /// these stay the same until the document or its schema changes.
+    pub(crate) key_modifiers: AsyncMutex<HashMap<Url, KeyModifiers>>,
 }
 
 pub static DEFAULT_WORKSPACE_URL: Lazy<Url> = Lazy::new(|| Url::parse("root:///").unwrap());
@@ -96,6 +132,11 @@ impl<E: Environment> WorldState<E> {
                 AsyncRwLock::new(Workspaces(m))
             },
             default_config: Default::default(),
+            diagnostic_pull: AtomicBool::new(false),
+            diagnostic_refresh: AtomicBool::new(false),
+            file_watching: AtomicBool::new(false),
+            semantic_tokens: Default::default(),
+            key_modifiers: Default::default(),
             env,
         }
     }
@@ -104,6 +145,80 @@ impl<E: Environment> WorldState<E> {
     pub fn set_default_config(&self, default_config: Arc<Config>) {
         self.default_config.store(default_config);
     }
+
+    /// Brings the symbol index of every workspace up to date,
+    /// the files are read and parsed while the workspaces are not locked.
+    #[tracing::instrument(skip_all)]
+    pub(crate) async fn update_symbols(&self) {
+        let mut pending = Vec::new();
+
+        {
+            let mut workspaces = self.workspaces.write().await;
+            let ws_urls: Vec<Url> = workspaces.keys().cloned().collect();
+
+            for ws_url in ws_urls {
+                let ws = &workspaces[&ws_url];
+                let urls: Vec<Url> = match &ws.outdated_symbols {
+                    Some(outdated) => outdated.iter().cloned().collect(),
+                    // Only open documents are indexed in the detached workspace.
+                    None if ws_url == *DEFAULT_WORKSPACE_URL => {
+                        ws.documents.keys().cloned().collect()
+                    }
+                    None => ws
+                        .files(&self.env)
+                        .into_iter()
+                        // Nested workspaces index their own files.
+                        .filter(|url| workspaces.by_document(url).root == ws_url)
+                        .chain(ws.documents.keys().cloned())
+                        .unique()
+                        .collect(),
+                };
+
+                let ws = workspaces.get_mut(&ws_url).unwrap();
+                if ws.outdated_symbols.is_none() {
+                    ws.symbols.clear();
+                }
+                ws.outdated_symbols = Some(HashSet::default());
+
+                for url in urls {
+                    if let Some(doc) = ws.documents.get(&url) {
+                        let symbols = document_symbol_information(doc, &url);
+                        ws.symbols.insert(url, symbols);
+                    } else if ws.indexes_file(&self.env, &url) {
+                        let options = ws.parse_options(&self.env, &url);
+                        pending.push((ws_url.clone(), ws.revision, options, url));
+                    } else {
+                        ws.symbols.remove(&url);
+                    }
+                }
+            }
+        }
+
+        let mut indexed = Vec::with_capacity(pending.len());
+        for (ws_url, revision, options, url) in pending {
+            let symbols = read_text(&self.env, &url)
+                .await
+                .map(|text| document_symbol_information(&DocumentState::new(text, options), &url));
+            indexed.push((ws_url, revision, url, symbols));
+        }
+
+        let mut workspaces = self.workspaces.write().await;
+        for (ws_url, revision, url, symbols) in indexed {
+            // Workspaces that were initialized again are indexed from scratch.
+            let Some(ws) = workspaces
+                .get_mut(&ws_url)
+                .filter(|ws| ws.revision == revision)
+            else {
+                continue;
+            };
+
+            // Deleted files are removed from the index as they cannot be read anymore.
+            match symbols {
+                Some(symbols) => ws.symbols.insert(url, symbols),
+                None => ws.symbols.remove(&url),
+            };
+        }
+    }
 }
 
 pub struct WorkspaceState<E: Environment> {
@@ -112,6 +227,14 @@ pub struct WorkspaceState<E: Environment> {
     pub(crate) taplo_config: Config,
     pub(crate) schemas: Schemas<E>,
     pub(crate) config: LspConfig,
+    /// Incremented every time the workspace is initialized,
+    /// diagnostic result IDs are only valid for a single revision.
+    pub(crate) revision: u64,
+    /// Symbols of all the files in the workspace, open or not.
+    pub(crate) symbols: HashMap<Url, Vec<SymbolInformation>>,
+    /// The files whose symbols are outdated,
+    /// all the files are indexed again if this is `None`.
+    pub(crate) outdated_symbols: Option<HashSet<Url>>,
 }
 
 impl<E: Environment> WorkspaceState<E> {
@@ -133,6 +256,9 @@ impl<E: Environment> WorkspaceState<E> {
             taplo_config: Default::default(),
             schemas: Schemas::new(env, client),
             config: LspConfig::default(),
+            revision: 0,
+            symbols: Default::default(),
+            outdated_symbols: None,
         }
     }
 }
@@ -144,12 +270,89 @@ impl<E: Environment> WorkspaceState<E> {
             .ok_or_else(rpc::Error::invalid_params)
     }
 
+    /// Parser options for a document based on the Taplo configuration.
+    pub(crate) fn parse_options(&self, env: &E, url: &Url) -> ParseOptions {
+        env.to_file_path_normalized(url)
+            .map(|path| self.taplo_config.parse_options(&path))
+            .unwrap_or_default()
+    }
+
+    /// All the files in the workspace that are included by the Taplo configuration.
+    pub(crate) fn files(&self, env: &E) -> Vec<Url> {
+        let Some(root_path) = env.to_file_path_normalized(&self.root) else {
+            return Vec::new();
+        };
+
+        let patterns = match &self.taplo_config.include {
+            Some(patterns) => patterns.clone(),
+            None => vec![root_path.join("**/*.toml").to_string_lossy().into_owned()],
+        };
+
+        patterns
+            .iter()
+            .unique()
+            .filter_map(|pattern| match env.glob_files_normalized(pattern) {
+                Ok(paths) => Some(paths),
+                Err(error) => {
+                    tracing::warn!(%error, %pattern, "invalid glob pattern");
+                    None
+                }
+            })
+            .flatten()
+            .filter(|path| path.starts_with(&root_path) && self.taplo_config.is_included(path))
+            .unique()
+            .filter_map(|path| env.to_file_url(&path))
+            .collect()
+    }
+
+    /// A copy of the workspace without documents and symbols that shares
+    /// the schemas with it, so that it can be initialized without
+    /// locking the workspaces.
+    pub(crate) fn detached_copy(&self) -> Self {
+        Self {
+            root: self.root.clone(),
+            documents: Default::default(),
+            taplo_config: self.taplo_config.clone(),
+            schemas: self.schemas.clone(),
+            config: self.config.clone(),
+            revision: self.revision,
+            symbols: Default::default(),
+            outdated_symbols: None,
+        }
+    }
+
+    /// Takes the configuration of an initialized copy of the workspace.
+    pub(crate) fn update_from(&mut self, copy: Self) {
+        self.taplo_config = copy.taplo_config;
+        self.revision += 1;
+        self.outdated_symbols = None;
+    }
+
+    /// Marks the symbols of the file as outdated,
+    /// they are updated before they are needed again.
+    pub(crate) fn invalidate_symbols(&mut self, url: &Url) {
+        if let Some(outdated) = &mut self.outdated_symbols {
+            outdated.insert(url.clone());
+        }
+    }
+
+    /// Whether the file is indexed even if it is not open,
+    /// only open documents are indexed in the detached workspace.
+    fn indexes_file(&self, env: &E, url: &Url) -> bool {
+        self.root != *DEFAULT_WORKSPACE_URL
+            && env
+                .to_file_path_normalized(url)
+                .is_some_and(|path| self.taplo_config.is_included(&path))
+    }
+
     #[tracing::instrument(skip_all, fields(%self.root))]
     pub(crate) async fn initialize(
         &mut self,
         context: Context<World<E>>,
         env: &impl Environment,
     ) -> Result<(), anyhow::Error> {
+        self.revision += 1;
+
         if let Err(error) = self
             .load_config(env, &context.world().default_config.load())
             .await
@@ -157,6 +360,14 @@ impl<E: Environment> WorkspaceState<E> {
             tracing::warn!(%error, "failed to load workspace configuration");
         }
 
+        // Reading every file of the workspace can take a while,
+        // so the files are indexed once the workspace is ready.
+        self.outdated_symbols = None;
+        let world = context.world().clone();
+        context.env.spawn_local(async move {
+            world.update_symbols().await;
+        });
+
         if !self.config.schema.enabled {
             return Ok(());
         }
@@ -291,9 +502,42 @@ impl<E: Environment> WorkspaceState<E> {
     }
 }
 
+/// Reads the text of a document that is not open in the editor.
+pub(crate) async fn read_text<E: Environment>(env: &E, url: &Url) -> Option<String> {
+    let path = env.to_file_path_normalized(url)?;
+
+    match env.read_file(&path).await {
+        Ok(bytes) => String::from_utf8(bytes).ok(),
+        Err(error) => {
+            tracing::debug!(%error, ?path, "failed to read document");
+            None
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DocumentState {
+    pub(crate) text: String,
     pub(crate) parse: Parse,
     pub(crate) dom: Node,
     pub(crate) mapper: Mapper,
+    /// The version of the document in the editor,
+    /// files that are not open are always at version 0.
+    pub(crate) version: i32,
+}
+
+impl DocumentState {
+    pub(crate) fn new(text: String, options: ParseOptions) -> Self {
+        let parse = taplo::parser::parse_with(&text, options);
+        let mapper = Mapper::new_utf16(&text, false);
+        let dom = parse.clone().into_dom();
+
+        Self {
+            text,
+            parse,
+            dom,
+            mapper,
+            version: 0,
+        }
+    }
 }
@@ -36,10 +36,7 @@ impl AsyncRead for JsAsyncRead {
             let ret: JsValue = match self.f.call1(&this, &JsValue::from(buf.remaining())) {
                 Ok(val) => val,
                 Err(error) => {
-                    return Poll::Ready(Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        format!("{:?}", error),
-                    )));
+                    return Poll::Ready(Err(io::Error::other(format!("{:?}", error))));
                 }
             };
 
@@ -58,7 +55,7 @@ impl AsyncRead for JsAsyncRead {
 
                             Ok(())
                         }
-                        Err(err) => Err(io::Error::new(io::ErrorKind::Other, format!("{:?}", err))),
+                        Err(err) => Err(io::Error::other(format!("{:?}", err))),
                     };
 
                     self.fut = None;
@@ -96,10 +93,7 @@ impl AsyncWrite for JsAsyncWrite {
             let ret: JsValue = match self.f.call1(&this, &Uint8Array::from(buf).into()) {
                 Ok(val) => val,
                 Err(error) => {
-                    return Poll::Ready(Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        format!("{:?}", error),
-                    )));
+                    return Poll::Ready(Err(io::Error::other(format!("{:?}", error))));
                 }
             };
 
@@ -114,7 +108,7 @@ impl AsyncWrite for JsAsyncWrite {
                             let n = num_written.as_f64().unwrap_or(0.0).floor() as usize;
                             Ok(n)
                         }
-                        Err(err) => Err(io::Error::new(io::ErrorKind::Other, format!("{:?}", err))),
+                        Err(err) => Err(io::Error::other(format!("{:?}", err))),
                     };
 
                     self.fut = None;
@@ -155,6 +149,7 @@ pub(crate) struct WasmEnvironment {
     js_glob_files: Function,
     js_read_file: Function,
     js_write_file: Function,
+    js_remove_file: Function,
     js_to_file_path: Function,
     js_is_absolute: Function,
     js_cwd: Function,
@@ -194,6 +189,9 @@ impl From<JsValue> for WasmEnvironment {
             js_write_file: js_sys::Reflect::get(&val, &JsValue::from_str("js_write_file"))
                 .unwrap()
                 .into(),
+            js_remove_file: js_sys::Reflect::get(&val, &JsValue::from_str("js_remove_file"))
+                .unwrap()
+                .into(),
             js_to_file_path: js_sys::Reflect::get(&val, &JsValue::from_str("js_to_file_path"))
                 .unwrap()
                 .into(),
@@ -319,6 +317,17 @@ impl Environment for WasmEnvironment {
         Ok(serde_wasm_bindgen::from_value(future).map_err(|err| anyhow!("{err}"))?)
     }
 
+    async fn remove_file(&self, path: &Path) -> Result<(), anyhow::Error> {
+        let path_str = JsValue::from_str(&path.to_string_lossy());
+        let this = JsValue::null();
+        let res: JsValue = self.js_remove_file.call1(&this, &path_str).unwrap();
+        let future = JsFuture::from(Promise::from(res))
+            .await
+            .map_err(|err| anyhow!("{:?}", err))?;
+
+        Ok(serde_wasm_bindgen::from_value(future).map_err(|err| anyhow!("{err}"))?)
+    }
+
     fn to_file_path(&self, url: &Url) -> Option<std::path::PathBuf> {
         let url_str = JsValue::from_str(url.as_str());
         let this = JsValue::null();
@@ -327,6 +336,14 @@ impl Environment for WasmEnvironment {
         res.as_string().map(Into::into)
     }
 
+    fn to_file_url(&self, path: &Path) -> Option<Url> {
+        // Paths are not native here, so `Url::from_file_path` is not available,
+        // the path is percent-encoded the same way instead.
+        let mut url = Url::parse("file:///").ok()?;
+        url.set_path(&path.to_string_lossy().replace('\\', "/"));
+        Some(url)
+    }
+
     fn is_absolute(&self, path: &Path) -> bool {
         let path_str = JsValue::from_str(&path.to_string_lossy());
         let this = JsValue::null();
@@ -2,7 +2,8 @@ use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use taplo::{
     dom::Node,
     formatter::{format, format_syntax, Options},
-    parser::parse,
+    parser::{parse, reparse},
+    rowan::{TextRange, TextSize},
 };
 
 pub fn parsing(c: &mut Criterion) {
@@ -10,6 +11,18 @@ pub fn parsing(c: &mut Criterion) {
     c.bench_function("parse taplo syntax", |b| {
         b.iter(|| parse(black_box(source)))
     });
+    c.bench_function("reparse taplo syntax", |b| {
+        let previous = parse(source);
+        let offset = source[..source.len() / 2].rfind('\n').unwrap() + 1;
+        let edited = format!(
+            "{}inserted = true\n{}",
+            &source[..offset],
+            &source[offset..]
+        );
+        let edit = TextRange::empty(TextSize::try_from(offset).unwrap());
+
+        b.iter(|| reparse(black_box(&previous), black_box(&edited), edit))
+    });
     c.bench_function("parse taplo dom", |b| {
         b.iter(|| parse(black_box(source)).into_dom())
     });
@@ -0,0 +1,1821 @@
+//! Serialization of modified DOM trees that preserves the original source.
+//!
+//! The modified tree is compared to a tree built from the syntax
+//! it was parsed from, and only the parts that differ are written again.
+//! Everything else, including comments, whitespace and the order of
+//! entries is copied from the source as-is.
+
+use super::{
+    node::{Array, ArrayKind, DomNode, Key, Node, Table, TableKind},
+    FromSyntax,
+};
+use crate::{
+    syntax::{
+        SyntaxElement,
+        SyntaxKind::{self, *},
+        SyntaxNode,
+    },
+    HashMap,
+};
+use rowan::{Direction, TextSize};
+use std::ops::Range;
+
+impl Node {
+    /// Serialize the node into TOML while keeping the source
+    /// it was parsed from intact wherever possible.
+    ///
+    /// Entries and items that were not modified are written exactly as they
+    /// appear in the source along with their comments and whitespace,
+    /// new entries are placed next to the existing entries of the same table.
+    ///
+    /// Only a root node parsed from a document has a source to preserve,
+    /// for any other node this is the same as [`Node::to_toml`].
+    pub fn to_toml_lossless(&self) -> String {
+        let (root, modified) = match (self.syntax().and_then(|s| s.as_node()), self) {
+            (Some(root), Node::Table(table)) if root.kind() == ROOT => (root.clone(), table),
+            _ => return self.to_toml(false, false),
+        };
+
+        let source = root.to_string();
+        apply_edits(&source, edits(&root, modified))
+    }
+}
+
+/// Text edits that turn the source of the root syntax into
+/// the modified table, sorted by their position and never overlapping.
+pub(super) fn edits(root: &SyntaxNode, modified: &Table) -> Vec<(Range<usize>, String)> {
+    let original = Table::from_syntax(root.clone().into());
+
+    let mut writer = Writer::new(root.clone());
+    writer.diff_table(&original, modified, None);
+    writer.finish()
+}
+
+/// Text edits that move an entry of a table before or after another entry
+/// of the same table.
+///
+/// Returns [`None`] if the entries are not separate lines or sections
+/// in the source, or if one contains the other.
+pub(super) fn move_entry_edits(
+    root: &SyntaxNode,
+    entry: (&Key, &Node),
+    target: (&Key, &Node),
+    after: bool,
+) -> Option<Vec<(Range<usize>, String)>> {
+    let mut writer = Writer::new(root.clone());
+
+    let moved = writer.entry_source(entry.0, entry.1)?;
+    let target = writer.entry_source(target.0, target.1)?;
+
+    let overlaps = moved.ranges().iter().any(|m| {
+        target
+            .ranges()
+            .iter()
+            .any(|t| m.start < t.end && t.start < m.end)
+    });
+    if overlaps {
+        return None;
+    }
+
+    match (moved, target) {
+        (EntrySource::Lines(moved), EntrySource::Lines(target)) => {
+            let text: String = moved
+                .iter()
+                .map(|range| with_newline(&writer.source[range.clone()]))
+                .collect();
+
+            writer.deletions.extend(moved);
+
+            if after {
+                writer.insert_lines(target.last()?.end, text);
+            } else {
+                writer.insert(target.first()?.start, text);
+            }
+        }
+        (EntrySource::Sections(moved), EntrySource::Sections(target)) => {
+            let text = moved
+                .iter()
+                .map(|range| with_newline(&writer.source[range.clone()]))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            for range in moved {
+                writer.remove_lines(range);
+            }
+
+            if after {
+                writer.insert_section(target.last()?.end, text);
+            } else {
+                writer.insert(target.first()?.start, text + "\n");
+            }
+        }
+        _ => return None,
+    }
+
+    Some(writer.finish())
+}
+
+/// Text edits that sort the entries of a table by their keys.
+///
+/// Every entry takes the place of another entry of the same kind,
+/// so values stay in the lines of values and tables in the sections of tables.
+///
+/// Returns [`None`] if an entry is not a single block of lines or sections
+/// in the source.
+pub(super) fn sort_entries_edits(
+    root: &SyntaxNode,
+    entries: &[(Key, Node)],
+) -> Option<Vec<(Range<usize>, String)>> {
+    let writer = Writer::new(root.clone());
+
+    let mut lines = Vec::new();
+    let mut sections = Vec::new();
+    for (key, node) in entries {
+        match writer.entry_source(key, node)? {
+            EntrySource::Lines(ranges) => lines.push((key, single_block(ranges)?)),
+            EntrySource::Sections(ranges) => sections.push((key, single_block(ranges)?)),
+        }
+    }
+
+    let mut edits = Vec::new();
+    for mut blocks in [lines, sections] {
+        blocks.sort_by_key(|(_, range)| range.start);
+        let slots: Vec<Range<usize>> = blocks.iter().map(|(_, range)| range.clone()).collect();
+        blocks.sort_by(|(a, _), (b, _)| a.value().cmp(b.value()));
+
+        // The last line might not end with a newline, so blocks
+        // are swapped without the newlines at their ends.
+        for (slot, (_, block)) in slots.into_iter().zip(blocks) {
+            if slot != block {
+                let slot_text = trim_newline(&writer.source[slot.clone()]);
+                let text = trim_newline(&writer.source[block]);
+                edits.push((slot.start..slot.start + slot_text.len(), text.to_string()));
+            }
+        }
+    }
+
+    edits.sort_by_key(|(range, _)| range.start);
+    if edits.windows(2).any(|w| w[0].0.end > w[1].0.start) {
+        return None;
+    }
+
+    Some(edits)
+}
+
+fn apply_edits(source: &str, edits: Vec<(Range<usize>, String)>) -> String {
+    let mut output = String::with_capacity(source.len());
+    let mut cursor = 0;
+    for (range, text) in edits {
+        output.push_str(&source[cursor..range.start]);
+        output.push_str(&text);
+        cursor = range.end;
+    }
+    output.push_str(&source[cursor..]);
+    output
+}
+
+/// Pending changes of an inline table in the source, these are
+/// collected first as the separators depend on all of them.
+#[derive(Default)]
+struct InlineTableEdits {
+    modified: Option<Table>,
+    removed: Vec<SyntaxNode>,
+    inserted: Vec<String>,
+}
+
+/// Where an item is inserted in an inline array or table.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Gap {
+    /// Before the given item and after all the preceding removed ones.
+    Before(usize),
+    /// After the given item.
+    After(usize),
+}
+
+/// The side of an inserted text with an empty line that separates it
+/// from the rest of the document.
+#[derive(Clone, Copy)]
+enum Separator {
+    Before,
+    After,
+}
+
+/// The lines or table sections of an entry in the source,
+/// including the comments directly above them.
+enum EntrySource {
+    Lines(Vec<Range<usize>>),
+    Sections(Vec<Range<usize>>),
+}
+
+impl EntrySource {
+    fn ranges(&self) -> &[Range<usize>] {
+        match self {
+            EntrySource::Lines(ranges) | EntrySource::Sections(ranges) => ranges,
+        }
+    }
+}
+
+/// An entry that is not in the source yet, along with
+/// the closest entries around it that are.
+struct NewEntry {
+    key: Key,
+    node: Node,
+    previous: Option<(Key, Node)>,
+    next: Option<(Key, Node)>,
+}
+
+/// Where new entries of a table are written.
+enum Location {
+    Root,
+    /// The section of a table header.
+    Section(SyntaxNode),
+    /// A table that only appears as a part of headers, the prefix is
+    /// the dotted key of the table.
+    HeaderPseudo {
+        header: SyntaxNode,
+        prefix: String,
+    },
+    /// A table defined by dotted keys of the given top-level entries.
+    Dotted {
+        entry: SyntaxNode,
+        prefix: String,
+    },
+    /// An inline table, the prefix is either empty or the dotted key
+    /// of a table in it followed by a dot.
+    Inline {
+        table: SyntaxNode,
+        prefix: String,
+    },
+}
+
+struct Writer {
+    root: SyntaxNode,
+    source: String,
+    deletions: Vec<Range<usize>>,
+    edits: Vec<(Range<usize>, String)>,
+    inline_tables: HashMap<SyntaxNode, InlineTableEdits>,
+    /// The newline of the source, inserted text is written with `\n`
+    /// and converted to it when the edits are finished.
+    newline: &'static str,
+    /// The indices of inserted edits that are separated by an empty line.
+    separators: Vec<(usize, Separator)>,
+}
+
+impl Writer {
+    fn new(root: SyntaxNode) -> Self {
+        let source = root.to_string();
+        let newline = match source.find('\n') {
+            Some(idx) if source[..idx].ends_with('\r') => "\r\n",
+            _ => "\n",
+        };
+
+        Self {
+            source,
+            newline,
+            separators: Default::default(),
+            root,
+            deletions: Default::default(),
+            edits: Default::default(),
+            inline_tables: Default::default(),
+        }
+    }
+
+    fn diff_node(&mut self, key: Option<&Key>, original: &Node, modified: &Node) {
+        match (original, modified) {
+            (Node::Table(original), Node::Table(modified)) => {
+                self.diff_table(original, modified, key);
+            }
+            (Node::Array(original), Node::Array(modified)) => self.diff_array(original, modified),
+            _ => {}
+        }
+    }
+
+    fn diff_table(&mut self, original: &Table, modified: &Table, key: Option<&Key>) {
+        if let Some(inline_table) = syntax_node(original, INLINE_TABLE) {
+            self.inline_table(inline_table).modified = Some(modified.clone());
+        }
+
+        let original_entries = original.entries().read();
+        let modified_entries = modified.entries().read();
+
+        // The original entries that are kept in place, in the modified order.
+        let mut kept: Vec<Option<(Key, Node)>> = Vec::with_capacity(modified_entries.len());
+
+        for (key, node) in modified_entries.iter() {
+            match original_entries.lookup.get_key_value(key) {
+                Some((original_key, original_node)) if is_same(original_node, node) => {
+                    self.diff_node(Some(original_key), original_node, node);
+                    kept.push(Some((original_key.clone(), original_node.clone())));
+                }
+                Some((original_key, original_node))
+                    if is_value(original_node)
+                        && (renders_inline(node) || is_nested(original_node)) =>
+                {
+                    self.replace(original_node, node);
+                    kept.push(Some((original_key.clone(), original_node.clone())));
+                }
+                Some((original_key, original_node)) => {
+                    self.remove_entry(original_key, original_node);
+                    kept.push(None);
+                }
+                None => kept.push(None),
+            }
+        }
+
+        let inserted: Vec<NewEntry> = modified_entries
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| kept[*idx].is_none())
+            .map(|(idx, (key, node))| NewEntry {
+                key: key.clone(),
+                node: node.clone(),
+                previous: kept[..idx].iter().rev().find_map(Clone::clone),
+                next: kept[idx + 1..].iter().find_map(Clone::clone),
+            })
+            .collect();
+
+        for (key, node) in original_entries.iter() {
+            if !modified_entries.lookup.contains_key(key) {
+                self.remove_entry(key, node);
+            }
+        }
+
+        if !inserted.is_empty() {
+            self.insert_entries(original, key, &inserted);
+        } else if original.kind() == TableKind::Pseudo
+            && modified_entries.is_empty()
+            && !original_entries.is_empty()
+        {
+            // Tables that only exist through their entries would disappear.
+            self.insert_empty_table(original, key);
+        }
+    }
+
+    fn diff_array(&mut self, original: &Array, modified: &Array) {
+        let original_items = original.items().read();
+        let modified_items = modified.items().read();
+
+        // Unchanged items are matched in their original order,
+        // everything else is removed or inserted.
+        let mut matched = vec![None; modified_items.len()];
+        let mut kept = vec![false; original_items.len()];
+        let mut next = 0;
+        for (idx, item) in modified_items.iter().enumerate() {
+            if let Some(original_idx) =
+                (next..original_items.len()).find(|&i| is_same(&original_items[i], item))
+            {
+                matched[idx] = Some(original_idx);
+                kept[original_idx] = true;
+                next = original_idx + 1;
+            }
+        }
+
+        for (item, original_idx) in modified_items.iter().zip(&matched) {
+            if let Some(original_idx) = original_idx {
+                self.diff_node(None, &original_items[*original_idx], item);
+            }
+        }
+
+        let mut inserted: Vec<(Option<Gap>, &Node)> = Vec::new();
+        for (idx, item) in modified_items.iter().enumerate() {
+            if matched[idx].is_some() {
+                continue;
+            }
+
+            let gap = matched[..idx]
+                .iter()
+                .rev()
+                .find_map(|m| m.map(Gap::After))
+                .or_else(|| matched[idx..].iter().find_map(|m| m.map(Gap::Before)));
+
+            inserted.push((gap, item));
+        }
+
+        if original.kind() == ArrayKind::Tables {
+            self.diff_array_of_tables(original, &original_items, &kept, &inserted);
+            return;
+        }
+
+        let array = match syntax_node(original, ARRAY) {
+            Some(array) => array,
+            None => return,
+        };
+
+        let elements: Vec<SyntaxNode> = array.children().filter(|n| n.kind() == VALUE).collect();
+
+        if !kept.contains(&true) || elements.len() != original_items.len() {
+            if !original_items.is_empty() || !modified_items.is_empty() {
+                self.edits
+                    .push((node_range(&array), render_inline(&modified.clone().into())));
+            }
+            return;
+        }
+
+        let removed: Vec<bool> = kept.iter().map(|k| !k).collect();
+        let inserted = inserted
+            .into_iter()
+            .map(|(gap, item)| (gap.unwrap(), render_inline(item)))
+            .collect();
+
+        self.edit_container(&elements, &removed, inserted);
+    }
+
+    fn diff_array_of_tables(
+        &mut self,
+        original: &Array,
+        original_items: &[Node],
+        kept: &[bool],
+        inserted: &[(Option<Gap>, &Node)],
+    ) {
+        for (item, kept) in original_items.iter().zip(kept) {
+            if !kept {
+                self.remove_node(item);
+            }
+        }
+
+        let first_header = match syntax_node(original, TABLE_ARRAY_HEADER) {
+            Some(header) => header,
+            None => return,
+        };
+        let path = header_path(&first_header);
+
+        for (gap, item) in inserted {
+            let mut section = String::new();
+            write_array_item(&mut section, &path, item);
+
+            match gap {
+                Some(Gap::Before(idx)) => {
+                    let header = original_items[*idx].syntax().and_then(|s| s.as_node());
+                    if let Some(header) = header {
+                        let at = line_start(&self.source, start(header));
+                        self.insert_separated(at, section + "\n", Separator::After);
+                    }
+                }
+                Some(Gap::After(idx)) => {
+                    let header = original_items[*idx].syntax().and_then(|s| s.as_node());
+                    if let Some(header) = header {
+                        let at = self.extended_section_end(header);
+                        self.insert_section(at, section);
+                    }
+                }
+                None => {
+                    let header = original_items
+                        .last()
+                        .and_then(|item| item.syntax())
+                        .and_then(|s| s.as_node());
+                    if let Some(header) = header {
+                        let at = self.extended_section_end(header);
+                        self.insert_section(at, section);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Removes all the syntax of an entry from the original document.
+    fn remove_entry(&mut self, key: &Key, node: &Node) {
+        let key_syntaxes = key_syntaxes(key);
+
+        for syntax in key_syntaxes {
+            if let Some(header) = owned_header(&syntax) {
+                self.remove_section(&header);
+            }
+        }
+
+        self.remove_node(node);
+    }
+
+    fn remove_node(&mut self, node: &Node) {
+        match node {
+            Node::Table(table) => {
+                if let Some(syntax) = table.syntax() {
+                    match syntax.kind() {
+                        TABLE_HEADER | TABLE_ARRAY_HEADER => {
+                            if let Some(header) = syntax.as_node() {
+                                self.remove_section(header);
+                            }
+                        }
+                        INLINE_TABLE => {
+                            self.remove_value(syntax);
+                            return;
+                        }
+                        _ => {}
+                    }
+                }
+
+                for (key, node) in table.entries().read().iter() {
+                    self.remove_entry(key, node);
+                }
+            }
+            Node::Array(array) if array.kind() == ArrayKind::Tables => {
+                for item in array.items().read().iter() {
+                    self.remove_node(item);
+                }
+            }
+            _ => {
+                if let Some(syntax) = node.syntax() {
+                    self.remove_value(syntax);
+                }
+            }
+        }
+    }
+
+    /// Removes the entry the value belongs to along with the comments above it.
+    fn remove_value(&mut self, value: &SyntaxElement) {
+        let entry = match value {
+            SyntaxElement::Node(n) => n.ancestors().find(|n| n.kind() == ENTRY),
+            SyntaxElement::Token(t) => t.parent_ancestors().find(|n| n.kind() == ENTRY),
+        };
+
+        let entry = match entry {
+            Some(entry) => entry,
+            None => return,
+        };
+
+        match entry.parent() {
+            Some(parent) if parent.kind() == INLINE_TABLE => {
+                let edits = self.inline_table(parent);
+                if !edits.removed.contains(&entry) {
+                    edits.removed.push(entry);
+                }
+            }
+            _ => {
+                let range = self.entry_lines(&entry);
+                self.deletions.push(range);
+            }
+        }
+    }
+
+    /// Removes a table header along with its entries and the comments above it,
+    /// sub-tables with separate headers are not affected.
+    fn remove_section(&mut self, header: &SyntaxNode) {
+        let from = self.comments_start(line_start(&self.source, start(header)));
+        let to = self.section_end(header);
+        self.remove_lines(from..to);
+    }
+
+    /// Removes whole lines of a section.
+    fn remove_lines(&mut self, range: Range<usize>) {
+        let Range { mut start, end } = range;
+
+        // The empty lines before the section are only removed if
+        // the next section is already separated from the previous one.
+        let next_line = line_end(&self.source, end);
+        if self.source[end..next_line].trim().is_empty() {
+            while start > 0 {
+                let previous = line_start(&self.source, start - 1);
+                if !self.source[previous..start].trim().is_empty() {
+                    break;
+                }
+                start = previous;
+            }
+        }
+
+        self.deletions.push(start..end);
+    }
+
+    fn replace(&mut self, original: &Node, modified: &Node) {
+        if let Some(syntax) = original.syntax() {
+            self.edits
+                .push((element_range(syntax), render_inline(modified)));
+        }
+    }
+
+    fn insert(&mut self, at: usize, text: String) {
+        self.edits.push((at..at, text));
+    }
+
+    /// Inserts lines at the start of a line, or at the end of the document.
+    fn insert_lines(&mut self, at: usize, text: String) {
+        if at == self.source.len() && !self.source.is_empty() && !self.source.ends_with('\n') {
+            self.insert(at, format!("\n{text}"));
+        } else {
+            self.insert(at, text);
+        }
+    }
+
+    /// Inserts a section separated by an empty line.
+    fn insert_section(&mut self, at: usize, section: String) {
+        if at == 0 && self.source[at..].trim().is_empty() {
+            self.insert(at, section);
+        } else {
+            self.insert_lines(at, format!("\n{section}"));
+            self.separators
+                .push((self.edits.len() - 1, Separator::Before));
+        }
+    }
+
+    /// Inserts text that ends with an empty line before the rest of the document.
+    fn insert_separated(&mut self, at: usize, text: String, separator: Separator) {
+        self.insert(at, text);
+        self.separators.push((self.edits.len() - 1, separator));
+    }
+
+    /// Removes the empty lines of inserted text that no longer separate it
+    /// from anything after the removals.
+    ///
+    /// A separator before the text is not needed at the start of the document or
+    /// after a table header that had its entries removed, and a separator after
+    /// the text is not needed at the end of the document.
+    fn remove_separators(&mut self, deletions: &[Range<usize>]) {
+        for (idx, separator) in core::mem::take(&mut self.separators) {
+            let at = self.edits[idx].0.start;
+
+            let needed = match separator {
+                Separator::Before => {
+                    let kept = self.kept_before(deletions, at);
+                    let from = kept.map_or(0, |kept| kept + 1);
+
+                    self.inserted_before(idx, from)
+                        || kept.is_some_and(|kept| {
+                            !self.in_header(kept)
+                                || !deletions.iter().any(|d| d.start < at && d.end > kept)
+                        })
+                }
+                Separator::After => {
+                    let kept = self.kept_after(deletions, at);
+                    let to = kept.unwrap_or(self.source.len());
+
+                    kept.is_some() || self.inserted_after(idx, to)
+                }
+            };
+
+            if needed {
+                continue;
+            }
+
+            let text = &mut self.edits[idx].1;
+            match separator {
+                Separator::Before if text.starts_with('\n') => {
+                    text.remove(0);
+                }
+                Separator::After if text.ends_with("\n\n") => {
+                    text.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The offset of the last character before the offset that is
+    /// neither whitespace nor removed.
+    fn kept_before(&self, deletions: &[Range<usize>], at: usize) -> Option<usize> {
+        self.source[..at]
+            .char_indices()
+            .rev()
+            .find(|(idx, c)| !c.is_whitespace() && !deletions.iter().any(|d| d.contains(idx)))
+            .map(|(idx, _)| idx)
+    }
+
+    /// The offset of the first character after the offset that is
+    /// neither whitespace nor removed.
+    fn kept_after(&self, deletions: &[Range<usize>], at: usize) -> Option<usize> {
+        self.source[at..]
+            .char_indices()
+            .map(|(idx, c)| (at + idx, c))
+            .find(|(idx, c)| !c.is_whitespace() && !deletions.iter().any(|d| d.contains(idx)))
+            .map(|(idx, _)| idx)
+    }
+
+    /// Whether other text is inserted from the offset up to the edit at the index,
+    /// insertions at the same offset are in the order they were made.
+    fn inserted_before(&self, idx: usize, from: usize) -> bool {
+        let at = self.edits[idx].0.start;
+
+        self.edits.iter().enumerate().any(|(other, (range, text))| {
+            !text.is_empty()
+                && ((from..at).contains(&range.start) || (range.start == at && other < idx))
+        })
+    }
+
+    /// Whether other text is inserted after the edit at the index up to the offset.
+    fn inserted_after(&self, idx: usize, to: usize) -> bool {
+        let at = self.edits[idx].0.start;
+
+        self.edits.iter().enumerate().any(|(other, (range, text))| {
+            !text.is_empty()
+                && ((at + 1..=to).contains(&range.start) || (range.start == at && other > idx))
+        })
+    }
+
+    /// Whether the character at the offset is part of a table header.
+    fn in_header(&self, offset: usize) -> bool {
+        let Ok(offset) = TextSize::try_from(offset) else {
+            return false;
+        };
+
+        self.root
+            .token_at_offset(offset)
+            .right_biased()
+            .is_some_and(|token| {
+                token
+                    .parent_ancestors()
+                    .any(|n| matches!(n.kind(), TABLE_HEADER | TABLE_ARRAY_HEADER))
+            })
+    }
+
+    fn inline_table(&mut self, syntax: SyntaxNode) -> &mut InlineTableEdits {
+        self.inline_tables.entry(syntax).or_default()
+    }
+
+    fn insert_entries(&mut self, table: &Table, key: Option<&Key>, entries: &[NewEntry]) {
+        let location = match self.location(table, key) {
+            Some(location) => location,
+            None => return,
+        };
+
+        if let Location::Inline { table, prefix } = location {
+            let edits = self.inline_table(table);
+            for entry in entries {
+                edits.inserted.push(format!(
+                    "{prefix}{} = {}",
+                    entry.key,
+                    render_inline(&entry.node)
+                ));
+            }
+            return;
+        }
+
+        let entries: Vec<(Key, Node)> = entries
+            .iter()
+            .filter(|entry| !self.insert_next_to_neighbor(&location, entry))
+            .map(|entry| (entry.key.clone(), entry.node.clone()))
+            .collect();
+
+        if entries.is_empty() {
+            return;
+        }
+
+        let (lines, sections): (Vec<_>, Vec<_>) =
+            entries.iter().partition(|(_, node)| renders_inline(node));
+
+        match location {
+            Location::Root => {
+                let section_path = String::new();
+                if !lines.is_empty() {
+                    self.insert_root_lines(&lines);
+                }
+
+                for (key, node) in sections {
+                    let mut section = String::new();
+                    write_section(&mut section, &join(&section_path, key), node);
+                    let at = self.source.len();
+                    self.insert_section(at, section);
+                }
+            }
+            Location::Section(header) => {
+                let path = header_path(&header);
+
+                if !lines.is_empty() {
+                    let (at, indent) = match self.last_entry(&header) {
+                        Some(entry) => (line_end(&self.source, end(&entry)), self.indent(&entry)),
+                        None => (line_end(&self.source, end(&header)), String::new()),
+                    };
+                    self.insert_lines(at, render_lines(&indent, "", &lines));
+                }
+
+                let at = self.section_end(&header);
+                for (key, node) in sections {
+                    let mut section = String::new();
+                    write_section(&mut section, &join(&path, key), node);
+                    self.insert_section(at, section);
+                }
+            }
+            Location::HeaderPseudo { header, prefix } => {
+                let mut section = format!("[{prefix}]\n");
+                write_table_body(&mut section, &prefix, &entries);
+                let at = self.extended_section_end(&header);
+                self.insert_section(at, section);
+            }
+            Location::Dotted { entry, prefix } => {
+                if !lines.is_empty() {
+                    let at = line_end(&self.source, end(&entry));
+                    let indent = self.indent(&entry);
+                    self.insert_lines(at, render_lines(&indent, &format!("{prefix}."), &lines));
+                }
+
+                if sections.is_empty() {
+                    return;
+                }
+
+                let header = entry
+                    .siblings(Direction::Prev)
+                    .find(|n| matches!(n.kind(), TABLE_HEADER | TABLE_ARRAY_HEADER));
+
+                let (path, at) = match &header {
+                    Some(header) => (
+                        join_str(&header_path(header), &prefix),
+                        self.section_end(header),
+                    ),
+                    None => (prefix, self.root_entries_end()),
+                };
+
+                for (key, node) in sections {
+                    let mut section = String::new();
+                    write_section(&mut section, &join(&path, key), node);
+                    self.insert_section(at, section);
+                }
+            }
+            Location::Inline { .. } => unreachable!(),
+        }
+    }
+
+    /// Inserts an entry directly before or after the closest entry
+    /// around it that is written the same way.
+    fn insert_next_to_neighbor(&mut self, location: &Location, entry: &NewEntry) -> bool {
+        let section = !renders_inline(&entry.node);
+
+        let (path, prefix) = match location {
+            Location::Root => (String::new(), String::new()),
+            Location::Section(header) => (header_path(header), String::new()),
+            Location::Dotted { prefix, .. } if !section => (String::new(), format!("{prefix}.")),
+            _ => return false,
+        };
+
+        let neighbors = [(&entry.previous, true), (&entry.next, false)];
+        for (neighbor, after) in neighbors {
+            let source = match neighbor {
+                Some((key, node)) => self.entry_source(key, node),
+                None => continue,
+            };
+
+            match source {
+                Some(EntrySource::Lines(ranges)) if !section => {
+                    let first = ranges[0].start;
+                    let indent = self.indent_at(first);
+                    let text = format!(
+                        "{indent}{prefix}{} = {}\n",
+                        entry.key,
+                        render_inline(&entry.node)
+                    );
+
+                    if after {
+                        self.insert_lines(ranges[ranges.len() - 1].end, text);
+                    } else {
+                        self.insert(first, text);
+                    }
+                    return true;
+                }
+                Some(EntrySource::Sections(ranges)) if section => {
+                    let mut text = String::new();
+                    write_section(&mut text, &join(&path, &entry.key), &entry.node);
+
+                    if after {
+                        self.insert_section(ranges[ranges.len() - 1].end, text);
+                    } else {
+                        self.insert_separated(ranges[0].start, text + "\n", Separator::After);
+                    }
+                    return true;
+                }
+                _ => {}
+            }
+        }
+
+        false
+    }
+
+    fn entry_source(&self, key: &Key, node: &Node) -> Option<EntrySource> {
+        match node {
+            Node::Table(table) => {
+                let syntax = table.syntax()?;
+                match syntax.kind() {
+                    TABLE_HEADER => {
+                        let header = syntax.as_node()?;
+                        return Some(EntrySource::Sections(
+                            self.sections_with_prefix(&header_keys(header)),
+                        ));
+                    }
+                    TABLE_ARRAY_HEADER => {
+                        let header = syntax.as_node()?;
+                        let start = self.comments_start(line_start(&self.source, start(header)));
+                        let section = start..self.extended_section_end(header);
+                        return Some(EntrySource::Sections(vec![section]));
+                    }
+                    INLINE_TABLE => return self.value_source(syntax),
+                    ROOT => return None,
+                    _ => {}
+                }
+
+                let key_syntaxes = key_syntaxes(key);
+
+                if let Some(header) = key_syntaxes.iter().find_map(owned_header) {
+                    return Some(EntrySource::Sections(
+                        self.sections_with_prefix(&header_keys(&header)),
+                    ));
+                }
+
+                let token = syntax.as_token()?;
+                let key_node = token.parent()?;
+                let parent = key_node.parent()?;
+
+                match parent.kind() {
+                    TABLE_HEADER | TABLE_ARRAY_HEADER => {
+                        let count = key_node
+                            .children_with_tokens()
+                            .filter(|c| c.kind() == IDENT)
+                            .position(|c| c.as_token() == Some(token))?
+                            + 1;
+
+                        let mut prefix = header_keys(&parent);
+                        prefix.truncate(count);
+                        Some(EntrySource::Sections(self.sections_with_prefix(&prefix)))
+                    }
+                    ENTRY if parent.parent()?.kind() == ROOT => {
+                        let mut entries: Vec<SyntaxNode> = key_syntaxes
+                            .iter()
+                            .filter_map(|s| s.parent()?.parent())
+                            .filter(|n| n.kind() == ENTRY)
+                            .collect();
+                        entries.sort_by_key(start);
+                        entries.dedup();
+
+                        Some(EntrySource::Lines(
+                            entries.iter().map(|e| self.entry_lines(e)).collect(),
+                        ))
+                    }
+                    _ => None,
+                }
+            }
+            Node::Array(array) if array.kind() == ArrayKind::Tables => {
+                let items = array.items().read();
+                let mut ranges = Vec::with_capacity(items.len());
+                for item in items.iter() {
+                    match self.entry_source(key, item)? {
+                        EntrySource::Sections(item_ranges) => ranges.extend(item_ranges),
+                        EntrySource::Lines(_) => return None,
+                    }
+                }
+                Some(EntrySource::Sections(self.join_blocks(ranges)))
+            }
+            _ => self.value_source(node.syntax()?),
+        }
+    }
+
+    /// The line of a top-level entry with the given value.
+    fn value_source(&self, value: &SyntaxElement) -> Option<EntrySource> {
+        let entry = match value {
+            SyntaxElement::Node(n) => n.ancestors().find(|n| n.kind() == ENTRY),
+            SyntaxElement::Token(t) => t.parent_ancestors().find(|n| n.kind() == ENTRY),
+        }?;
+
+        if entry.parent()?.kind() != ROOT {
+            return None;
+        }
+
+        Some(EntrySource::Lines(vec![self.entry_lines(&entry)]))
+    }
+
+    fn entry_lines(&self, entry: &SyntaxNode) -> Range<usize> {
+        let start = self.comments_start(line_start(&self.source, start(entry)));
+        start..line_end(&self.source, end(entry))
+    }
+
+    /// All the sections with headers that start with the given keys.
+    fn sections_with_prefix(&self, prefix: &[String]) -> Vec<Range<usize>> {
+        let ranges = self
+            .root
+            .children()
+            .filter(|n| matches!(n.kind(), TABLE_HEADER | TABLE_ARRAY_HEADER))
+            .filter(|header| header_keys(header).starts_with(prefix))
+            .map(|header| {
+                self.comments_start(line_start(&self.source, start(&header)))
+                    ..self.section_end(&header)
+            })
+            .collect();
+
+        self.join_blocks(ranges)
+    }
+
+    /// Joins ranges that are only separated by empty lines.
+    fn join_blocks(&self, ranges: Vec<Range<usize>>) -> Vec<Range<usize>> {
+        let mut joined: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match joined.last_mut() {
+                Some(last)
+                    if last.end <= range.start
+                        && self.source[last.end..range.start].trim().is_empty() =>
+                {
+                    last.end = range.end;
+                }
+                _ => joined.push(range),
+            }
+        }
+        joined
+    }
+
+    /// The start of the comment lines directly above the given line.
+    fn comments_start(&self, mut line: usize) -> usize {
+        while line > 0 {
+            let previous = line_start(&self.source, line - 1);
+            if !self.source[previous..line].trim_start().starts_with('#') {
+                break;
+            }
+            line = previous;
+        }
+        line
+    }
+
+    fn insert_empty_table(&mut self, table: &Table, key: Option<&Key>) {
+        match self.location(table, key) {
+            Some(Location::HeaderPseudo { header, prefix }) => {
+                let at = self.extended_section_end(&header);
+                self.insert_section(at, format!("[{prefix}]\n"));
+            }
+            Some(Location::Dotted { entry, prefix }) => {
+                let at = line_end(&self.source, end(&entry));
+                let indent = self.indent(&entry);
+                self.insert_lines(at, format!("{indent}{prefix} = {{}}\n"));
+            }
+            Some(Location::Inline { table, prefix }) if !prefix.is_empty() => {
+                let prefix = prefix.trim_end_matches('.');
+                self.inline_table(table)
+                    .inserted
+                    .push(format!("{prefix} = {{}}"));
+            }
+            _ => {}
+        }
+    }
+
+    /// Top-level entry lines are written after the existing ones,
+    /// or before the first table header.
+    fn insert_root_lines(&mut self, lines: &[&(Key, Node)]) {
+        let last_entry = self
+            .root
+            .children()
+            .take_while(|n| !matches!(n.kind(), TABLE_HEADER | TABLE_ARRAY_HEADER))
+            .filter(|n| n.kind() == ENTRY)
+            .last();
+
+        if let Some(entry) = last_entry {
+            let at = line_end(&self.source, end(&entry));
+            let indent = self.indent(&entry);
+            self.insert_lines(at, render_lines(&indent, "", lines));
+            return;
+        }
+
+        let first_header = self
+            .root
+            .children()
+            .find(|n| matches!(n.kind(), TABLE_HEADER | TABLE_ARRAY_HEADER));
+
+        match first_header {
+            Some(header) => {
+                // Keep the comments directly above the header attached to it.
+                let mut at = line_start(&self.source, start(&header));
+                while at > 0 {
+                    let previous = line_start(&self.source, at - 1);
+                    if !self.source[previous..at].trim_start().starts_with('#') {
+                        break;
+                    }
+                    at = previous;
+                }
+                self.insert_separated(at, render_lines("", "", lines) + "\n", Separator::After);
+            }
+            None => {
+                let at = self.source.len();
+                self.insert_lines(at, render_lines("", "", lines));
+            }
+        }
+    }
+
+    fn location(&self, table: &Table, key: Option<&Key>) -> Option<Location> {
+        let syntax = table.syntax()?;
+
+        match syntax.kind() {
+            ROOT => return Some(Location::Root),
+            TABLE_HEADER | TABLE_ARRAY_HEADER => {
+                return Some(Location::Section(syntax.as_node()?.clone()))
+            }
+            INLINE_TABLE => {
+                return Some(Location::Inline {
+                    table: syntax.as_node()?.clone(),
+                    prefix: String::new(),
+                })
+            }
+            _ => {}
+        }
+
+        let key = key?;
+        let key_syntaxes = key_syntaxes(key);
+
+        if let Some(header) = key_syntaxes.iter().find_map(owned_header) {
+            return Some(Location::Section(header));
+        }
+
+        let token = syntax.as_token()?;
+        let key_node = token.parent()?;
+        let prefix = self.source[start(&key_node)..usize::from(token.text_range().end())]
+            .trim()
+            .to_string();
+        let parent = key_node.parent()?;
+
+        match parent.kind() {
+            TABLE_HEADER | TABLE_ARRAY_HEADER => Some(Location::HeaderPseudo {
+                header: parent,
+                prefix,
+            }),
+            ENTRY => match parent.parent() {
+                Some(inline_table) if inline_table.kind() == INLINE_TABLE => {
+                    Some(Location::Inline {
+                        table: inline_table,
+                        prefix: format!("{prefix}."),
+                    })
+                }
+                _ => {
+                    let entry = key_syntaxes
+                        .iter()
+                        .filter_map(|s| s.parent()?.parent())
+                        .filter(|n| n.kind() == ENTRY)
+                        .max_by_key(start)?;
+                    Some(Location::Dotted { entry, prefix })
+                }
+            },
+            _ => None,
+        }
+    }
+
+    fn last_entry(&self, header: &SyntaxNode) -> Option<SyntaxNode> {
+        header
+            .siblings(Direction::Next)
+            .skip(1)
+            .take_while(|n| !matches!(n.kind(), TABLE_HEADER | TABLE_ARRAY_HEADER))
+            .filter(|n| n.kind() == ENTRY)
+            .last()
+    }
+
+    /// The end of the line of the last entry that belongs to the header.
+    fn section_end(&self, header: &SyntaxNode) -> usize {
+        let last = self.last_entry(header).unwrap_or_else(|| header.clone());
+        line_end(&self.source, end(&last))
+    }
+
+    /// The end of the section including all the following
+    /// sections of sub-tables.
+    fn extended_section_end(&self, header: &SyntaxNode) -> usize {
+        let keys = header_keys(header);
+        let mut section_end = self.section_end(header);
+
+        for next in header.siblings(Direction::Next).skip(1) {
+            if !matches!(next.kind(), TABLE_HEADER | TABLE_ARRAY_HEADER) {
+                continue;
+            }
+
+            let next_keys = header_keys(&next);
+            if next_keys.len() <= keys.len() || !next_keys.starts_with(&keys) {
+                break;
+            }
+
+            section_end = self.section_end(&next);
+        }
+
+        section_end
+    }
+
+    /// The end of the line of the last entry before any table headers.
+    fn root_entries_end(&self) -> usize {
+        self.root
+            .children()
+            .take_while(|n| !matches!(n.kind(), TABLE_HEADER | TABLE_ARRAY_HEADER))
+            .filter(|n| n.kind() == ENTRY)
+            .last()
+            .map(|entry| line_end(&self.source, end(&entry)))
+            .unwrap_or(0)
+    }
+
+    fn indent(&self, node: &SyntaxNode) -> String {
+        self.indent_at(start(node))
+    }
+
+    fn indent_at(&self, offset: usize) -> String {
+        let from = line_start(&self.source, offset);
+        self.source[from..]
+            .chars()
+            .take_while(|c| matches!(c, ' ' | '\t'))
+            .collect()
+    }
+
+    /// Removes and inserts the items of an inline array or table,
+    /// with at least one item kept.
+    fn edit_container(
+        &mut self,
+        elements: &[SyntaxNode],
+        removed: &[bool],
+        inserted: Vec<(Gap, String)>,
+    ) {
+        let last_kept = match removed.iter().rposition(|r| !r) {
+            Some(idx) => idx,
+            None => return,
+        };
+        let trailing_comma = comma_after(elements.last().unwrap()).is_some();
+
+        let mut gaps: Vec<(Gap, Vec<String>)> = Vec::new();
+        for (gap, text) in inserted {
+            match gaps.iter_mut().find(|(g, _)| *g == gap) {
+                Some((_, texts)) => texts.push(text),
+                None => gaps.push((gap, vec![text])),
+            }
+        }
+
+        let mut keep_last_comma = false;
+
+        for (gap, texts) in gaps {
+            match gap {
+                Gap::After(idx) => {
+                    let element = &elements[idx];
+                    let next_start = elements
+                        .get(idx + 1)
+                        .map(start)
+                        .or_else(|| {
+                            Some(element.parent()?.last_token()?.text_range().start().into())
+                        })
+                        .unwrap_or_else(|| content_end(element));
+
+                    let multiline = self.source[content_end(element)..next_start].contains('\n');
+
+                    if !multiline {
+                        let text: String = texts.iter().map(|t| format!(", {t}")).collect();
+                        self.insert(content_end(element), text);
+                        continue;
+                    }
+
+                    let indent = self.indent(element);
+                    match comma_after(element) {
+                        Some(_) => {
+                            let at = self.item_range(element, false).end;
+
+                            let last_group = idx == last_kept;
+                            let count = texts.len();
+                            let text: String = texts
+                                .iter()
+                                .enumerate()
+                                .map(|(i, t)| {
+                                    let comma = if last_group && i + 1 == count && !trailing_comma {
+                                        ""
+                                    } else {
+                                        ","
+                                    };
+                                    format!("\n{indent}{t}{comma}")
+                                })
+                                .collect();
+
+                            if last_group {
+                                keep_last_comma = true;
+                            }
+                            self.insert(at, text);
+                        }
+                        None => {
+                            let text: String =
+                                texts.iter().map(|t| format!(",\n{indent}{t}")).collect();
+                            self.insert(content_end(element), text);
+                        }
+                    }
+                }
+                Gap::Before(idx) => {
+                    let element = &elements[idx];
+                    let from = line_start(&self.source, start(element));
+
+                    if self.source[from..start(element)].trim().is_empty() {
+                        let indent = self.indent(element);
+                        let text: String =
+                            texts.iter().map(|t| format!("{indent}{t},\n")).collect();
+                        self.insert(from, text);
+                    } else {
+                        let text: String = texts.iter().map(|t| format!("{t}, ")).collect();
+                        self.insert(start(element), text);
+                    }
+                }
+            }
+        }
+
+        for (element, removed) in elements.iter().zip(removed) {
+            if *removed {
+                let range = self.item_range(element, true);
+                self.deletions.push(range);
+            }
+        }
+
+        // The comma after the last kept item is no longer followed by
+        // anything, so it is removed unless the items had a trailing comma.
+        if last_kept + 1 < elements.len() && !trailing_comma && !keep_last_comma {
+            let element = &elements[last_kept];
+            let next = &elements[last_kept + 1];
+
+            if self.source[content_end(element)..start(next)].contains('\n') {
+                if let Some(comma) = comma_after(element) {
+                    self.deletions.push(element_range(&comma));
+                }
+            } else {
+                self.deletions.push(content_end(element)..start(next));
+            }
+        }
+    }
+
+    /// The range of an item in an inline array or table, including
+    /// the comma and a comment after it.
+    ///
+    /// If `lines` is true and the item is the only one in its line,
+    /// the whole line is included.
+    fn item_range(&self, element: &SyntaxNode, lines: bool) -> Range<usize> {
+        let from = start(element);
+        let mut to = content_end(element);
+        let mut has_comma = false;
+
+        let mut next = element.next_sibling_or_token();
+        let skip_whitespace = |next: &mut Option<SyntaxElement>| {
+            while let Some(n) = next.as_ref().filter(|n| n.kind() == WHITESPACE) {
+                *next = n.next_sibling_or_token();
+            }
+        };
+
+        skip_whitespace(&mut next);
+        if let Some(comma) = next.as_ref().filter(|n| n.kind() == COMMA) {
+            to = usize::from(comma.text_range().end());
+            has_comma = true;
+            next = comma.next_sibling_or_token();
+            skip_whitespace(&mut next);
+        }
+        if let Some(comment) = next.as_ref().filter(|n| n.kind() == COMMENT) {
+            to = usize::from(comment.text_range().end());
+        }
+
+        if !lines {
+            return from..to;
+        }
+
+        let line_from = line_start(&self.source, from);
+        let line_to = line_end(&self.source, to);
+        if self.source[line_from..from].trim().is_empty()
+            && self.source[to..line_to].trim().is_empty()
+            && self.source[..line_to].ends_with('\n')
+        {
+            return line_from..line_to;
+        }
+
+        if has_comma {
+            to += self.source[to..].len() - self.source[to..].trim_start_matches([' ', '\t']).len();
+            from..to
+        } else {
+            // The last item, the whitespace before it is not needed anymore.
+            self.source[..from].trim_end().len()..to
+        }
+    }
+
+    fn finish(mut self) -> Vec<(Range<usize>, String)> {
+        for (table, edits) in core::mem::take(&mut self.inline_tables) {
+            if edits.removed.is_empty() && edits.inserted.is_empty() {
+                continue;
+            }
+
+            let elements: Vec<SyntaxNode> =
+                table.children().filter(|n| n.kind() == ENTRY).collect();
+            let removed: Vec<bool> = elements.iter().map(|e| edits.removed.contains(e)).collect();
+
+            match removed.iter().rposition(|r| !r) {
+                Some(last_kept) => {
+                    let inserted = edits
+                        .inserted
+                        .into_iter()
+                        .map(|text| (Gap::After(last_kept), text))
+                        .collect();
+                    self.edit_container(&elements, &removed, inserted);
+                }
+                None => {
+                    if let Some(modified) = edits.modified {
+                        self.edits
+                            .push((node_range(&table), render_inline(&modified.into())));
+                    }
+                }
+            }
+        }
+
+        let mut deletions = merge_ranges(core::mem::take(&mut self.deletions));
+
+        // Removed lines after an empty line or at the start of the document
+        // would leave behind redundant empty lines.
+        for deletion in &mut deletions {
+            let before = &self.source[..deletion.start];
+            if !(before.is_empty() || before.ends_with("\n\n") || before.ends_with("\n\r\n")) {
+                continue;
+            }
+
+            while deletion.end < self.source.len() {
+                let next = line_end(&self.source, deletion.end);
+                if !self.source[deletion.end..next].trim().is_empty() {
+                    break;
+                }
+                deletion.end = next;
+            }
+        }
+        let deletions = merge_ranges(deletions);
+        self.remove_separators(&deletions);
+
+        // The original position is kept to order insertions
+        // that were moved after removed text.
+        let mut edits: Vec<(Range<usize>, String, usize)> = Vec::new();
+        for (range, text) in self.edits.drain(..) {
+            let original_start = range.start;
+            let overlapping = deletions
+                .iter()
+                .find(|d| d.start < range.end.max(range.start + 1) && range.start < d.end);
+
+            match overlapping {
+                // Insertions inside removed text are moved after it.
+                Some(d) if range.is_empty() => {
+                    let range = if range.start > d.start {
+                        d.end..d.end
+                    } else {
+                        range
+                    };
+                    edits.push((range, text, original_start));
+                }
+                Some(_) => {}
+                None => edits.push((range, text, original_start)),
+            }
+        }
+        edits.extend(
+            deletions
+                .into_iter()
+                .map(|range| (range.clone(), String::new(), range.start)),
+        );
+
+        // Insertions come first at the same position,
+        // then the widest of the overlapping replacements.
+        edits.sort_by_key(|(range, _, original_start)| {
+            (
+                range.start,
+                !range.is_empty(),
+                *original_start,
+                core::cmp::Reverse(range.end),
+            )
+        });
+
+        let mut cursor = 0;
+        edits.retain(|(range, _, _)| {
+            if range.start < cursor {
+                return false;
+            }
+            cursor = range.end;
+            true
+        });
+
+        let newline = self.newline;
+        edits
+            .into_iter()
+            .map(|(range, text, _)| (range, with_line_endings(text, newline)))
+            .collect()
+    }
+}
+
+/// Writes the bare `\n` newlines of the text as the given newline.
+///
+/// Text copied from the source keeps its `\r\n` newlines.
+fn with_line_endings(text: String, newline: &str) -> String {
+    if newline == "\n" || !text.contains('\n') {
+        return text;
+    }
+
+    let mut converted = String::with_capacity(text.len());
+    let mut previous = None;
+    for c in text.chars() {
+        if c == '\n' && previous != Some('\r') {
+            converted.push('\r');
+        }
+        converted.push(c);
+        previous = Some(c);
+    }
+    converted
+}
+
+fn merge_ranges(mut ranges: Vec<Range<usize>>) -> Vec<Range<usize>> {
+    ranges.sort_by_key(|r| (r.start, r.end));
+
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+fn is_same(original: &Node, modified: &Node) -> bool {
+    if core::mem::discriminant(original) != core::mem::discriminant(modified) {
+        return false;
+    }
+
+    // An array of tables can only be kept if it still has tables and nothing else.
+    if let Node::Array(array) = modified {
+        let items = array.items().read();
+        if array.kind() == ArrayKind::Tables
+            && (items.is_empty() || !items.iter().all(Node::is_table))
+        {
+            return false;
+        }
+    }
+
+    matches!(
+        (original.syntax(), modified.syntax()),
+        (Some(a), Some(b)) if a == b
+    )
+}
+
+/// The source of a value that has not been modified since it was parsed,
+/// it is written as-is even if the value comes from a different document.
+fn source_text(node: &Node) -> Option<String> {
+    let syntax = node.syntax()?;
+    let original = Node::from_syntax(syntax.clone());
+    is_unchanged(&original, node).then(|| syntax.to_string())
+}
+
+fn is_unchanged(original: &Node, modified: &Node) -> bool {
+    if !is_same(original, modified) {
+        return false;
+    }
+
+    match (original, modified) {
+        (Node::Table(original), Node::Table(modified)) => {
+            let original = original.entries().read();
+            let modified = modified.entries().read();
+            original.len() == modified.len()
+                && original
+                    .iter()
+                    .zip(modified.iter())
+                    .all(|(o, m)| o.0 == m.0 && is_unchanged(&o.1, &m.1))
+        }
+        (Node::Array(original), Node::Array(modified)) => {
+            let original = original.items().read();
+            let modified = modified.items().read();
+            original.len() == modified.len()
+                && original
+                    .iter()
+                    .zip(modified.iter())
+                    .all(|(o, m)| is_unchanged(o, m))
+        }
+        _ => true,
+    }
+}
+
+/// Whether the node is the value of an entry in the source.
+fn is_value(node: &Node) -> bool {
+    match node {
+        Node::Table(table) => table.kind() == TableKind::Inline,
+        Node::Array(array) => array.kind() == ArrayKind::Inline,
+        _ => true,
+    }
+}
+
+/// Whether the node is in an inline array or table in the source.
+fn is_nested(node: &Node) -> bool {
+    let parent = match node.syntax() {
+        Some(syntax) => syntax.parent(),
+        None => None,
+    };
+
+    parent
+        .into_iter()
+        .flat_map(|p| p.ancestors())
+        .any(|n| matches!(n.kind(), ARRAY | INLINE_TABLE))
+}
+
+/// Whether the node is written as a value instead of a separate section.
+fn renders_inline(node: &Node) -> bool {
+    match node {
+        Node::Table(table) => table.kind() == TableKind::Inline,
+        Node::Array(array) => {
+            let items = array.items().read();
+            array.kind() == ArrayKind::Inline
+                || items.is_empty()
+                || !items.iter().all(Node::is_table)
+        }
+        _ => true,
+    }
+}
+
+fn render_inline(node: &Node) -> String {
+    if let Some(text) = source_text(node) {
+        return text;
+    }
+
+    match node {
+        Node::Table(table) => {
+            let entries = table.entries().read();
+            if entries.is_empty() {
+                return "{}".into();
+            }
+
+            let entries: Vec<String> = entries
+                .iter()
+                .map(|(key, node)| format!("{key} = {}", render_inline(node)))
+                .collect();
+            format!("{{ {} }}", entries.join(", "))
+        }
+        Node::Array(array) => {
+            let items: Vec<String> = array.items().read().iter().map(render_inline).collect();
+            format!("[{}]", items.join(", "))
+        }
+        _ => node.to_toml(true, false),
+    }
+}
+
+fn render_lines(indent: &str, prefix: &str, lines: &[&(Key, Node)]) -> String {
+    lines
+        .iter()
+        .map(|(key, node)| format!("{indent}{prefix}{key} = {}\n", render_inline(node)))
+        .collect()
+}
+
+fn write_section(out: &mut String, path: &str, node: &Node) {
+    match node {
+        Node::Table(table) => {
+            let entries = table.entries().read();
+
+            // Tables that only contain other sections are implied by their headers.
+            if entries.is_empty() || entries.iter().any(|(_, n)| renders_inline(n)) {
+                out.push_str(&format!("[{path}]\n"));
+                write_table_body(out, path, &entries.all);
+                return;
+            }
+
+            for (idx, (key, node)) in entries.iter().enumerate() {
+                if idx != 0 {
+                    out.push('\n');
+                }
+                write_section(out, &join(path, key), node);
+            }
+        }
+        Node::Array(array) => {
+            for (idx, item) in array.items().read().iter().enumerate() {
+                if idx != 0 {
+                    out.push('\n');
+                }
+                write_array_item(out, path, item);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn write_array_item(out: &mut String, path: &str, item: &Node) {
+    if let Node::Table(table) = item {
+        out.push_str(&format!("[[{path}]]\n"));
+        write_table_body(out, path, &table.entries().read().all);
+    }
+}
+
+fn write_table_body(out: &mut String, path: &str, entries: &[(Key, Node)]) {
+    for (key, node) in entries.iter().filter(|(_, n)| renders_inline(n)) {
+        out.push_str(&format!("{key} = {}\n", render_inline(node)));
+    }
+
+    for (key, node) in entries.iter().filter(|(_, n)| !renders_inline(n)) {
+        out.push('\n');
+        write_section(out, &join(path, key), node);
+    }
+}
+
+fn join(path: &str, key: &Key) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+fn join_str(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+fn syntax_node<N: DomNode>(node: &N, kind: SyntaxKind) -> Option<SyntaxNode> {
+    node.syntax()
+        .and_then(|s| s.as_node())
+        .filter(|s| s.kind() == kind)
+        .cloned()
+}
+
+/// All the positions of the key in the source.
+fn key_syntaxes(key: &Key) -> Vec<SyntaxElement> {
+    key.syntax()
+        .cloned()
+        .into_iter()
+        .chain(key.inner.additional_syntaxes.read().iter().cloned())
+        .collect()
+}
+
+fn with_newline(text: &str) -> String {
+    if text.ends_with('\n') {
+        text.to_string()
+    } else {
+        format!("{text}\n")
+    }
+}
+
+/// The ranges as one range if they follow each other directly.
+fn single_block(ranges: Vec<Range<usize>>) -> Option<Range<usize>> {
+    let first = ranges.first()?;
+    let mut block = first.clone();
+    for range in &ranges[1..] {
+        if range.start != block.end {
+            return None;
+        }
+        block.end = range.end;
+    }
+    Some(block)
+}
+
+fn trim_newline(text: &str) -> &str {
+    text.strip_suffix('\n')
+        .map_or(text, |text| text.strip_suffix('\r').unwrap_or(text))
+}
+
+/// The header that the key is the last part of.
+fn owned_header(key: &SyntaxElement) -> Option<SyntaxNode> {
+    let token = key.as_token()?;
+    let key_node = token.parent().filter(|n| n.kind() == KEY)?;
+    let header = key_node
+        .parent()
+        .filter(|n| matches!(n.kind(), TABLE_HEADER | TABLE_ARRAY_HEADER))?;
+
+    let last_ident = key_node
+        .children_with_tokens()
+        .filter(|c| c.kind() == IDENT)
+        .last()?;
+
+    (last_ident.as_token()? == token).then_some(header)
+}
+
+fn header_key(header: &SyntaxNode) -> Option<SyntaxNode> {
+    header.children().find(|n| n.kind() == KEY)
+}
+
+fn header_path(header: &SyntaxNode) -> String {
+    header_key(header)
+        .map(|key| key.to_string().trim().to_string())
+        .unwrap_or_default()
+}
+
+fn header_keys(header: &SyntaxNode) -> Vec<String> {
+    header_key(header)
+        .map(|key| {
+            super::from_syntax::keys_from_syntax(&key.into())
+                .map(|k| k.value().to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn comma_after(element: &SyntaxNode) -> Option<SyntaxElement> {
+    element
+        .siblings_with_tokens(Direction::Next)
+        .skip(1)
+        .find(|n| n.kind() != WHITESPACE)
+        .filter(|n| n.kind() == COMMA)
+}
+
+fn start(node: &SyntaxNode) -> usize {
+    node.text_range().start().into()
+}
+
+fn end(node: &SyntaxNode) -> usize {
+    node.text_range().end().into()
+}
+
+/// The end of the node without trailing whitespace.
+fn content_end(node: &SyntaxNode) -> usize {
+    let mut token = node.last_token();
+    while let Some(t) = token
+        .as_ref()
+        .filter(|t| matches!(t.kind(), WHITESPACE | NEWLINE))
+    {
+        if t.text_range().start() <= node.text_range().start() {
+            break;
+        }
+        token = t.prev_token();
+    }
+
+    token.map_or_else(|| end(node), |t| t.text_range().end().into())
+}
+
+fn node_range(node: &SyntaxNode) -> Range<usize> {
+    start(node)..end(node)
+}
+
+fn element_range(element: &SyntaxElement) -> Range<usize> {
+    let range = element.text_range();
+    range.start().into()..range.end().into()
+}
+
+fn line_start(source: &str, offset: usize) -> usize {
+    source[..offset].rfind('\n').map_or(0, |idx| idx + 1)
+}
+
+/// The offset after the newline that ends the line.
+fn line_end(source: &str, offset: usize) -> usize {
+    source[offset..]
+        .find('\n')
+        .map_or(source.len(), |idx| offset + idx + 1)
+}
@@ -14,6 +14,7 @@ pub(crate) mod from_syntax;
 
 pub mod error;
 pub mod index;
+mod lossless;
 pub mod node;
 pub mod rewrite;
 mod to_toml;
@@ -266,6 +267,39 @@ impl Entries {
         self.lookup.insert(key.clone(), node.clone());
         self.all.push((key, node));
     }
+
+    /// Replaces the value of an existing entry in place,
+    /// or adds a new entry at the end.
+    pub(crate) fn insert(&mut self, key: Key, node: Node) -> Option<Node> {
+        match self.all.iter_mut().find(|(k, _)| *k == key) {
+            Some((existing_key, existing_node)) => {
+                self.lookup.insert(existing_key.clone(), node.clone());
+                Some(core::mem::replace(existing_node, node))
+            }
+            None => {
+                self.add(key, node);
+                None
+            }
+        }
+    }
+
+    /// Same as [`Entries::insert`], but new entries are added
+    /// at the given index instead of the end.
+    pub(crate) fn insert_at(&mut self, index: usize, key: Key, node: Node) -> Option<Node> {
+        if self.lookup.contains_key(&key) {
+            return self.insert(key, node);
+        }
+
+        self.lookup.insert(key.clone(), node.clone());
+        self.all.insert(index.min(self.all.len()), (key, node));
+        None
+    }
+
+    pub(crate) fn remove(&mut self, key: &Key) -> Option<Node> {
+        self.lookup.remove(key)?;
+        let idx = self.all.iter().position(|(k, _)| k == key)?;
+        Some(self.all.remove(idx).1)
+    }
 }
 
 impl FromIterator<(Key, Node)> for Entries {
@@ -334,7 +368,7 @@ impl Comment {
                 Some(t) => {
                     let text = t.text();
 
-                    if let Some(directive_content) = text.strip_prefix("#:") {
+                    if let Some(directive_content) = directive_content(text) {
                         let mut directive_content = directive_content.split_whitespace();
                         let directive_name = directive_content.next().unwrap_or("");
                         let directive_value = directive_content.next().unwrap_or("");
@@ -376,6 +410,13 @@ impl core::fmt::Display for Comment {
     }
 }
 
+/// The content of a directive comment after its prefix,
+/// either `#:` or `# taplo:`.
+fn directive_content(text: &str) -> Option<&str> {
+    text.strip_prefix("#:")
+        .or_else(|| text.strip_prefix('#')?.trim_start().strip_prefix("taplo:"))
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum CommentValue {
     Comment(String),
@@ -17,6 +17,19 @@ use super::{
     Comment, FromSyntax, KeyOrIndex, Keys,
 };
 
+/// An occurrence of a key that is written differently than where
+/// the key first appears, see [`Node::inconsistent_keys`].
+#[derive(Debug, Clone)]
+pub struct InconsistentKey {
+    pub key: Key,
+    /// The text of the key at this occurrence, e.g. `"a"`.
+    pub text: String,
+    pub range: TextRange,
+    /// The text of the key where it first appears, e.g. `a`.
+    pub first_text: String,
+    pub first_range: TextRange,
+}
+
 pub trait DomNode: Sized + Sealed {
     fn syntax(&self) -> Option<&SyntaxElement>;
     fn errors(&self) -> &Shared<Vec<Error>>;
@@ -176,6 +189,50 @@ impl Node {
         all.into_iter()
     }
 
+    /// Keys that are written differently where they appear in the document,
+    /// e.g. `a` in `a.b = 1` and `"a".c = 2`.
+    ///
+    /// Every occurrence that differs from the first one is returned.
+    pub fn inconsistent_keys(&self) -> Vec<InconsistentKey> {
+        let mut inconsistent = Vec::new();
+
+        for (keys, _) in self.flat_iter() {
+            let Some(KeyOrIndex::Key(key)) = keys.iter().last() else {
+                continue;
+            };
+
+            let mut syntaxes: Vec<SyntaxElement> = key
+                .syntax()
+                .cloned()
+                .into_iter()
+                .chain(key.inner.additional_syntaxes.read().iter().cloned())
+                .collect();
+            syntaxes.sort_by_key(|syntax| syntax.text_range().start());
+
+            let Some((first, others)) = syntaxes.split_first() else {
+                continue;
+            };
+
+            let first_text = first.to_string();
+
+            for other in others {
+                let text = other.to_string();
+
+                if text != first_text {
+                    inconsistent.push(InconsistentKey {
+                        key: key.clone(),
+                        text,
+                        range: other.text_range(),
+                        first_text: first_text.clone(),
+                        first_range: first.text_range(),
+                    });
+                }
+            }
+        }
+
+        inconsistent
+    }
+
     pub fn find_all_matches(
         &self,
         keys: Keys,
@@ -663,3 +720,83 @@ impl From<Invalid> for Node {
         Self::Invalid(v)
     }
 }
+
+impl From<bool> for Node {
+    fn from(v: bool) -> Self {
+        BoolInner {
+            errors: Default::default(),
+            syntax: None,
+            value: v.into(),
+        }
+        .wrap()
+        .into()
+    }
+}
+
+impl From<i64> for Node {
+    fn from(v: i64) -> Self {
+        IntegerInner {
+            errors: Default::default(),
+            syntax: None,
+            repr: IntegerRepr::Dec,
+            value: if v.is_negative() {
+                IntegerValue::Negative(v)
+            } else {
+                IntegerValue::Positive(v as _)
+            }
+            .into(),
+        }
+        .wrap()
+        .into()
+    }
+}
+
+impl From<i32> for Node {
+    fn from(v: i32) -> Self {
+        i64::from(v).into()
+    }
+}
+
+impl From<u64> for Node {
+    fn from(v: u64) -> Self {
+        IntegerInner {
+            errors: Default::default(),
+            syntax: None,
+            repr: IntegerRepr::Dec,
+            value: IntegerValue::Positive(v).into(),
+        }
+        .wrap()
+        .into()
+    }
+}
+
+impl From<f64> for Node {
+    fn from(v: f64) -> Self {
+        FloatInner {
+            errors: Default::default(),
+            syntax: None,
+            value: v.into(),
+        }
+        .wrap()
+        .into()
+    }
+}
+
+impl From<String> for Node {
+    fn from(v: String) -> Self {
+        StrInner {
+            errors: Default::default(),
+            syntax: None,
+            repr: StrRepr::Basic,
+            value: v.into(),
+        }
+        .wrap()
+        .into()
+    }
+}
+
+impl From<&str> for Node {
+    fn from(v: &str) -> Self {
+        v.to_string().into()
+    }
+}
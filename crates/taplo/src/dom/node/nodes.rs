@@ -2,9 +2,8 @@ use super::{DomNode, Node};
 use crate::{
     dom::{error::Error, Entries, KeyOrIndex, Keys},
     syntax::{SyntaxElement, SyntaxKind},
-    util::{shared::Shared, unescape},
+    util::{escape, is_bare_key, shared::Shared, unescape},
 };
-use logos::Lexer;
 use once_cell::unsync::OnceCell;
 use rowan::{NodeOrToken, TextRange};
 use std::{fmt::Write, iter::once, sync::Arc};
@@ -69,6 +68,30 @@ wrap_node! {
 }
 
 impl Table {
+    /// Create a new empty table that is not part of any document.
+    ///
+    /// When written into a document, the table becomes
+    /// a separate section where possible.
+    pub fn new() -> Self {
+        Self::new_kind(TableKind::Regular)
+    }
+
+    /// Create a new empty inline table that is not part of any document.
+    pub fn new_inline() -> Self {
+        Self::new_kind(TableKind::Inline)
+    }
+
+    fn new_kind(kind: TableKind) -> Self {
+        TableInner {
+            errors: Default::default(),
+            syntax: None,
+            header: false,
+            kind,
+            entries: Default::default(),
+        }
+        .wrap()
+    }
+
     pub fn get(&self, key: impl Into<Key>) -> Option<Node> {
         let key = key.into();
         let entries = self.inner.entries.read();
@@ -83,6 +106,47 @@ impl Table {
         self.inner.kind
     }
 
+    /// Insert an entry into the table.
+    ///
+    /// If an entry with the same key already exists, its value
+    /// is replaced at the same position and the previous value is returned,
+    /// otherwise the entry is added after all the other entries.
+    pub fn insert(&self, key: impl Into<Key>, node: impl Into<Node>) -> Option<Node> {
+        let mut previous = None;
+        self.inner.entries.update(|entries| {
+            previous = entries.insert(key.into(), node.into());
+        });
+        previous
+    }
+
+    /// Insert an entry into the table at the given index.
+    ///
+    /// If an entry with the same key already exists, its value
+    /// is replaced at the same position and the previous value is returned.
+    /// An index past the end adds the entry after all the other entries.
+    pub fn insert_at(
+        &self,
+        index: usize,
+        key: impl Into<Key>,
+        node: impl Into<Node>,
+    ) -> Option<Node> {
+        let mut previous = None;
+        self.inner.entries.update(|entries| {
+            previous = entries.insert_at(index, key.into(), node.into());
+        });
+        previous
+    }
+
+    /// Remove an entry from the table and return its value.
+    pub fn remove(&self, key: impl Into<Key>) -> Option<Node> {
+        let key = key.into();
+        let mut removed = None;
+        self.inner.entries.update(|entries| {
+            removed = entries.remove(&key);
+        });
+        removed
+    }
+
     /// Add an entry and also collect errors on conflicts.
     pub(crate) fn add_entry(&self, key: Key, node: Node) {
         self.inner.entries.update(|entries| {
@@ -92,14 +156,14 @@ impl Table {
                     if existing_table.inner.kind == TableKind::Pseudo
                         && new_table.inner.kind == TableKind::Pseudo
                     {
+                        if let Some(additional_syntax) = key.syntax() {
+                            existing_key.inner.additional_syntaxes.update(|syntaxes| {
+                                syntaxes.push(additional_syntax.clone());
+                            });
+                        }
+
                         let new_entries = new_table.entries().read();
                         for (k, n) in new_entries.iter() {
-                            if let Some(additional_syntax) = k.syntax() {
-                                existing_key.inner.additional_syntaxes.update(|syntaxes| {
-                                    syntaxes.push(additional_syntax.clone());
-                                });
-                            }
-
                             existing_table.add_entry(k.clone(), n.clone());
                         }
                         return;
@@ -127,6 +191,12 @@ impl Table {
     }
 }
 
+impl Default for Table {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TableKind {
     Regular,
@@ -266,17 +336,17 @@ impl core::fmt::Display for Key {
             return s.fmt(f);
         }
 
-        if !matches!(
-            Lexer::<SyntaxKind>::new(self.value()).next(),
-            Some(SyntaxKind::IDENT) | None
-        ) {
+        if is_bare_key(self.value()) {
+            return self.value().fmt(f);
+        }
+
+        if self.value().contains(|c: char| c == '\'' || c.is_control()) {
+            write!(f, r#""{}""#, escape(self.value()))
+        } else {
             f.write_char('\'')?;
             self.value().fmt(f)?;
-            f.write_char('\'')?;
-            return Ok(());
+            f.write_char('\'')
         }
-
-        self.value().fmt(f)
     }
 }
 
@@ -316,10 +386,74 @@ wrap_node! {
 }
 
 impl Array {
+    /// Create a new empty array that is not part of any document.
+    pub fn new() -> Self {
+        Self::new_kind(ArrayKind::Inline)
+    }
+
+    /// Create a new empty array of tables that is not part of any document.
+    ///
+    /// When written into a document, every table in the array
+    /// becomes a separate section where possible.
+    pub fn new_tables() -> Self {
+        Self::new_kind(ArrayKind::Tables)
+    }
+
+    fn new_kind(kind: ArrayKind) -> Self {
+        ArrayInner {
+            errors: Default::default(),
+            syntax: None,
+            kind,
+            items: Default::default(),
+        }
+        .wrap()
+    }
+
     pub fn items(&self) -> &Shared<Vec<Node>> {
         &self.inner.items
     }
 
+    /// Append an item to the end of the array.
+    pub fn push(&self, node: impl Into<Node>) {
+        let node = node.into();
+        self.inner.items.update(|items| items.push(node));
+    }
+
+    /// Insert an item at the given index, shifting all the items after it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the length of the array.
+    pub fn insert(&self, index: usize, node: impl Into<Node>) {
+        let node = node.into();
+        self.inner.items.update(|items| items.insert(index, node));
+    }
+
+    /// Replace the item at the given index and return the previous one,
+    /// or [`None`] if the index is out of bounds.
+    pub fn replace(&self, index: usize, node: impl Into<Node>) -> Option<Node> {
+        let node = node.into();
+        let mut previous = None;
+        self.inner.items.update(|items| {
+            if let Some(item) = items.get_mut(index) {
+                previous = Some(core::mem::replace(item, node));
+            }
+        });
+        previous
+    }
+
+    /// Remove the item at the given index and return it,
+    /// or [`None`] if the index is out of bounds.
+    pub fn remove(&self, index: usize) -> Option<Node> {
+        let mut removed = None;
+        self.inner.items.update(|items| {
+            if index < items.len() {
+                removed = Some(items.remove(index));
+            }
+        });
+        removed
+    }
+
     pub fn kind(&self) -> ArrayKind {
         self.inner.kind
     }
@@ -333,6 +467,12 @@ impl Array {
     }
 }
 
+impl Default for Array {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ArrayKind {
     Tables,
@@ -655,6 +795,16 @@ impl DateTime {
                     }
                 }
 
+                // Seconds can be omitted since TOML 1.1.
+                if token.kind() != SyntaxKind::DATE {
+                    let time_start = text.find('T').map_or(0, |idx| idx + 1);
+                    if text.len() >= time_start + 5
+                        && text.as_bytes().get(time_start + 5) != Some(&b':')
+                    {
+                        text.insert_str(time_start + 5, ":00");
+                    }
+                }
+
                 match token.kind() {
                     SyntaxKind::DATE_TIME_OFFSET => {
                         if let Ok(d) = time::OffsetDateTime::parse(
@@ -1,15 +1,29 @@
 use super::{
-    node::{DomNode, Node},
-    Keys,
+    error::QueryError,
+    lossless,
+    node::{DomNode, Key, Node, Table},
+    FromSyntax, KeyOrIndex, Keys,
 };
-use crate::{dom, syntax::SyntaxKind};
-use rowan::TextRange;
+use crate::{
+    dom,
+    parser::parse,
+    syntax::{SyntaxKind, SyntaxNode},
+};
+use rowan::{TextRange, TextSize};
 use std::{ops::Range, sync::Arc};
 use thiserror::Error;
 
 #[derive(Debug)]
 pub struct Rewrite {
     root: Node,
+    /// The patches that change the text of the source directly.
+    text_patches: Vec<PendingPatch>,
+    /// The patches that modify the tree, these are applied in order
+    /// and the source is compared to the resulting tree.
+    tree_patches: Vec<Patch>,
+    /// The changes of the source made by the tree patches.
+    tree_edits: Vec<PendingPatch>,
+    /// All the pending patches sorted by their position.
     patches: Vec<PendingPatch>,
 }
 
@@ -21,17 +35,21 @@ impl Rewrite {
 
         Ok(Self {
             root,
+            text_patches: Default::default(),
+            tree_patches: Default::default(),
+            tree_edits: Default::default(),
             patches: Default::default(),
         })
     }
 
     pub fn add(&mut self, patch: impl Into<Patch>) -> Result<&mut Self, Error> {
         let patch = patch.into();
-        match patch {
+        let patches = match &patch {
             Patch::RenameKeys { key, to } => {
                 let keys = key.parse::<Keys>()?;
                 let nodes = self.root.find_all_matches(keys, false)?;
 
+                let mut patches = Vec::new();
                 for (keys, _) in nodes {
                     let key = match keys.iter().last().cloned() {
                         Some(dom::KeyOrIndex::Key(k)) => k,
@@ -39,19 +57,29 @@ impl Rewrite {
                     };
 
                     for range in key.text_ranges() {
-                        self.check_overlap(range)?;
+                        self.check_overlap(&patches, range)?;
 
-                        self.patches.push(PendingPatch {
+                        patches.push(PendingPatch {
                             range,
                             kind: PendingPatchKind::Replace(to.clone()),
                         })
                     }
                 }
+                patches
+            }
+            Patch::MoveEntry { key, position } => self.move_entry_patches(key, position)?,
+            Patch::SortEntries { table } => self.sort_entries_patches(table)?,
+            Patch::SetValue { .. } | Patch::Remove { .. } | Patch::Insert { .. } => {
+                return self.add_tree_patch(patch);
             }
+        };
+
+        for patch in &patches {
+            self.check_overlap(&[], patch.range)?;
         }
 
-        self.patches
-            .sort_by(|a, b| b.range.start().cmp(&a.range.start()));
+        self.text_patches.extend(patches);
+        self.update_patches();
 
         Ok(self)
     }
@@ -60,19 +88,201 @@ impl Rewrite {
         &self.patches
     }
 
-    fn check_overlap(&self, range: TextRange) -> Result<(), Error> {
-        for patch in self.patches() {
-            if patch.range.contains_range(range)
-                || range.contains_range(patch.range)
-                || patch.range.contains(range.start())
-                || patch.range.contains(range.end())
-            {
+    /// Apply the patches to the source of the root node
+    /// and return the new text.
+    pub fn apply(&self) -> String {
+        let source = self.root.syntax().unwrap().to_string();
+
+        let mut output = String::with_capacity(source.len());
+        let mut cursor = 0;
+        for patch in &self.patches {
+            let range = std_range(patch.range);
+            match &patch.kind {
+                PendingPatchKind::Replace(to) => {
+                    output.push_str(&source[cursor..range.start]);
+                    output.push_str(to);
+                    cursor = range.end;
+                }
+            }
+        }
+        output.push_str(&source[cursor..]);
+
+        output
+    }
+
+    /// Every patch that modifies the tree is applied to the tree left by
+    /// the previous ones, so that they can change the same parts of the source,
+    /// e.g. entries set in the same new table share its header.
+    fn add_tree_patch(&mut self, patch: Patch) -> Result<&mut Self, Error> {
+        let root = self.original();
+        for previous in self.tree_patches.iter().chain([&patch]) {
+            modify(&root, previous)?;
+        }
+
+        let tree_edits = self.diff(&root);
+        for edit in &tree_edits {
+            for patch in &self.text_patches {
+                if overlaps(patch.range, edit.range) {
+                    return Err(Error::Overlap);
+                }
+            }
+        }
+
+        self.tree_patches.push(patch);
+        self.tree_edits = tree_edits;
+        self.update_patches();
+
+        Ok(self)
+    }
+
+    fn update_patches(&mut self) {
+        self.patches = self
+            .text_patches
+            .iter()
+            .chain(&self.tree_edits)
+            .cloned()
+            .collect();
+        self.patches
+            .sort_by_key(|patch| (patch.range.start(), patch.range.end()));
+    }
+
+    fn check_overlap(&self, new_patches: &[PendingPatch], range: TextRange) -> Result<(), Error> {
+        for patch in self.patches().iter().chain(new_patches) {
+            if overlaps(patch.range, range) {
                 return Err(Error::Overlap);
             }
         }
 
         Ok(())
     }
+
+    fn root_syntax(&self) -> SyntaxNode {
+        self.root
+            .syntax()
+            .and_then(|s| s.as_node())
+            .cloned()
+            .unwrap()
+    }
+
+    /// A new tree from the source that can be modified
+    /// without affecting the root node.
+    fn original(&self) -> Table {
+        Table::from_syntax(self.root_syntax().into())
+    }
+
+    fn diff(&self, modified: &Table) -> Vec<PendingPatch> {
+        lossless::edits(&self.root_syntax(), modified)
+            .into_iter()
+            .map(|(range, text)| PendingPatch {
+                range: text_range(range),
+                kind: PendingPatchKind::Replace(text.into()),
+            })
+            .collect()
+    }
+
+    fn move_entry_patches(
+        &self,
+        key: &str,
+        position: &Position,
+    ) -> Result<Vec<PendingPatch>, Error> {
+        let root = self.original();
+        let root_syntax = self.root_syntax();
+
+        let mut patches: Vec<PendingPatch> = Vec::new();
+        for (path, node) in matches(&root, key)? {
+            let (parent, key) = parent_table(&root, &path)?;
+            let entries = parent.entries().read();
+
+            let siblings: Vec<&(Key, Node)> = entries.iter().filter(|(k, _)| *k != key).collect();
+            let targets: Vec<(&(Key, Node), bool)> = match position {
+                Position::First => siblings.iter().map(|e| (*e, false)).collect(),
+                Position::Last => siblings.iter().rev().map(|e| (*e, true)).collect(),
+                Position::Before(target) | Position::After(target) => {
+                    let target = Key::new(&**target);
+                    let entry = siblings
+                        .iter()
+                        .find(|(k, _)| *k == target)
+                        .ok_or(QueryError::NotFound)
+                        .map_err(dom::error::Error::from)?;
+                    vec![(*entry, matches!(position, Position::After(_)))]
+                }
+            };
+
+            // The first and last positions are relative to the entries
+            // that can be swapped with the moved one.
+            let edits = targets
+                .into_iter()
+                .find_map(|((target_key, target), after)| {
+                    lossless::move_entry_edits(
+                        &root_syntax,
+                        (&key, &node),
+                        (target_key, target),
+                        after,
+                    )
+                });
+
+            let edits = match (edits, position) {
+                (Some(edits), _) => edits,
+                (None, Position::First | Position::Last) if siblings.is_empty() => continue,
+                (None, _) => return Err(Error::InvalidPosition),
+            };
+
+            for (range, text) in edits {
+                let range = text_range(range);
+                self.check_overlap(&patches, range)?;
+                patches.push(PendingPatch {
+                    range,
+                    kind: PendingPatchKind::Replace(text.into()),
+                });
+            }
+        }
+
+        Ok(patches)
+    }
+
+    fn sort_entries_patches(&self, table: &str) -> Result<Vec<PendingPatch>, Error> {
+        let root = self.original();
+        let root_syntax = self.root_syntax();
+
+        let tables: Vec<Node> = if table.is_empty() {
+            vec![root.into()]
+        } else {
+            matches(&root, table)?
+                .into_iter()
+                .map(|(_, node)| node)
+                .collect()
+        };
+
+        let mut patches: Vec<PendingPatch> = Vec::new();
+        for table in tables {
+            let Node::Table(table) = table else {
+                return Err(Error::ExpectedTable);
+            };
+
+            let entries = table.entries().read();
+            if entries
+                .all
+                .windows(2)
+                .all(|w| w[0].0.value() <= w[1].0.value())
+            {
+                continue;
+            }
+
+            let edits = lossless::sort_entries_edits(&root_syntax, &entries.all)
+                .ok_or(Error::InvalidPosition)?;
+
+            for (range, text) in edits {
+                let range = text_range(range);
+                self.check_overlap(&patches, range)?;
+                patches.push(PendingPatch {
+                    range,
+                    kind: PendingPatchKind::Replace(text.into()),
+                });
+            }
+        }
+
+        Ok(patches)
+    }
 }
 
 impl Rewrite {
@@ -82,36 +292,115 @@ impl Rewrite {
             to: to.into(),
         })
     }
+
+    pub fn set_value(&mut self, key: &str, value: &str) -> Result<&mut Self, Error> {
+        self.add(Patch::SetValue {
+            key: key.into(),
+            value: value.into(),
+        })
+    }
+
+    pub fn remove(&mut self, key: &str) -> Result<&mut Self, Error> {
+        self.add(Patch::Remove { key: key.into() })
+    }
+
+    pub fn insert(
+        &mut self,
+        table: &str,
+        key: &str,
+        value: &str,
+        position: Position,
+    ) -> Result<&mut Self, Error> {
+        self.add(Patch::Insert {
+            table: table.into(),
+            key: key.into(),
+            value: value.into(),
+            position,
+        })
+    }
+
+    pub fn move_entry(&mut self, key: &str, position: Position) -> Result<&mut Self, Error> {
+        self.add(Patch::MoveEntry {
+            key: key.into(),
+            position,
+        })
+    }
+
+    pub fn sort_entries(&mut self, table: &str) -> Result<&mut Self, Error> {
+        self.add(Patch::SortEntries {
+            table: table.into(),
+        })
+    }
 }
 
 impl core::fmt::Display for Rewrite {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut s = self.root.syntax().unwrap().to_string();
-
-        for patch in &self.patches {
-            match &patch.kind {
-                PendingPatchKind::Replace(to) => {
-                    s.replace_range(std_range(patch.range), to);
-                }
-            }
-        }
-
-        s.fmt(f)
+        self.apply().fmt(f)
     }
 }
 
+/// Patches that can be applied to a document.
+///
+/// Keys are dotted key patterns that may contain globs, values are TOML
+/// values (e.g. `"string"` or `{ a = 1 }`) written as they are given.
 #[derive(Debug)]
 pub enum Patch {
-    RenameKeys { key: Arc<str>, to: Arc<str> },
+    RenameKeys {
+        key: Arc<str>,
+        to: Arc<str>,
+    },
+    /// Set the value of all the matching entries,
+    /// if there are none the entry is created along with its missing tables
+    /// unless the key contains glob patterns.
+    SetValue {
+        key: Arc<str>,
+        value: Arc<str>,
+    },
+    /// Remove all the matching entries and array items.
+    Remove {
+        key: Arc<str>,
+    },
+    /// Insert a new entry into all the matching tables,
+    /// an empty table pattern is the root table.
+    Insert {
+        table: Arc<str>,
+        key: Arc<str>,
+        value: Arc<str>,
+        position: Position,
+    },
+    /// Move all the matching entries within their tables.
+    MoveEntry {
+        key: Arc<str>,
+        position: Position,
+    },
+    /// Sort the entries of all the matching tables by their keys,
+    /// an empty table pattern is the root table.
+    SortEntries {
+        table: Arc<str>,
+    },
 }
 
-#[derive(Debug)]
+/// The position of an entry relative to the other entries of its table.
+///
+/// Entries can only be placed next to entries of the same kind,
+/// that is values next to values and tables next to other tables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Position {
+    First,
+    Last,
+    /// Before the entry with the given key.
+    Before(Arc<str>),
+    /// After the entry with the given key.
+    After(Arc<str>),
+}
+
+#[derive(Debug, Clone)]
 pub struct PendingPatch {
     pub range: TextRange,
     pub kind: PendingPatchKind,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[non_exhaustive]
 pub enum PendingPatchKind {
     Replace(Arc<str>),
@@ -125,21 +414,241 @@ pub enum Error {
     ExpectedTable,
     #[error("new patches would overlap with existing ones")]
     Overlap,
+    #[error("the value is not valid TOML")]
+    InvalidValue,
+    #[error("the key already exists")]
+    KeyExists,
+    #[error("the entry cannot be moved to the given position")]
+    InvalidPosition,
     #[error("{0}")]
     Dom(#[from] dom::error::Error),
 }
 
+fn parse_value(value: &str) -> Result<Node, Error> {
+    let p = parse(&format!("value = {value}"));
+    if !p.errors.is_empty() {
+        return Err(Error::InvalidValue);
+    }
+
+    let dom = p.into_dom();
+    if dom.validate().is_err() {
+        return Err(Error::InvalidValue);
+    }
+
+    let root = dom.as_table().ok_or(Error::InvalidValue)?;
+    let entries = root.entries().read();
+    match entries.all.as_slice() {
+        [(key, node)] if key.value() == "value" => Ok(node.clone()),
+        _ => Err(Error::InvalidValue),
+    }
+}
+
+/// Applies a patch that modifies the tree.
+fn modify(root: &Table, patch: &Patch) -> Result<(), Error> {
+    match patch {
+        Patch::SetValue { key, value } => set_value(root, key, parse_value(value)?),
+        Patch::Remove { key } => remove(root, key),
+        Patch::Insert {
+            table,
+            key,
+            value,
+            position,
+        } => insert(root, table, Key::new(&**key), parse_value(value)?, position),
+        Patch::RenameKeys { .. } | Patch::MoveEntry { .. } | Patch::SortEntries { .. } => Ok(()),
+    }
+}
+
+/// Insertions only overlap with replaced text that surrounds them.
+fn overlaps(a: TextRange, b: TextRange) -> bool {
+    (!a.is_empty() || !b.is_empty()) && a.start() < b.end() && b.start() < a.end()
+}
+
+fn matches(root: &Table, key: &str) -> Result<Vec<(Keys, Node)>, Error> {
+    let keys = key.parse::<Keys>()?;
+    Ok(Node::from(root.clone())
+        .find_all_matches(keys, false)?
+        .collect())
+}
+
+/// The table that contains the last key of the path.
+fn parent_table(root: &Table, path: &Keys) -> Result<(Table, Key), Error> {
+    let key = match path.iter().last() {
+        Some(KeyOrIndex::Key(key)) => key.clone(),
+        _ => return Err(Error::ExpectedTable),
+    };
+
+    let parent = Keys::new(path.iter().take(path.len() - 1).cloned());
+    match Node::from(root.clone()).path(&parent) {
+        Some(Node::Table(table)) => Ok((table, key)),
+        _ => Err(Error::ExpectedTable),
+    }
+}
+
+fn set_value(root: &Table, key: &str, value: Node) -> Result<(), Error> {
+    let matches = matches(root, key)?;
+
+    if matches.is_empty() {
+        let keys = key.parse::<Keys>()?;
+
+        // Patterns can only be created if they are plain keys.
+        if keys.iter().any(|k| {
+            matches!(k, KeyOrIndex::Key(k) if k.value().contains(['*', '?', '[', ']', '{', '}']))
+        }) {
+            return Err(dom::error::Error::from(QueryError::NotFound).into());
+        }
+
+        let keys: Vec<Key> = keys
+            .iter()
+            .map(|k| match k {
+                KeyOrIndex::Key(k) => Ok(k.clone()),
+                KeyOrIndex::Index(_) => Err(Error::ExpectedTable),
+            })
+            .collect::<Result<_, _>>()?;
+
+        let mut table = root.clone();
+        for (idx, key) in keys.iter().enumerate() {
+            match table.get(key.clone()) {
+                Some(Node::Table(t)) if idx + 1 < keys.len() => table = t,
+                Some(_) => return Err(Error::ExpectedTable),
+                None => {
+                    let node = keys[idx + 1..].iter().rev().fold(value, |node, key| {
+                        let table = Table::new();
+                        table.insert(key.clone(), node);
+                        table.into()
+                    });
+                    table.insert(key.clone(), node);
+                    break;
+                }
+            }
+        }
+
+        return Ok(());
+    }
+
+    for (path, _) in matches {
+        match path.iter().last() {
+            Some(KeyOrIndex::Index(idx)) => {
+                let parent = Keys::new(path.iter().take(path.len() - 1).cloned());
+                match Node::from(root.clone()).path(&parent) {
+                    Some(Node::Array(array)) => {
+                        array.replace(*idx, value.clone());
+                    }
+                    _ => return Err(Error::ExpectedTable),
+                }
+            }
+            _ => {
+                let (table, key) = parent_table(root, &path)?;
+                table.insert(key, value.clone());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn remove(root: &Table, key: &str) -> Result<(), Error> {
+    // Later array items are removed first so that the indices stay valid.
+    for (path, _) in matches(root, key)?.into_iter().rev() {
+        match path.iter().last() {
+            Some(KeyOrIndex::Index(idx)) => {
+                let parent = Keys::new(path.iter().take(path.len() - 1).cloned());
+                if let Some(Node::Array(array)) = Node::from(root.clone()).path(&parent) {
+                    array.remove(*idx);
+                }
+            }
+            _ => {
+                if let Ok((table, key)) = parent_table(root, &path) {
+                    table.remove(key);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn insert(
+    root: &Table,
+    table: &str,
+    key: Key,
+    value: Node,
+    position: &Position,
+) -> Result<(), Error> {
+    let tables: Vec<Node> = if table.is_empty() {
+        vec![root.clone().into()]
+    } else {
+        matches(root, table)?
+            .into_iter()
+            .map(|(_, node)| node)
+            .collect()
+    };
+
+    if tables.is_empty() {
+        return Err(dom::error::Error::from(QueryError::NotFound).into());
+    }
+
+    for table in tables {
+        let table = match table {
+            Node::Table(table) => table,
+            _ => return Err(Error::ExpectedTable),
+        };
+
+        if table.get(key.clone()).is_some() {
+            return Err(Error::KeyExists);
+        }
+
+        let index = {
+            let entries = table.entries().read();
+            let index_of = |target: &Arc<str>| {
+                let target = Key::new(&**target);
+                entries
+                    .iter()
+                    .position(|(k, _)| *k == target)
+                    .ok_or_else(|| dom::error::Error::from(QueryError::NotFound))
+            };
+
+            match position {
+                Position::First => 0,
+                Position::Last => entries.len(),
+                Position::Before(target) => index_of(target)?,
+                Position::After(target) => index_of(target)? + 1,
+            }
+        };
+
+        table.insert_at(index, key.clone(), value.clone());
+    }
+
+    Ok(())
+}
+
 fn std_range(range: TextRange) -> Range<usize> {
     let start: usize = u32::from(range.start()) as usize;
     let end: usize = u32::from(range.end()) as usize;
     start..end
 }
 
+fn text_range(range: Range<usize>) -> TextRange {
+    TextRange::new(
+        TextSize::from(range.start as u32),
+        TextSize::from(range.end as u32),
+    )
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Rewrite;
+    use super::{Error, Position, Rewrite};
     use crate::parser::parse;
 
+    fn rewrite_toml(toml: &str) -> Rewrite {
+        Rewrite::new(parse(toml).into_dom()).unwrap()
+    }
+
+    fn assert_rewrite(expected_toml: &str, rewrite: &Rewrite) {
+        let output = rewrite.apply();
+        assert_eq!(expected_toml, output);
+        assert!(parse(&output).errors.is_empty());
+    }
+
     #[test]
     fn rename_keys() {
         let toml = r#"
@@ -197,4 +706,246 @@ mod tests {
 
         assert_eq!(expected_toml, patches.to_string());
     }
+
+    #[test]
+    fn rewrite_nothing() {
+        let rewrite = rewrite_toml(include_str!("../../../../test-data/rewrite/nothing.toml"));
+        assert_rewrite(
+            include_str!("../../../../test-data/rewrite/nothing_expected.toml"),
+            &rewrite,
+        );
+    }
+
+    #[test]
+    fn rewrite_key() {
+        let mut rewrite = rewrite_toml(include_str!("../../../../test-data/rewrite/key.toml"));
+
+        rewrite.rename_keys("rewrite_me", "rewritten").unwrap();
+        rewrite
+            .rename_keys("table.rewrite_me", "rewritten")
+            .unwrap();
+        rewrite
+            .rename_keys("table.subtable.rewrite_me", "rewritten")
+            .unwrap();
+        rewrite.rename_keys("arr.rewrite_me", "rewritten").unwrap();
+        rewrite
+            .rename_keys("arr.rewrite_me.*.rewrite_me", "rewritten")
+            .unwrap();
+
+        assert_rewrite(
+            include_str!("../../../../test-data/rewrite/key_expected.toml"),
+            &rewrite,
+        );
+    }
+
+    #[test]
+    fn rewrite_value() {
+        let mut rewrite = rewrite_toml(include_str!("../../../../test-data/rewrite/value.toml"));
+
+        rewrite
+            .set_value("value", "{ original_value = 2, additional_value = 3 }")
+            .unwrap();
+
+        assert_rewrite(
+            include_str!("../../../../test-data/rewrite/value_expected.toml"),
+            &rewrite,
+        );
+    }
+
+    #[test]
+    fn rewrite_multiple() {
+        let mut rewrite = rewrite_toml(include_str!("../../../../test-data/rewrite/multiple.toml"));
+
+        rewrite.remove("remove_this").unwrap();
+        rewrite
+            .rename_keys("table.rewrite_me", "rewritten")
+            .unwrap();
+        rewrite
+            .set_value("table.arr.0.rewrite_my_value", "0")
+            .unwrap();
+        rewrite
+            .set_value("table.arr.1.rewrite_my_value", "1")
+            .unwrap();
+
+        assert_rewrite(
+            include_str!("../../../../test-data/rewrite/multiple_expected.toml"),
+            &rewrite,
+        );
+    }
+
+    #[test]
+    fn rewrite_set_value() {
+        let mut rewrite =
+            rewrite_toml(include_str!("../../../../test-data/rewrite/set_value.toml"));
+
+        rewrite.set_value("package.version", r#""0.2.0""#).unwrap();
+        rewrite.set_value("package.edition", r#""2021""#).unwrap();
+        rewrite
+            .set_value("dependencies.serde.version", r#""1.0.100""#)
+            .unwrap();
+        rewrite
+            .set_value("dependencies.logos", r#""0.12""#)
+            .unwrap();
+        rewrite
+            .set_value(
+                "dev-dependencies.criterion",
+                r#"{ version = "0.3", default-features = false }"#,
+            )
+            .unwrap();
+
+        assert_rewrite(
+            include_str!("../../../../test-data/rewrite/set_value_expected.toml"),
+            &rewrite,
+        );
+
+        assert!(matches!(
+            rewrite.set_value("package.name", "not a value"),
+            Err(Error::InvalidValue)
+        ));
+        assert!(matches!(
+            rewrite.set_value("package.name", "1\n[table]"),
+            Err(Error::InvalidValue)
+        ));
+        assert!(matches!(
+            rewrite.set_value("dependencies.tokio-*", r#""1""#),
+            Err(Error::Dom(_))
+        ));
+
+        // Later patches are applied to the result of the earlier ones.
+        rewrite.set_value("package.version", r#""0.3.0""#).unwrap();
+        assert!(rewrite.apply().contains(r#"version = "0.3.0""#));
+        assert!(!rewrite.apply().contains(r#"version = "0.2.0""#));
+    }
+
+    #[test]
+    fn rewrite_set_value_same_table() {
+        let mut rewrite = rewrite_toml("a = 1\n");
+
+        rewrite.set_value("x.y", "1").unwrap();
+        rewrite.set_value("x.z", "2").unwrap();
+        rewrite.remove("a").unwrap();
+
+        assert_rewrite("[x]\ny = 1\nz = 2\n", &rewrite);
+        assert!(parse(&rewrite.apply()).into_dom().validate().is_ok());
+    }
+
+    #[test]
+    fn rewrite_remove() {
+        let mut rewrite = rewrite_toml(include_str!("../../../../test-data/rewrite/remove.toml"));
+
+        rewrite.remove("package.version").unwrap();
+        rewrite.remove("package.authors.1").unwrap();
+        rewrite.remove("features").unwrap();
+        rewrite.remove("dependencies.serde.optional").unwrap();
+        rewrite.remove("dependencies.old").unwrap();
+        rewrite.remove("does.not.exist").unwrap();
+
+        assert_rewrite(
+            include_str!("../../../../test-data/rewrite/remove_expected.toml"),
+            &rewrite,
+        );
+    }
+
+    #[test]
+    fn rewrite_insert() {
+        let mut rewrite = rewrite_toml(include_str!("../../../../test-data/rewrite/insert.toml"));
+
+        rewrite
+            .insert(
+                "package",
+                "description",
+                r#""A TOML toolkit""#,
+                Position::After("name".into()),
+            )
+            .unwrap();
+        rewrite
+            .insert("package", "edition", r#""2021""#, Position::Last)
+            .unwrap();
+        rewrite
+            .insert("dependencies", "ahash", r#""0.8""#, Position::First)
+            .unwrap();
+        rewrite
+            .insert(
+                "dependencies",
+                "rowan",
+                r#""0.15""#,
+                Position::Before("serde".into()),
+            )
+            .unwrap();
+
+        assert_rewrite(
+            include_str!("../../../../test-data/rewrite/insert_expected.toml"),
+            &rewrite,
+        );
+
+        assert!(matches!(
+            rewrite.insert("package", "name", r#""taplo""#, Position::Last),
+            Err(Error::KeyExists)
+        ));
+        assert!(matches!(
+            rewrite.insert("package.name", "key", "1", Position::Last),
+            Err(Error::ExpectedTable)
+        ));
+        assert!(matches!(
+            rewrite.insert("nothing", "key", "1", Position::Last),
+            Err(Error::Dom(_))
+        ));
+    }
+
+    #[test]
+    fn rewrite_move_entry() {
+        let mut rewrite = rewrite_toml(include_str!(
+            "../../../../test-data/rewrite/move_entry.toml"
+        ));
+
+        rewrite.move_entry("package.name", Position::First).unwrap();
+        rewrite.move_entry("dependencies", Position::Last).unwrap();
+
+        assert_rewrite(
+            include_str!("../../../../test-data/rewrite/move_entry_expected.toml"),
+            &rewrite,
+        );
+
+        let mut rewrite = rewrite_toml(include_str!(
+            "../../../../test-data/rewrite/move_entry.toml"
+        ));
+        assert!(matches!(
+            rewrite.move_entry("package.edition", Position::After("metadata".into())),
+            Err(Error::InvalidPosition)
+        ));
+    }
+
+    #[test]
+    fn rewrite_sort_entries() {
+        let mut rewrite = rewrite_toml(include_str!(
+            "../../../../test-data/rewrite/sort_entries.toml"
+        ));
+
+        rewrite.sort_entries("package").unwrap();
+        rewrite.sort_entries("dependencies").unwrap();
+
+        assert_rewrite(
+            include_str!("../../../../test-data/rewrite/sort_entries_expected.toml"),
+            &rewrite,
+        );
+
+        let mut rewrite = rewrite_toml(include_str!(
+            "../../../../test-data/rewrite/sort_entries.toml"
+        ));
+        rewrite.sort_entries("").unwrap();
+
+        assert_rewrite(
+            include_str!("../../../../test-data/rewrite/sort_entries_root_expected.toml"),
+            &rewrite,
+        );
+
+        // The sections of the package table are not next to each other.
+        let mut rewrite = rewrite_toml(include_str!(
+            "../../../../test-data/rewrite/move_entry.toml"
+        ));
+        assert!(matches!(
+            rewrite.sort_entries(""),
+            Err(Error::InvalidPosition)
+        ));
+    }
 }
@@ -174,7 +174,17 @@ impl Node {
                 IntegerRepr::Hex => write!(f, "{:#X}", i.value().as_positive().unwrap())?,
             },
             Node::Float(float) => {
-                write!(f, "{}", float.value())?;
+                let value = float.value();
+
+                if value.is_nan() {
+                    f.write_str("nan")?;
+                } else if value.is_infinite() {
+                    f.write_str(if value > 0.0 { "inf" } else { "-inf" })?;
+                } else {
+                    // Keep the value a float when read back, the debug
+                    // representation always has a fraction or an exponent.
+                    write!(f, "{value:?}")?;
+                }
             }
             Node::Date(d) => write!(f, "{}", d.value())?,
             Node::Invalid(_) => {}
@@ -0,0 +1,303 @@
+//! Line-based differences between an original and a formatted text.
+//!
+//! The changed lines are found with the Myers diff algorithm, the edits
+//! derived from them only span the characters that actually differ.
+
+use rowan::{TextRange, TextSize};
+use std::ops::Range;
+
+/// Documents with more changed lines than this are treated
+/// as a single change to keep the diff fast.
+const MAX_CHANGED_LINES: usize = 2000;
+
+/// The differences between the lines of two texts.
+#[derive(Debug, Clone)]
+pub struct Diff<'a> {
+    /// The lines of the original text including their line endings.
+    pub original: Vec<&'a str>,
+    /// The lines of the modified text including their line endings.
+    pub modified: Vec<&'a str>,
+    /// The changed lines ordered by their position,
+    /// the lines between them are equal.
+    pub changes: Vec<Change>,
+}
+
+/// Changes with the equal lines around them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    /// The lines of the original text in the hunk.
+    pub original: Range<usize>,
+    /// The lines of the modified text in the hunk.
+    pub modified: Range<usize>,
+    /// The changes in the hunk.
+    pub changes: Vec<Change>,
+}
+
+/// Lines of the original text that were replaced by lines of the modified one.
+///
+/// Either of the ranges is empty for inserted or removed lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub original: Range<usize>,
+    pub modified: Range<usize>,
+}
+
+impl<'a> Diff<'a> {
+    pub fn new(original: &'a str, modified: &'a str) -> Self {
+        let original: Vec<&str> = original.split_inclusive('\n').collect();
+        let modified: Vec<&str> = modified.split_inclusive('\n').collect();
+
+        let prefix = original
+            .iter()
+            .zip(&modified)
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix = original[prefix..]
+            .iter()
+            .rev()
+            .zip(modified[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        let a = &original[prefix..original.len() - suffix];
+        let b = &modified[prefix..modified.len() - suffix];
+
+        let changes = if a.is_empty() && b.is_empty() {
+            Vec::new()
+        } else {
+            myers(a, b).unwrap_or_else(|| {
+                Vec::from([Change {
+                    original: 0..a.len(),
+                    modified: 0..b.len(),
+                }])
+            })
+        };
+
+        let changes = changes
+            .into_iter()
+            .map(|c| Change {
+                original: c.original.start + prefix..c.original.end + prefix,
+                modified: c.modified.start + prefix..c.modified.end + prefix,
+            })
+            .collect();
+
+        Self {
+            original,
+            modified,
+            changes,
+        }
+    }
+
+    /// Whether the texts are the same.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// The changes grouped into hunks with at most `context`
+    /// equal lines before and after them.
+    ///
+    /// Changes that are closer than twice the context are in the same hunk.
+    pub fn hunks(&self, context: usize) -> Vec<Hunk> {
+        let mut hunks: Vec<Hunk> = Vec::new();
+
+        for change in &self.changes {
+            match hunks.last_mut() {
+                Some(hunk) if change.original.start - hunk.original.end <= context * 2 => {
+                    hunk.original.end = change.original.end;
+                    hunk.modified.end = change.modified.end;
+                    hunk.changes.push(change.clone());
+                }
+                _ => hunks.push(Hunk {
+                    original: change.original.clone(),
+                    modified: change.modified.clone(),
+                    changes: Vec::from([change.clone()]),
+                }),
+            }
+        }
+
+        for hunk in &mut hunks {
+            let before = hunk.original.start.min(context);
+            let after = (self.original.len() - hunk.original.end).min(context);
+            hunk.original = hunk.original.start - before..hunk.original.end + after;
+            hunk.modified = hunk.modified.start - before..hunk.modified.end + after;
+        }
+
+        hunks
+    }
+
+    /// Edits that turn the original text into the modified one,
+    /// ordered by their ranges in the original text.
+    ///
+    /// Lines that changed in place are edited separately and
+    /// only the characters that differ are replaced.
+    pub fn text_edits(&self) -> Vec<(TextRange, String)> {
+        let mut line_offsets = Vec::with_capacity(self.original.len() + 1);
+        let mut offset = 0;
+        line_offsets.push(offset);
+        for line in &self.original {
+            offset += line.len();
+            line_offsets.push(offset);
+        }
+
+        let mut edits = Vec::new();
+
+        for change in &self.changes {
+            if change.original.len() == change.modified.len() {
+                for (idx, line) in change.original.clone().zip(change.modified.clone()) {
+                    edits.extend(minimal_edit(
+                        line_offsets[idx],
+                        self.original[idx],
+                        self.modified[line],
+                    ));
+                }
+            } else {
+                let start = line_offsets[change.original.start];
+                let end = line_offsets[change.original.end];
+                let original: String = self.original[change.original.clone()].concat();
+                let modified: String = self.modified[change.modified.clone()].concat();
+                debug_assert_eq!(original.len(), end - start);
+                edits.extend(minimal_edit(start, &original, &modified));
+            }
+        }
+
+        edits
+    }
+}
+
+/// A single edit at the given offset that replaces only
+/// the part of the original text that differs.
+fn minimal_edit(offset: usize, original: &str, modified: &str) -> Option<(TextRange, String)> {
+    let mut prefix = original
+        .bytes()
+        .zip(modified.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !original.is_char_boundary(prefix) || !modified.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+
+    let mut suffix = original[prefix..]
+        .bytes()
+        .rev()
+        .zip(modified[prefix..].bytes().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !original.is_char_boundary(original.len() - suffix)
+        || !modified.is_char_boundary(modified.len() - suffix)
+    {
+        suffix -= 1;
+    }
+
+    let removed = prefix..original.len() - suffix;
+    let inserted = &modified[prefix..modified.len() - suffix];
+
+    if removed.is_empty() && inserted.is_empty() {
+        return None;
+    }
+
+    Some((
+        TextRange::new(
+            TextSize::from((offset + removed.start) as u32),
+            TextSize::from((offset + removed.end) as u32),
+        ),
+        inserted.to_string(),
+    ))
+}
+
+/// The shortest edit script between the lines, or [`None`]
+/// if there are too many changes.
+fn myers(a: &[&str], b: &[&str]) -> Option<Vec<Change>> {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let max = (a.len() + b.len()).min(MAX_CHANGED_LINES) as isize;
+
+    // Furthest reaching `x` of each diagonal `k = x - y`.
+    let offset = max + 1;
+    let mut v = vec![0_isize; (2 * offset + 1) as usize];
+    let idx = |k: isize| (k + offset) as usize;
+
+    // The diagonals of each step for finding the path backwards.
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    for d in 0..=max {
+        trace.push(v[idx(-d - 1)..=idx(d + 1)].to_vec());
+
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[idx(k - 1)] < v[idx(k + 1)]) {
+                v[idx(k + 1)]
+            } else {
+                v[idx(k - 1)] + 1
+            };
+            let mut y = x - k;
+
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+
+            v[idx(k)] = x;
+
+            if x >= n && y >= m {
+                return Some(backtrack(&trace, n, m));
+            }
+        }
+    }
+
+    None
+}
+
+fn backtrack(trace: &[Vec<isize>], n: isize, m: isize) -> Vec<Change> {
+    let (mut x, mut y) = (n, m);
+
+    // Single line insertions and removals from the end.
+    let mut moves: Vec<(isize, isize, bool)> = Vec::new();
+
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let get = |k: isize| v[(k + d + 1) as usize];
+
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && get(k - 1) < get(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+
+        let prev_x = get(prev_k);
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+        }
+
+        if d > 0 {
+            // Moving down the diagonals inserts a line, moving right removes one.
+            moves.push((prev_x, prev_y, x == prev_x));
+        }
+
+        x = prev_x;
+        y = prev_y;
+    }
+
+    let mut changes: Vec<Change> = Vec::new();
+
+    for (x, y, insertion) in moves.into_iter().rev() {
+        let (x, y) = (x as usize, y as usize);
+        let (original, modified) = if insertion {
+            (x..x, y..y + 1)
+        } else {
+            (x..x + 1, y..y)
+        };
+
+        match changes.last_mut() {
+            Some(last) if last.original.end == x && last.modified.end == y => {
+                last.original.end = original.end;
+                last.modified.end = modified.end;
+            }
+            _ => changes.push(Change { original, modified }),
+        }
+    }
+
+    changes
+}
@@ -0,0 +1,155 @@
+use crate::{
+    dom::{self, error::QueryError, KeyOrIndex, Keys},
+    HashMap,
+};
+use globset::{Glob, GlobMatcher};
+use std::{cmp::Ordering, convert::Infallible, str::FromStr};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+
+/// Preferred orders of the keys in tables, e.g. declared by a schema.
+///
+/// Keys without a preferred order come after the ordered ones
+/// in alphabetical order.
+#[derive(Debug, Clone, Default)]
+pub struct KeyOrder {
+    tables: HashMap<Vec<String>, Vec<String>>,
+}
+
+impl KeyOrder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the order of the keys in the table at the path.
+    ///
+    /// Paths do not contain array indices, so the order of an array
+    /// of tables applies to all of its tables.
+    pub fn insert(&mut self, table: Vec<String>, keys: Vec<String>) {
+        self.tables.insert(table, keys);
+    }
+
+    /// The order of the keys in the table at the path.
+    #[must_use]
+    pub fn get(&self, table: &[String]) -> Option<&[String]> {
+        self.tables.get(table).map(Vec::as_slice)
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// Compares the (dotted) keys of two entries in the table at the path.
+    pub(super) fn cmp_keys(&self, table: &[String], a: &[String], b: &[String]) -> Ordering {
+        self.sort_key(table, a).cmp(&self.sort_key(table, b))
+    }
+
+    /// The position of each key in the order of its table along with the key,
+    /// unordered keys are compared by the key alone.
+    pub(super) fn sort_key(&self, table: &[String], keys: &[String]) -> Vec<(usize, String)> {
+        let mut path = table.to_vec();
+        let mut sort_key = Vec::with_capacity(keys.len());
+
+        for key in keys {
+            let rank = self
+                .get(&path)
+                .and_then(|order| order.iter().position(|k| k == key))
+                .unwrap_or(usize::MAX);
+
+            sort_key.push((rank, key.clone()));
+            path.push(key.clone());
+        }
+
+        sort_key
+    }
+}
+
+/// Key patterns of tables in the order the tables are sorted in,
+/// e.g. `["package", "dependencies", "target.*.dependencies"]`.
+///
+/// Patterns of nested tables also order their parent tables, and tables
+/// that match none of the patterns come after the others in alphabetical order.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct TableOrder(pub Vec<String>);
+
+impl TableOrder {
+    /// Parses the patterns, each key of a pattern is a glob.
+    ///
+    /// Patterns that are not valid keys or globs are errors,
+    /// just like invalid keys of scoped options.
+    pub fn patterns(&self) -> Result<TablePatterns, dom::Error> {
+        self.0
+            .iter()
+            .map(|pattern| {
+                let keys: Keys = pattern.parse()?;
+
+                keys.iter()
+                    .map(|key| match key {
+                        KeyOrIndex::Key(key) => Glob::new(key.value())
+                            .map(|glob| Some(glob.compile_matcher()))
+                            .map_err(|error| QueryError::from(error).into()),
+                        // Tables are never matched by indices.
+                        KeyOrIndex::Index(_) => Ok(None),
+                    })
+                    .collect()
+            })
+            .collect::<Result<_, _>>()
+            .map(TablePatterns)
+    }
+}
+
+/// The parsed patterns of a [`TableOrder`].
+#[derive(Debug, Clone, Default)]
+pub struct TablePatterns(Vec<Vec<Option<GlobMatcher>>>);
+
+impl TablePatterns {
+    /// The position of the first pattern matching each parent table
+    /// and the table itself, along with their last keys.
+    pub(super) fn sort_key(&self, path: &[String]) -> Vec<(usize, String)> {
+        (1..=path.len())
+            .map(|len| {
+                let table = &path[..len];
+                let rank = self
+                    .0
+                    .iter()
+                    .position(|pattern| matches(pattern, table))
+                    .unwrap_or(usize::MAX);
+
+                (rank, table[len - 1].clone())
+            })
+            .collect()
+    }
+}
+
+/// Patterns are separated by commas, e.g. `package,dependencies`.
+impl FromStr for TableOrder {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(
+            s.split(',')
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(ToString::to_string)
+                .collect(),
+        ))
+    }
+}
+
+/// Whether the keys match the key pattern or the start of it.
+fn matches(pattern: &[Option<GlobMatcher>], keys: &[String]) -> bool {
+    pattern.len() >= keys.len()
+        && pattern
+            .iter()
+            .zip(keys)
+            .all(|(glob, key)| glob.as_ref().is_some_and(|glob| glob.is_match(key)))
+}
@@ -5,12 +5,15 @@
 
 use crate::{
     dom::{self, node::DomNode, FromSyntax, Keys, Node},
-    syntax::{SyntaxElement, SyntaxKind::*, SyntaxNode, SyntaxToken},
+    parser::{ParseOptions, TomlVersion},
+    syntax::{SyntaxElement, SyntaxKind, SyntaxKind::*, SyntaxNode, SyntaxToken},
     util::overlaps,
+    HashMap,
 };
 use itertools::Itertools;
+use logos::Logos;
 use once_cell::unsync::OnceCell;
-use rowan::{GreenNode, NodeOrToken, TextRange};
+use rowan::{GreenNode, NodeOrToken, TextRange, TextSize};
 use std::{
     cmp,
     collections::VecDeque,
@@ -28,6 +31,17 @@ use schemars::JsonSchema;
 #[macro_use]
 mod macros;
 
+pub mod diff;
+
+mod key_order;
+pub use key_order::{KeyOrder, TableOrder, TablePatterns};
+
+mod strings;
+pub use strings::{StringEscapes, StringQuotes, UnknownStringStyle};
+
+mod numbers;
+pub use numbers::{DateTimeSeparator, UnknownDateTimeSeparator};
+
 #[derive(Debug, Clone, Default)]
 /// Scoped formatter options based on text ranges.
 pub struct ScopedOptions(Vec<(TextRange, OptionsIncomplete)>);
@@ -70,6 +84,10 @@ create_options!(
         /// where possible.
         pub inline_table_expand: bool,
 
+        /// Put trailing commas for multiline inline tables,
+        /// only with TOML 1.1 which allows them.
+        pub inline_table_trailing_comma: bool,
+
         /// Automatically collapse arrays if they
         /// fit in one line.
         ///
@@ -114,11 +132,78 @@ create_options!(
         /// Alphabetically reorder inline table values.
         pub reorder_inline_tables: bool,
 
+        /// Reorder keys that are not separated by blank lines and tables
+        /// in the order declared by the schema of the document.
+        ///
+        /// Keys without a declared order are sorted alphabetically after the others.
+        pub reorder_by_schema: bool,
+
+        /// Reorder tables and arrays of tables alphabetically,
+        /// or in the order of the `table_order` patterns.
+        ///
+        /// Subtables stay right after their parent tables, arrays of tables keep
+        /// the order of their elements, and comments right above tables are moved
+        /// along with them. Tables are already reordered with `reorder_by_schema`.
+        pub reorder_tables: bool,
+
+        /// Key patterns of tables in the order they are sorted in with `reorder_tables`,
+        /// e.g. `["package", "dependencies", "target.*.dependencies"]`.
+        ///
+        /// Tables matching none of the patterns are sorted alphabetically after the others.
+        pub table_order: TableOrder,
+
+        /// The preferred quotes of string values, `"basic"` or `"literal"`.
+        ///
+        /// Strings are only converted if their values can be written
+        /// with the other quotes without any escapes.
+        pub string_quotes: StringQuotes,
+
+        /// Write multi-line strings without line breaks
+        /// in their values as single-line strings.
+        pub collapse_multiline_strings: bool,
+
+        /// Replace unicode escapes in basic strings with the characters (`"unicode"`),
+        /// or escape every non-ASCII character (`"ascii"`).
+        pub string_escapes: StringEscapes,
+
+        /// Remove the quotes of keys that are valid bare keys.
+        pub unquote_keys: bool,
+
+        /// The preferred quotes of keys that need quotes, `"basic"` or `"literal"`.
+        ///
+        /// Keys are only converted if they can be written
+        /// with the other quotes without any escapes.
+        pub key_quotes: StringQuotes,
+
+        /// Separate the digits of decimal integers and of the integer part of floats
+        /// in groups of three with `_` if there are more digits than this,
+        /// and remove the separators of shorter numbers.
+        ///
+        /// Separators are kept as they are if this is zero.
+        pub digit_separator_threshold: usize,
+
+        /// Write the digits of hexadecimal integers and the exponents of floats in lowercase.
+        pub lowercase_numbers: bool,
+
+        /// Remove the explicit `+` signs of numbers and exponents.
+        pub remove_plus_signs: bool,
+
+        /// The separator between the date and the time of date-times, `"T"` or `"space"`.
+        pub datetime_separator: DateTimeSeparator,
+
         /// The maximum amount of consecutive blank lines allowed.
         pub allowed_blank_lines: usize,
 
         /// Use CRLF line endings
         pub crlf: bool,
+
+        /// The TOML version of the formatted documents.
+        ///
+        /// Inline tables that span multiple lines are only kept
+        /// that way since TOML 1.1.
+        #[cfg_attr(feature = "serde", serde(skip))]
+        #[cfg_attr(feature = "schema", schemars(skip))]
+        pub toml_version: TomlVersion,
     }
 );
 
@@ -166,13 +251,27 @@ impl Default for Options {
             indent_tables: false,
             indent_entries: false,
             inline_table_expand: true,
+            inline_table_trailing_comma: true,
             trailing_newline: true,
             allowed_blank_lines: 2,
             indent_string: "  ".into(),
             reorder_keys: false,
             reorder_arrays: false,
             reorder_inline_tables: false,
+            reorder_by_schema: false,
+            reorder_tables: false,
+            table_order: TableOrder::default(),
+            string_quotes: StringQuotes::Preserve,
+            collapse_multiline_strings: false,
+            string_escapes: StringEscapes::Preserve,
+            unquote_keys: false,
+            key_quotes: StringQuotes::Preserve,
+            digit_separator_threshold: 0,
+            lowercase_numbers: false,
+            remove_plus_signs: false,
+            datetime_separator: DateTimeSeparator::Preserve,
             crlf: false,
+            toml_version: TomlVersion::V1_0,
         }
     }
 }
@@ -201,6 +300,11 @@ struct Context {
     force_multiline: bool,
     errors: Rc<[TextRange]>,
     scopes: Rc<ScopedOptions>,
+    /// Top-level syntax in these ranges is written as-is.
+    ignored: Rc<[TextRange]>,
+    key_order: Rc<KeyOrder>,
+    /// The keys of the table the formatted entries are in.
+    table_path: Rc<[String]>,
 }
 
 impl Default for Context {
@@ -210,6 +314,9 @@ impl Default for Context {
             force_multiline: Default::default(),
             errors: Rc::from([]),
             scopes: Default::default(),
+            ignored: Rc::from([]),
+            key_order: Default::default(),
+            table_path: Rc::from([]),
         }
     }
 }
@@ -246,7 +353,12 @@ pub fn format_green(green: GreenNode, options: Options) -> String {
 
 /// Parses then formats a TOML document, skipping ranges that contain syntax errors.
 pub fn format(src: &str, options: Options) -> String {
-    let p = crate::parser::parse(src);
+    let p = crate::parser::parse_with(
+        src,
+        ParseOptions {
+            toml_version: options.toml_version,
+        },
+    );
 
     let ctx = Context {
         errors: p.errors.iter().map(|err| err.range).collect(),
@@ -312,23 +424,34 @@ where
     I: IntoIterator<Item = (S, OptionsIncomplete)>,
     S: AsRef<str>,
 {
-    let mut c = Context {
-        errors: errors.into(),
-        ..Context::default()
-    };
-
-    let mut s = Vec::new();
-
-    for (scope, opts) in scopes {
-        let keys: Keys = scope.as_ref().parse()?;
-        let matched = dom.find_all_matches(keys, false)?;
+    format_with_key_order(dom, options, errors, scopes, KeyOrder::default())
+}
 
-        for (_, node) in matched {
-            s.extend(node.text_ranges(true).map(|r| (r, opts.clone())));
-        }
+/// Formats a DOM root node with given scopes, keys and tables
+/// are reordered by the key order if `reorder_by_schema` is enabled.
+///
+/// **This doesn't check errors of the DOM.**
+pub fn format_with_key_order<I, S>(
+    dom: Node,
+    options: Options,
+    errors: &[TextRange],
+    scopes: I,
+    key_order: KeyOrder,
+) -> Result<String, dom::Error>
+where
+    I: IntoIterator<Item = (S, OptionsIncomplete)>,
+    S: AsRef<str>,
+{
+    if options.reorder_tables {
+        options.table_order.patterns()?;
     }
 
-    c.scopes = Rc::new(ScopedOptions::from_iter(s));
+    let c = Context {
+        errors: errors.into(),
+        scopes: Rc::new(path_scopes(&dom, scopes)?),
+        key_order: Rc::new(key_order),
+        ..Context::default()
+    };
 
     let mut s = format_impl(
         dom.syntax().unwrap().clone().into_node().unwrap(),
@@ -345,10 +468,86 @@ where
     Ok(s)
 }
 
+/// Formats the entries, tables and comments of a DOM root node
+/// that overlap the given range, everything else is left as-is.
+///
+/// The result is the edits of the original text ordered by their ranges,
+/// it is empty if the range is already formatted.
+///
+/// **This doesn't check errors of the DOM.**
+pub fn format_range<I, S>(
+    dom: Node,
+    range: TextRange,
+    options: Options,
+    errors: &[TextRange],
+    scopes: I,
+) -> Result<Vec<(TextRange, String)>, dom::Error>
+where
+    I: IntoIterator<Item = (S, OptionsIncomplete)>,
+    S: AsRef<str>,
+{
+    let node = dom.syntax().unwrap().clone().into_node().unwrap();
+    let ignored = outside_range(&node, range);
+    let until_end = !ignored.iter().any(|r| r.end() == node.text_range().end());
+
+    let c = Context {
+        errors: errors.into(),
+        scopes: Rc::new(path_scopes(&dom, scopes)?),
+        ignored: ignored.into(),
+        ..Context::default()
+    };
+
+    let original = node.to_string();
+    let mut formatted = format_root(node, &options, &c);
+
+    if until_end {
+        formatted = formatted.trim_end().into();
+        if options.trailing_newline {
+            formatted += options.newline();
+        }
+    }
+
+    Ok(diff::Diff::new(&original, &formatted).text_edits())
+}
+
+/// Scoped options for the text ranges of the nodes matching the key patterns.
+fn path_scopes<I, S>(dom: &Node, scopes: I) -> Result<ScopedOptions, dom::Error>
+where
+    I: IntoIterator<Item = (S, OptionsIncomplete)>,
+    S: AsRef<str>,
+{
+    let mut s = Vec::new();
+
+    for (scope, opts) in scopes {
+        let keys: Keys = scope.as_ref().parse()?;
+        let matched = dom.find_all_matches(keys, false)?;
+
+        for (_, node) in matched {
+            s.extend(node.text_ranges(true).map(|r| (r, opts.clone())));
+        }
+    }
+
+    Ok(ScopedOptions::from_iter(s))
+}
+
 fn format_impl(node: SyntaxNode, options: Options, context: Context) -> String {
     assert!(node.kind() == ROOT);
     let mut formatted = format_root(node, &options, &context);
 
+    if context.errors.is_empty() && context.ignored.is_empty() {
+        if options.reorder_by_schema {
+            formatted = reorder_tables(&formatted, &options, |path| {
+                context.key_order.sort_key(&[], path)
+            });
+        } else if options.reorder_tables {
+            // Invalid patterns are reported before formatting where errors can be returned,
+            // otherwise the tables are not reordered.
+            if let Ok(patterns) = options.table_order.patterns() {
+                formatted = reorder_tables(&formatted, &options, |path| patterns.sort_key(path));
+            }
+        }
+    }
+
     if formatted.ends_with("\r\n") {
         formatted.truncate(formatted.len() - 2);
     } else if formatted.ends_with('\n') {
@@ -418,10 +617,6 @@ impl FormattedItem for FormattedEntry {
     fn trailing_comment(&self) -> Option<String> {
         self.comment.clone()
     }
-
-    fn syntax(&self) -> SyntaxElement {
-        self.syntax.clone()
-    }
 }
 
 fn format_root(node: SyntaxNode, options: &Options, context: &Context) -> String {
@@ -466,6 +661,13 @@ fn format_root(node: SyntaxNode, options: &Options, context: &Context) -> String
     let mut dangling_newline_count = 0;
     let mut scoped_options = options.clone();
 
+    let ignored = merge_ranges(
+        ignored_regions(&node)
+            .into_iter()
+            .chain(context.ignored.iter().copied()),
+    );
+    let mut ignored_end = TextSize::from(0);
+
     for c in node.children_with_tokens() {
         if context.error_at(c.text_range()) {
             formatted += &c.to_string();
@@ -474,6 +676,48 @@ fn format_root(node: SyntaxNode, options: &Options, context: &Context) -> String
 
         let c_range = c.text_range();
 
+        // Regions that are not formatted are written as-is when they start,
+        // only the tables in them are tracked for the entries that come after.
+        let ignored_region = if c_range.start() < ignored_end {
+            true
+        } else if let Some(region) = ignored.iter().find(|r| r.contains(c_range.start())) {
+            if add_entries(&mut entry_group, &mut formatted, &scoped_options, &context) {
+                formatted += scoped_options.newline();
+            }
+
+            if add_comments(
+                &mut comment_group,
+                &mut formatted,
+                &context,
+                &scoped_options,
+            ) {
+                formatted += scoped_options.newline();
+            }
+
+            formatted += &node.text().slice(*region).to_string();
+            skip_newlines = 0;
+            ignored_end = region.end();
+            true
+        } else {
+            false
+        };
+
+        if ignored_region {
+            if let Some(header) = c
+                .as_node()
+                .filter(|n| matches!(n.kind(), TABLE_HEADER | TABLE_ARRAY_HEADER))
+            {
+                enter_table(
+                    header,
+                    options,
+                    &mut scoped_options,
+                    &mut context,
+                    &mut table_key_indent_history,
+                );
+            }
+            continue;
+        }
+
         match c {
             NodeOrToken::Node(node) => match node.kind() {
                 TABLE_ARRAY_HEADER | TABLE_HEADER => {
@@ -482,24 +726,13 @@ fn format_root(node: SyntaxNode, options: &Options, context: &Context) -> String
                         skip_newlines = 0;
                     }
 
-                    scoped_options = options.clone();
-                    context.update_options(&mut scoped_options, c_range);
-
-                    // We treat everything as indented other than table headers from now on.
-                    if scoped_options.indent_entries && context.indent_level == 0 {
-                        context.indent_level = 1;
-                    }
-
-                    if let Some(key) = node.first_child().map(Into::into).map(Keys::from_syntax) {
-                        if scoped_options.indent_tables {
-                            context.indent_level = table_indent_level(
-                                &table_key_indent_history,
-                                &key,
-                                if scoped_options.indent_entries { 1 } else { 0 },
-                            );
-                        }
-                        table_key_indent_history.push((key.clone(), context.indent_level));
-                    }
+                    enter_table(
+                        &node,
+                        options,
+                        &mut scoped_options,
+                        &mut context,
+                        &mut table_key_indent_history,
+                    );
 
                     let mut header_context = context.clone();
 
@@ -604,6 +837,359 @@ fn format_root(node: SyntaxNode, options: &Options, context: &Context) -> String
     formatted
 }
 
+/// Update the options and the indentation for the entries of a table.
+fn enter_table(
+    header: &SyntaxNode,
+    options: &Options,
+    scoped_options: &mut Options,
+    context: &mut Context,
+    table_key_indent_history: &mut Vec<(Keys, usize)>,
+) {
+    *scoped_options = options.clone();
+    context.update_options(scoped_options, header.text_range());
+
+    // We treat everything as indented other than table headers from now on.
+    if scoped_options.indent_entries && context.indent_level == 0 {
+        context.indent_level = 1;
+    }
+
+    if let Some(key) = header.first_child().map(Into::into).map(Keys::from_syntax) {
+        context.table_path = key_path(&key).into();
+
+        if scoped_options.indent_tables {
+            context.indent_level = table_indent_level(
+                table_key_indent_history,
+                &key,
+                if scoped_options.indent_entries { 1 } else { 0 },
+            );
+        }
+        table_key_indent_history.push((key.clone(), context.indent_level));
+    }
+}
+
+/// Formatter directives in comments, e.g. `# taplo:fmt off` or `#:fmt off`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Directive {
+    /// Stop formatting until the next `on` directive.
+    Off,
+    /// Continue formatting after an `off` directive.
+    On,
+    /// Skip the next entry, or table if it is followed by a table header.
+    Skip,
+}
+
+impl Directive {
+    fn from_comment(token: &SyntaxToken) -> Option<Self> {
+        let comment = dom::Comment::from_syntax(token.clone().into());
+
+        if comment.directive()? != "fmt" {
+            return None;
+        }
+
+        match comment.value() {
+            "off" => Some(Self::Off),
+            "on" => Some(Self::On),
+            "skip" => Some(Self::Skip),
+            _ => None,
+        }
+    }
+}
+
+/// Whether the node contains a directive, e.g. in an array or a trailing comment.
+fn has_directive(node: &SyntaxNode) -> bool {
+    node.descendants_with_tokens().any(|c| {
+        c.as_token()
+            .filter(|t| t.kind() == COMMENT)
+            .and_then(Directive::from_comment)
+            .is_some()
+    })
+}
+
+/// The ranges of the document that are excluded from formatting by directives.
+///
+/// The ranges start at the beginning of a line so that they can be written
+/// as-is along with their indentation. Directives in arrays and inline tables,
+/// or after table headers apply to the whole entry or table.
+fn ignored_regions(root: &SyntaxNode) -> Vec<TextRange> {
+    let text = root.to_string();
+    let line_start = |offset: TextSize| {
+        let offset = usize::from(offset) - usize::from(root.text_range().start());
+        let start = text[..offset].rfind('\n').map_or(0, |idx| idx + 1);
+        root.text_range().start() + TextSize::from(start as u32)
+    };
+
+    let mut regions: Vec<TextRange> = Vec::new();
+
+    let mut off: Option<TextSize> = None;
+    let mut skip: Option<TextSize> = None;
+    // A table that is skipped along with all its entries.
+    let mut table: Option<TextRange> = None;
+    let mut last_end = root.text_range().start();
+
+    for c in root.children_with_tokens() {
+        let range = c.text_range();
+
+        match &c {
+            NodeOrToken::Token(t) if t.kind() == COMMENT => match Directive::from_comment(t) {
+                Some(Directive::Off) if off.is_none() => off = Some(line_start(range.start())),
+                Some(Directive::On) => {
+                    if let Some(start) = off.take() {
+                        regions.push(TextRange::new(start, range.end()));
+                    }
+                }
+                Some(Directive::Skip) if skip.is_none() => {
+                    skip = Some(line_start(range.start()));
+                }
+                _ => {}
+            },
+            NodeOrToken::Node(n) if n.kind() == ENTRY => {
+                if let Some(table) = &mut table {
+                    *table = table.cover(range);
+                } else if let Some(start) = skip.take() {
+                    regions.push(TextRange::new(start, range.end()));
+                } else if has_directive(n) {
+                    regions.push(TextRange::new(line_start(range.start()), range.end()));
+                }
+            }
+            NodeOrToken::Node(n) => {
+                regions.extend(table.take());
+
+                let start = skip
+                    .take()
+                    .or_else(|| has_directive(n).then(|| line_start(range.start())));
+
+                if let Some(start) = start {
+                    table = Some(TextRange::new(start, range.end()));
+                }
+            }
+            _ => {}
+        }
+
+        if !matches!(c.kind(), WHITESPACE | NEWLINE) {
+            last_end = range.end();
+        }
+    }
+
+    regions.extend(table);
+
+    if let Some(start) = off.or(skip) {
+        regions.push(TextRange::new(start, last_end));
+    }
+
+    merge_ranges(regions)
+}
+
+/// The ranges of the document before and after the top-level
+/// syntax that overlaps the given range.
+fn outside_range(root: &SyntaxNode, range: TextRange) -> Vec<TextRange> {
+    let root_range = root.text_range();
+
+    let elements: Vec<TextRange> = root
+        .children_with_tokens()
+        .filter(|c| !matches!(c.kind(), WHITESPACE | NEWLINE))
+        .map(|c| c.text_range())
+        .collect();
+
+    let overlapping = elements
+        .iter()
+        .copied()
+        .filter(|r| {
+            if range.is_empty() {
+                r.contains_inclusive(range.start())
+            } else {
+                r.start() < range.end() && range.start() < r.end()
+            }
+        })
+        .reduce(|a, b| a.cover(b));
+
+    let formatted = match overlapping {
+        Some(r) => r,
+        None => return Vec::from([root_range]),
+    };
+
+    // The ranges start at the beginning of a line so that the
+    // formatted syntax can be indented and followed by blank lines.
+    let text = root.to_string();
+    let line_start = |offset: TextSize| {
+        let offset = usize::from(offset) - usize::from(root_range.start());
+        let start = text[..offset].rfind('\n').map_or(0, |idx| idx + 1);
+        root_range.start() + TextSize::from(start as u32)
+    };
+
+    let mut ranges = Vec::with_capacity(2);
+
+    let start = line_start(formatted.start());
+    if start > root_range.start() {
+        ranges.push(TextRange::new(root_range.start(), start));
+    }
+
+    if let Some(next) = elements.iter().find(|r| r.start() >= formatted.end()) {
+        ranges.push(TextRange::new(line_start(next.start()), root_range.end()));
+    }
+
+    ranges
+}
+
+/// Reorders the tables of a formatted document by the sort keys of their keys.
+///
+/// Tables are moved along with the comments right above them,
+/// and the tables inside arrays of tables stay with the array elements
+/// they belong to. The blank lines between tables are kept in place.
+///
+/// The sort keys must start with the sort keys of the parent tables
+/// so that subtables stay after them.
+fn reorder_tables<K: Ord>(
+    src: &str,
+    options: &Options,
+    sort_key: impl Fn(&[String]) -> K,
+) -> String {
+    let root = crate::parser::parse_with(
+        src,
+        ParseOptions {
+            toml_version: options.toml_version,
+        },
+    );
+
+    if !root.errors.is_empty() {
+        return src.into();
+    }
+
+    let root = root.into_syntax();
+
+    if !ignored_regions(&root).is_empty() {
+        return src.into();
+    }
+
+    let line_start = |offset: TextSize| {
+        let offset = usize::from(offset);
+        src[..offset].rfind('\n').map_or(0, |idx| idx + 1)
+    };
+
+    // The keys of the sections, each made of the tables in the pieces of the source
+    // that start at the offsets along with the index of their section.
+    let mut sections: Vec<Vec<String>> = Vec::new();
+    let mut pieces: Vec<(usize, usize)> = Vec::new();
+    // The section of the last element of each array of tables.
+    let mut array_sections: HashMap<Vec<String>, usize> = HashMap::new();
+    let mut comment_start: Option<TextSize> = None;
+
+    for c in root.children_with_tokens() {
+        match c.kind() {
+            // Directives such as `#:schema` stay where they are.
+            COMMENT if c.to_string().starts_with("#:") => comment_start = None,
+            COMMENT => {
+                comment_start.get_or_insert(c.text_range().start());
+            }
+            NEWLINE if c.as_token().is_some_and(|t| t.text().newline_count() > 1) => {
+                comment_start = None;
+            }
+            ENTRY => comment_start = None,
+            TABLE_HEADER | TABLE_ARRAY_HEADER => {
+                let start = comment_start.take().unwrap_or(c.text_range().start());
+
+                let Some(keys) = c
+                    .as_node()
+                    .and_then(SyntaxNode::first_child)
+                    .map(|k| Keys::from_syntax(k.into()))
+                else {
+                    return src.into();
+                };
+                let path = key_path(&keys);
+
+                // Tables inside arrays of tables belong to the last element
+                // of the innermost array, even if other tables came in between.
+                let array_section = (1..path.len())
+                    .rev()
+                    .find_map(|len| array_sections.get(&path[..len]).copied());
+
+                let section = match array_section {
+                    Some(section) => section,
+                    None => {
+                        sections.push(path.clone());
+                        sections.len() - 1
+                    }
+                };
+
+                if c.kind() == TABLE_ARRAY_HEADER {
+                    array_sections.insert(path, section);
+                }
+
+                if pieces.last().map(|(_, s)| *s) != Some(section) {
+                    pieces.push((line_start(start), section));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if sections.len() < 2 {
+        return src.into();
+    }
+
+    let ends = pieces
+        .iter()
+        .skip(1)
+        .map(|(start, _)| *start)
+        .chain([src.len()]);
+
+    // The text of the pieces by section and the whitespace after each piece.
+    let mut section_texts: Vec<Vec<&str>> = vec![Vec::new(); sections.len()];
+    let mut separators: Vec<&str> = Vec::with_capacity(pieces.len());
+
+    for ((start, section), end) in pieces.iter().zip(ends) {
+        let text = &src[*start..end];
+        let content = text.trim_end();
+        section_texts[*section].push(content);
+        separators.push(&text[content.len()..]);
+    }
+
+    let mut order: Vec<usize> = (0..sections.len()).collect();
+
+    // The sort is stable, so arrays of tables keep the order of their elements.
+    order.sort_by_cached_key(|idx| sort_key(&sections[*idx]));
+
+    let mut reordered = String::from(&src[..pieces[0].0]);
+
+    let contents = order
+        .into_iter()
+        .flat_map(|idx| section_texts[idx].iter().copied());
+
+    for (content, separator) in contents.zip(separators) {
+        reordered += content;
+        // The last table might not have been followed by a newline.
+        if separator.is_empty() {
+            reordered += options.newline();
+        } else {
+            reordered += separator;
+        }
+    }
+
+    reordered
+}
+
+/// The keys of a table header without array indices.
+fn key_path(keys: &Keys) -> Vec<String> {
+    keys.iter()
+        .filter_map(|k| k.as_key())
+        .map(|k| k.value().to_string())
+        .collect()
+}
+
+fn merge_ranges(ranges: impl IntoIterator<Item = TextRange>) -> Vec<TextRange> {
+    let mut ranges: Vec<TextRange> = ranges.into_iter().collect();
+    ranges.sort_by_key(|r| r.start());
+
+    let mut merged: Vec<TextRange> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start() <= last.end() => *last = last.cover(range),
+            _ => merged.push(range),
+        }
+    }
+
+    merged
+}
+
 /// Determine the indentation level using the indentation history.
 ///
 /// The latest key that is a strict prefix is used and indented. If none is found, the default
@@ -632,7 +1218,13 @@ fn add_entries(
 ) -> bool {
     let were_entries = !entry_group.is_empty();
 
-    if options.reorder_keys {
+    if options.reorder_by_schema {
+        entry_group.sort_by(|a, b| {
+            context
+                .key_order
+                .cmp_keys(&context.table_path, a.cleaned_key(), b.cleaned_key())
+        });
+    } else if options.reorder_keys {
         entry_group.sort();
     }
 
@@ -786,7 +1378,10 @@ fn format_entry(node: SyntaxNode, options: &Options, context: &Context) -> Forma
     }
 }
 
-fn format_key(node: SyntaxNode, formatted: &mut String, _options: &Options, _context: &Context) {
+fn format_key(node: SyntaxNode, formatted: &mut String, options: &Options, context: &Context) {
+    let mut scoped_options = options.clone();
+    context.update_options(&mut scoped_options, node.text_range());
+
     // Idents and periods without whitespace
     for c in node.children_with_tokens() {
         match c {
@@ -794,7 +1389,14 @@ fn format_key(node: SyntaxNode, formatted: &mut String, _options: &Options, _con
             NodeOrToken::Token(t) => match t.kind() {
                 WHITESPACE | NEWLINE => {}
                 _ => {
-                    *formatted += t.text();
+                    match strings::format_key(
+                        t.text(),
+                        scoped_options.key_quotes,
+                        scoped_options.unquote_keys,
+                    ) {
+                        Some(key) => *formatted += &key,
+                        None => *formatted += t.text(),
+                    }
                 }
             },
         }
@@ -846,6 +1448,29 @@ fn format_value(node: SyntaxNode, options: &Options, context: &Context) -> impl
                     debug_assert!(comment.is_none());
                     comment = Some(t.text().into());
                 }
+                kind
+                @ (STRING | MULTI_LINE_STRING | STRING_LITERAL | MULTI_LINE_STRING_LITERAL) => {
+                    value = strings::format_string(
+                        kind,
+                        t.text(),
+                        scoped_options.string_quotes,
+                        scoped_options.string_escapes,
+                        scoped_options.collapse_multiline_strings,
+                    )
+                    .unwrap_or_else(|| t.text().into());
+                }
+                kind @ (INTEGER | INTEGER_HEX | INTEGER_OCT | INTEGER_BIN | FLOAT) => {
+                    value = numbers::format_number(kind, t.text(), &scoped_options)
+                        .unwrap_or_else(|| t.text().into());
+                }
+                kind @ (DATE_TIME_OFFSET | DATE_TIME_LOCAL) => {
+                    value = numbers::format_date_time(
+                        kind,
+                        t.text(),
+                        scoped_options.datetime_separator,
+                    )
+                    .unwrap_or_else(|| t.text().into());
+                }
                 _ => {
                     value = t.text().into();
                 }
@@ -856,11 +1481,25 @@ fn format_value(node: SyntaxNode, options: &Options, context: &Context) -> impl
     (node.into(), value, comment)
 }
 
+// Inline tables can only be collapsed if they contain no comments,
+// and are kept on multiple lines since TOML 1.1 if they already were.
+fn is_inline_table_multiline(node: &SyntaxNode, options: &Options) -> bool {
+    node.children().count() != 0
+        && node.children_with_tokens().any(|c| {
+            c.kind() == COMMENT
+                || (c.kind() == NEWLINE && options.toml_version >= TomlVersion::V1_1)
+        })
+}
+
 fn format_inline_table(
     node: SyntaxNode,
     options: &Options,
     context: &Context,
-) -> impl FormattedItem {
+) -> (SyntaxElement, String, Option<String>) {
+    if is_inline_table_multiline(&node, options) {
+        return format_inline_table_multiline(node, options, context);
+    }
+
     let mut formatted = String::new();
     let mut comment = None;
 
@@ -932,7 +1571,7 @@ fn format_inline_table(
                     }
                     formatted += "}";
                 }
-                WHITESPACE | COMMA => {}
+                WHITESPACE | NEWLINE | COMMA => {}
                 COMMENT => {
                     debug_assert!(comment.is_none());
                     comment = Some(t.text().into());
@@ -944,6 +1583,119 @@ fn format_inline_table(
 
     (node.into(), formatted, comment)
 }
+
+fn format_inline_table_multiline(
+    node: SyntaxNode,
+    options: &Options,
+    context: &Context,
+) -> (SyntaxElement, String, Option<String>) {
+    let mut formatted = String::new();
+
+    let mut inner_context = context.clone();
+    inner_context.indent_level += 1;
+    if inner_context.force_multiline {
+        inner_context.force_multiline = options.inline_table_expand;
+    }
+
+    let entry_count = node.children().count();
+
+    // Indented entries and their optional trailing comments, separated by standalone comments.
+    let mut rows: Vec<Vec<String>> = Vec::new();
+
+    let add_rows = |rows: &mut Vec<Vec<String>>, formatted: &mut String| {
+        if rows.is_empty() {
+            return;
+        }
+
+        let comment_count = rows.iter().filter(|row| row.len() > 2).count();
+        let align_comments = options.should_align_comments(comment_count);
+
+        *formatted += &format_rows(
+            if align_comments { 0..usize::MAX } else { 0..0 },
+            1..usize::MAX,
+            rows,
+            options.newline(),
+            " ",
+        );
+        *formatted += options.newline();
+        rows.clear();
+    };
+
+    let mut entry_index = 0;
+    for c in node.children_with_tokens() {
+        match c {
+            NodeOrToken::Node(n) => {
+                if formatted.ends_with('{') {
+                    formatted += options.newline();
+                }
+
+                let entry = format_entry(n, options, &inner_context);
+                debug_assert!(entry.comment.is_none());
+
+                let mut entry_string = String::new();
+                entry.write_to(&mut entry_string, options);
+
+                entry_index += 1;
+                if entry_index < entry_count
+                    || (options.inline_table_trailing_comma
+                        && options.toml_version >= TomlVersion::V1_1)
+                {
+                    entry_string += ",";
+                }
+
+                rows.push(vec![
+                    inner_context.indent(options).collect::<String>(),
+                    entry_string,
+                ]);
+            }
+            NodeOrToken::Token(t) => match t.kind() {
+                BRACE_START => {
+                    formatted += "{";
+                }
+                BRACE_END => {
+                    add_rows(&mut rows, &mut formatted);
+                    formatted.extend(context.indent(options));
+                    formatted += "}";
+                }
+                COMMENT => {
+                    let prev = t
+                        .siblings_with_tokens(rowan::Direction::Prev)
+                        .skip(1)
+                        .find(|s| s.kind() != WHITESPACE)
+                        .map(|s| s.kind());
+
+                    match prev {
+                        Some(BRACE_START) => {
+                            formatted += " ";
+                            formatted += t.text();
+                            formatted += options.newline();
+                        }
+                        Some(NEWLINE) | None => {
+                            add_rows(&mut rows, &mut formatted);
+                            if formatted.ends_with('{') {
+                                formatted += options.newline();
+                            }
+                            formatted.extend(inner_context.indent(options));
+                            formatted += t.text();
+                            formatted += options.newline();
+                        }
+                        Some(_) => match rows.last_mut() {
+                            Some(row) => row.push(t.text().into()),
+                            None => {
+                                formatted.extend(inner_context.indent(options));
+                                formatted += t.text();
+                                formatted += options.newline();
+                            }
+                        },
+                    }
+                }
+                _ => {}
+            },
+        }
+    }
+
+    (node.into(), formatted, None)
+}
 // Check whether the array spans multiple lines in its current form.
 fn is_array_multiline(node: &SyntaxNode) -> bool {
     node.descendants_with_tokens().any(|n| n.kind() == NEWLINE)
@@ -1206,14 +1958,9 @@ impl<T: AsRef<str>> FormattedItem for (SyntaxElement, T, Option<T>) {
     fn trailing_comment(&self) -> Option<String> {
         self.2.as_ref().map(|s| s.as_ref().to_string())
     }
-
-    fn syntax(&self) -> SyntaxElement {
-        self.0.clone()
-    }
 }
 
 trait FormattedItem {
-    fn syntax(&self) -> SyntaxElement;
     #[allow(clippy::ptr_arg)]
     fn write_to(&self, formatted: &mut String, options: &Options);
     fn trailing_comment(&self) -> Option<String>;
@@ -1332,3 +2079,10 @@ fn dangling_newlines(t: SyntaxToken) -> Option<usize> {
 
     None
 }
+
+/// Whether the text is lexed as exactly one token of the given kind,
+/// formatted tokens must never change the structure of the document.
+fn is_single_token(kind: SyntaxKind, text: &str) -> bool {
+    let mut lexer = SyntaxKind::lexer(text);
+    lexer.next() == Some(kind) && lexer.next().is_none()
+}
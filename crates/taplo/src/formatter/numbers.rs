@@ -0,0 +1,194 @@
+use super::{is_single_token, Options};
+use crate::syntax::SyntaxKind::{self, *};
+use std::str::FromStr;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+
+/// The separator between the date and the time of date-times.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum DateTimeSeparator {
+    /// Keep the separator of every date-time.
+    #[default]
+    #[cfg_attr(feature = "serde", serde(rename = "preserve"))]
+    Preserve,
+    /// An uppercase `T`, e.g. `1979-05-27T07:32:00`.
+    #[cfg_attr(feature = "serde", serde(rename = "T"))]
+    T,
+    /// A space, e.g. `1979-05-27 07:32:00`.
+    #[cfg_attr(feature = "serde", serde(rename = "space"))]
+    Space,
+}
+
+impl FromStr for DateTimeSeparator {
+    type Err = UnknownDateTimeSeparator;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "preserve" => Ok(DateTimeSeparator::Preserve),
+            "T" | "t" => Ok(DateTimeSeparator::T),
+            "space" | " " => Ok(DateTimeSeparator::Space),
+            _ => Err(UnknownDateTimeSeparator(s.into())),
+        }
+    }
+}
+
+/// An unknown date-time separator.
+#[derive(Debug, Clone, Eq, PartialEq, thiserror::Error)]
+#[error(r#"unknown date-time separator "{0}", expected "preserve", "T" or "space""#)]
+pub struct UnknownDateTimeSeparator(pub String);
+
+/// Writes the integer or float token with the configured digit separators,
+/// markers and signs, the value of the number is never changed.
+///
+/// Returns `None` if the number is kept as it is.
+pub(super) fn format_number(kind: SyntaxKind, text: &str, options: &Options) -> Option<String> {
+    let formatted = match kind {
+        INTEGER => {
+            let (sign, digits) = split_sign(text, options);
+            format!("{sign}{}", group_digits(digits, options))
+        }
+        INTEGER_HEX | INTEGER_OCT | INTEGER_BIN if options.lowercase_numbers => {
+            text.to_ascii_lowercase()
+        }
+        FLOAT => format_float(text, options),
+        _ => return None,
+    };
+
+    if formatted == text || !is_single_token(kind, &formatted) {
+        return None;
+    }
+
+    // Should not happen, but the value must be the same in any case.
+    let same_value = match kind {
+        FLOAT => match (float_value(text), float_value(&formatted)) {
+            (Some(a), Some(b)) => a.to_bits() == b.to_bits() || (a.is_nan() && b.is_nan()),
+            _ => false,
+        },
+        _ => integer_value(kind, text).is_some_and(|v| integer_value(kind, &formatted) == Some(v)),
+    };
+
+    same_value.then_some(formatted)
+}
+
+/// Writes the date-time token with the configured separator between
+/// the date and the time.
+///
+/// Returns `None` if the date-time is kept as it is.
+pub(super) fn format_date_time(
+    kind: SyntaxKind,
+    text: &str,
+    separator: DateTimeSeparator,
+) -> Option<String> {
+    let separator = match separator {
+        DateTimeSeparator::Preserve => return None,
+        DateTimeSeparator::T => "T",
+        DateTimeSeparator::Space => " ",
+    };
+
+    if !matches!(kind, DATE_TIME_OFFSET | DATE_TIME_LOCAL) {
+        return None;
+    }
+
+    // Dates are always written as `YYYY-MM-DD`.
+    let (date, time) = (text.get(..10)?, text.get(11..)?);
+    let formatted = format!("{date}{separator}{time}");
+
+    (formatted != text && is_single_token(kind, &formatted)).then_some(formatted)
+}
+
+fn format_float(text: &str, options: &Options) -> String {
+    let (sign, number) = split_sign(text, options);
+
+    // `inf` and `nan` have no digits.
+    if !number.starts_with(|c: char| c.is_ascii_digit()) {
+        return format!("{sign}{number}");
+    }
+
+    let (mantissa, exponent) = match number.find(['e', 'E']) {
+        Some(idx) => number.split_at(idx),
+        None => (number, ""),
+    };
+
+    let (int, fraction) = match mantissa.find('.') {
+        Some(idx) => mantissa.split_at(idx),
+        None => (mantissa, ""),
+    };
+
+    let mut formatted = format!("{sign}{}{fraction}", group_digits(int, options));
+
+    if !exponent.is_empty() {
+        let (marker, exponent) = exponent.split_at(1);
+
+        formatted.push_str(if options.lowercase_numbers {
+            "e"
+        } else {
+            marker
+        });
+        formatted.push_str(match exponent.strip_prefix('+') {
+            Some(unsigned) if options.remove_plus_signs => unsigned,
+            _ => exponent,
+        });
+    }
+
+    formatted
+}
+
+/// The sign of the number, without an explicit `+` if those are removed.
+fn split_sign<'t>(text: &'t str, options: &Options) -> (&'t str, &'t str) {
+    match text.strip_prefix('+') {
+        Some(number) if options.remove_plus_signs => ("", number),
+        Some(number) => ("+", number),
+        None => match text.strip_prefix('-') {
+            Some(number) => ("-", number),
+            None => ("", text),
+        },
+    }
+}
+
+/// Separates decimal digits in groups of three if there are more of them
+/// than the threshold, or removes the separators otherwise.
+fn group_digits(digits: &str, options: &Options) -> String {
+    if options.digit_separator_threshold == 0 {
+        return digits.into();
+    }
+
+    let digits: Vec<char> = digits.chars().filter(|c| *c != '_').collect();
+
+    if digits.len() <= options.digit_separator_threshold {
+        return digits.into_iter().collect();
+    }
+
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+
+    for (idx, c) in digits.iter().enumerate() {
+        if idx != 0 && (digits.len() - idx) % 3 == 0 {
+            grouped.push('_');
+        }
+
+        grouped.push(*c);
+    }
+
+    grouped
+}
+
+fn integer_value(kind: SyntaxKind, text: &str) -> Option<i128> {
+    let text = text.replace('_', "");
+
+    match kind {
+        INTEGER => text.parse().ok(),
+        INTEGER_HEX => i128::from_str_radix(text.strip_prefix("0x")?, 16).ok(),
+        INTEGER_OCT => i128::from_str_radix(text.strip_prefix("0o")?, 8).ok(),
+        INTEGER_BIN => i128::from_str_radix(text.strip_prefix("0b")?, 2).ok(),
+        _ => None,
+    }
+}
+
+fn float_value(text: &str) -> Option<f64> {
+    text.replace('_', "").replace("nan", "NaN").parse().ok()
+}
@@ -0,0 +1,358 @@
+use super::is_single_token;
+use crate::{
+    syntax::SyntaxKind::{self, *},
+    util::{escape, escape::Escape, is_bare_key, unescape},
+};
+use logos::Logos;
+use std::str::FromStr;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+
+/// The preferred kind of quotes of string values.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum StringQuotes {
+    /// Keep the quotes of every string.
+    #[default]
+    Preserve,
+    /// Use double quotes for literal strings that contain
+    /// no characters that would have to be escaped.
+    Basic,
+    /// Use single quotes for basic strings whose values
+    /// can be written without escapes.
+    Literal,
+}
+
+impl FromStr for StringQuotes {
+    type Err = UnknownStringStyle;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "preserve" => Ok(StringQuotes::Preserve),
+            "basic" => Ok(StringQuotes::Basic),
+            "literal" => Ok(StringQuotes::Literal),
+            _ => Err(UnknownStringStyle(s.into())),
+        }
+    }
+}
+
+/// How characters are written in basic strings.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum StringEscapes {
+    /// Keep the escape sequences as they are.
+    #[default]
+    Preserve,
+    /// Replace the unicode escapes of printable characters with the characters,
+    /// e.g. `\u00e9` with `é`.
+    Unicode,
+    /// Escape every non-ASCII character, e.g. `é` as `\u00E9`.
+    ///
+    /// Literal strings with non-ASCII characters are converted to basic strings.
+    Ascii,
+}
+
+impl FromStr for StringEscapes {
+    type Err = UnknownStringStyle;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "preserve" => Ok(StringEscapes::Preserve),
+            "unicode" => Ok(StringEscapes::Unicode),
+            "ascii" => Ok(StringEscapes::Ascii),
+            _ => Err(UnknownStringStyle(s.into())),
+        }
+    }
+}
+
+/// An unknown string quote or escape style.
+#[derive(Debug, Clone, Eq, PartialEq, thiserror::Error)]
+#[error(r#"unknown string style "{0}""#)]
+pub struct UnknownStringStyle(pub String);
+
+/// A string token split into its kind and its text between the quotes.
+struct TomlString {
+    kind: SyntaxKind,
+    body: String,
+}
+
+impl TomlString {
+    fn parse(kind: SyntaxKind, text: &str) -> Option<Self> {
+        let quotes = delimiter(kind)?;
+
+        let body = text
+            .strip_prefix(quotes)
+            .and_then(|s| s.strip_suffix(quotes))
+            .filter(|_| text.len() >= quotes.len() * 2)?;
+
+        Some(Self {
+            kind,
+            body: body.into(),
+        })
+    }
+
+    /// The body without the line break right after the opening quotes
+    /// of multi-line strings, which is not part of the value.
+    fn trimmed_body(&self) -> &str {
+        if !self.is_multi_line() {
+            return &self.body;
+        }
+
+        match self.body.strip_prefix("\r\n") {
+            Some(body) => body,
+            None => self.body.strip_prefix('\n').unwrap_or(&self.body),
+        }
+    }
+
+    /// The value of the string, `None` if it contains invalid escapes.
+    fn value(&self) -> Option<String> {
+        let body = self.trimmed_body();
+
+        match self.kind {
+            STRING | MULTI_LINE_STRING => unescape(body).ok(),
+            _ => Some(body.into()),
+        }
+    }
+
+    fn is_literal(&self) -> bool {
+        matches!(self.kind, STRING_LITERAL | MULTI_LINE_STRING_LITERAL)
+    }
+
+    fn is_multi_line(&self) -> bool {
+        matches!(self.kind, MULTI_LINE_STRING | MULTI_LINE_STRING_LITERAL)
+    }
+
+    /// Single-line strings of the same kind, the value of
+    /// a multi-line string must have no line breaks.
+    fn collapse(&mut self) {
+        let body = self.trimmed_body();
+
+        if !self.is_multi_line() || body.contains(['\n', '\r']) {
+            return;
+        }
+
+        if self.is_literal() {
+            // Single quotes can only be escaped in basic strings.
+            if !body.contains('\'') {
+                self.body = body.into();
+                self.kind = STRING_LITERAL;
+            }
+        } else {
+            self.body = escape_quotes(body);
+            self.kind = STRING;
+        }
+    }
+
+    fn convert_quotes(&mut self, quotes: StringQuotes, value: &str) {
+        match quotes {
+            StringQuotes::Preserve => {}
+            StringQuotes::Basic => {
+                if self.is_literal() && !value.contains(['\\', '"']) {
+                    self.convert_to_basic();
+                }
+            }
+            StringQuotes::Literal => match self.kind {
+                STRING if can_be_literal(value) => {
+                    self.body = value.into();
+                    self.kind = STRING_LITERAL;
+                }
+                // The line breaks are kept as they are, so there can be no escapes at all.
+                MULTI_LINE_STRING if !self.body.contains(['\\', '\'']) => {
+                    self.kind = MULTI_LINE_STRING_LITERAL;
+                }
+                _ => {}
+            },
+        }
+    }
+
+    fn convert_to_basic(&mut self) {
+        self.body = self.body.replace('\\', r"\\").replace('"', r#"\""#);
+        self.kind = match self.kind {
+            STRING_LITERAL => STRING,
+            _ => MULTI_LINE_STRING,
+        };
+    }
+
+    fn convert_escapes(&mut self, escapes: StringEscapes) {
+        match escapes {
+            StringEscapes::Preserve => {}
+            StringEscapes::Unicode => {
+                if !self.is_literal() {
+                    self.body = unescape_unicode(&self.body);
+                }
+            }
+            StringEscapes::Ascii => {
+                if self.is_literal() && !self.body.is_ascii() {
+                    self.convert_to_basic();
+                }
+
+                if !self.is_literal() {
+                    self.body = escape_non_ascii(&self.body);
+                }
+            }
+        }
+    }
+
+    fn to_text(&self) -> String {
+        let quotes = delimiter(self.kind).unwrap_or_default();
+        format!("{quotes}{}{quotes}", self.body)
+    }
+}
+
+/// Writes the string token in the configured quote and escape styles,
+/// the value of the string is never changed.
+///
+/// Returns `None` if the string is kept as it is.
+pub(super) fn format_string(
+    kind: SyntaxKind,
+    text: &str,
+    quotes: StringQuotes,
+    escapes: StringEscapes,
+    collapse: bool,
+) -> Option<String> {
+    if quotes == StringQuotes::Preserve && escapes == StringEscapes::Preserve && !collapse {
+        return None;
+    }
+
+    let mut string = TomlString::parse(kind, text)?;
+    let value = string.value()?;
+
+    if collapse {
+        string.collapse();
+    }
+
+    string.convert_quotes(quotes, &value);
+    string.convert_escapes(escapes);
+
+    let formatted = string.to_text();
+
+    if formatted == text {
+        return None;
+    }
+
+    // Should not happen, but the string must stay a single token
+    // with the same value in any case.
+    if !is_single_token(string.kind, &formatted) {
+        return None;
+    }
+
+    let reparsed = TomlString::parse(string.kind, &formatted).and_then(|s| s.value());
+    if reparsed.as_deref() != Some(value.as_str()) {
+        return None;
+    }
+
+    Some(formatted)
+}
+
+/// Writes the quoted key without quotes if it is a valid bare key,
+/// or in the preferred quotes otherwise.
+///
+/// Returns `None` if the key is kept as it is.
+pub(super) fn format_key(text: &str, quotes: StringQuotes, unquote: bool) -> Option<String> {
+    // Quoted keys are parsed as identifiers.
+    let kind = match text.chars().next() {
+        Some('"') => STRING,
+        Some('\'') => STRING_LITERAL,
+        _ => return None,
+    };
+
+    if unquote {
+        let value = TomlString::parse(kind, text).and_then(|s| s.value());
+
+        if let Some(value) = value.filter(|value| is_bare_key(value)) {
+            return Some(value);
+        }
+    }
+
+    format_string(kind, text, quotes, StringEscapes::Preserve, false)
+}
+
+fn delimiter(kind: SyntaxKind) -> Option<&'static str> {
+    match kind {
+        STRING => Some(r#"""#),
+        STRING_LITERAL => Some("'"),
+        MULTI_LINE_STRING => Some(r#"""""#),
+        MULTI_LINE_STRING_LITERAL => Some("'''"),
+        _ => None,
+    }
+}
+
+/// Whether the value can be written between single quotes,
+/// which allow no control characters other than tabs.
+fn can_be_literal(value: &str) -> bool {
+    !value.contains('\'') && value.chars().all(|c| !c.is_control() || c == '\t')
+}
+
+/// Escapes the double quotes that are not escaped yet.
+fn escape_quotes(body: &str) -> String {
+    let mut escaped = String::with_capacity(body.len());
+    let mut chars = body.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                escaped.push(c);
+                escaped.extend(chars.next());
+            }
+            '"' => escaped.push_str(r#"\""#),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Replaces the unicode escapes of printable characters with the characters,
+/// other escapes are kept unless they have a shorter form.
+fn unescape_unicode(body: &str) -> String {
+    let mut new_body = String::with_capacity(body.len());
+    let mut lexer = Escape::lexer(body);
+
+    while let Some(token) = lexer.next() {
+        let slice = lexer.slice();
+
+        let c = match token {
+            Escape::Unicode | Escape::UnicodeLarge | Escape::Hex => {
+                u32::from_str_radix(&slice[2..], 16)
+                    .ok()
+                    .and_then(char::from_u32)
+            }
+            _ => None,
+        };
+
+        match c {
+            Some(c) if (!c.is_control() && !c.is_whitespace()) || c == ' ' => {
+                new_body.push_str(&escape(&c.to_string()));
+            }
+            Some(c) if escape(&c.to_string()).len() == 2 => {
+                new_body.push_str(&escape(&c.to_string()));
+            }
+            _ => new_body.push_str(slice),
+        }
+    }
+
+    new_body + lexer.remainder()
+}
+
+/// Escapes every non-ASCII character outside of escape sequences.
+fn escape_non_ascii(body: &str) -> String {
+    let mut new_body = String::with_capacity(body.len());
+
+    for c in body.chars() {
+        match c as u32 {
+            _ if c.is_ascii() => new_body.push(c),
+            code @ ..=0xFFFF => new_body.push_str(&format!(r"\u{code:04X}")),
+            code => new_body.push_str(&format!(r"\U{code:08X}")),
+        }
+    }
+
+    new_body
+}
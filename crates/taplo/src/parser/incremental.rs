@@ -0,0 +1,167 @@
+//! Reparsing of edited sources that reuses unchanged parts of a previous green tree.
+//!
+//! The parser state is fully reset after each newline at the top level
+//! of a document, so every top-level child between two such newlines
+//! can be parsed independently. Only the lines touched by an edit are
+//! parsed again, everything before and after them is copied from the
+//! previous tree.
+
+use super::{Error, Parse, ParseOptions, Parser};
+use crate::syntax::SyntaxKind::{self, *};
+use logos::Logos;
+use rowan::{GreenNode, GreenNodeData, GreenTokenData, NodeOrToken, TextRange, TextSize};
+
+/// A top-level child of the previous tree along with its range.
+type Child<'g> = (
+    TextRange,
+    NodeOrToken<&'g GreenNodeData, &'g GreenTokenData>,
+);
+
+/// Returns [`None`] if the unchanged parts of the tree cannot be reused safely,
+/// in which case a full parse is required.
+pub(super) fn reparse(
+    previous: &Parse,
+    source: &str,
+    edit: TextRange,
+    options: ParseOptions,
+) -> Option<Parse> {
+    let old_len = previous.green_node.text_len();
+    let new_len = TextSize::try_from(source.len()).ok()?;
+
+    if edit.end() > old_len {
+        return None;
+    }
+
+    let inserted_len = (new_len + edit.len()).checked_sub(old_len)?;
+
+    let mut offset = TextSize::from(0);
+    let children: Vec<Child> = previous
+        .green_node
+        .children()
+        .map(|child| {
+            let range = TextRange::at(offset, child.text_len());
+            offset = range.end();
+            (range, child)
+        })
+        .collect();
+
+    // The first child that will be parsed again and its offset.
+    let (start_idx, start) = sync_points(&children)
+        .take_while(|(_, offset)| *offset == 0.into() || *offset < edit.start())
+        .last()
+        .unwrap_or_default();
+
+    // The first child that is reused after the edit.
+    let (end_idx, old_end) = sync_points(&children)
+        .skip(1)
+        .find(|(idx, _)| children[idx - 1].0.start() >= edit.end())
+        .unwrap_or((children.len(), old_len));
+
+    let new_end = old_end + inserted_len - edit.len();
+
+    if !tokens_align(source, &children, start_idx, start, new_end) {
+        return None;
+    }
+
+    let region = Parser::new(&source[start.into()..new_end.into()])
+        .with_options(options)
+        .parse();
+
+    if end_idx < children.len()
+        && !matches!(
+            region.green_node.children().last(),
+            Some(NodeOrToken::Token(t)) if t.kind() == NEWLINE.into()
+        )
+    {
+        return None;
+    }
+
+    let green_children: Vec<_> = children[..start_idx]
+        .iter()
+        .map(|(_, child)| (*child).to_owned())
+        .chain(region.green_node.children().map(NodeOrToken::to_owned))
+        .chain(
+            children[end_idx..]
+                .iter()
+                .map(|(_, child)| (*child).to_owned()),
+        )
+        .collect();
+
+    let green_node = GreenNode::new(ROOT.into(), green_children);
+
+    let errors = previous
+        .errors
+        .iter()
+        .filter(|err| err.range.start() < start)
+        .cloned()
+        .chain(region.errors.into_iter().map(|err| Error {
+            range: err.range + start,
+            ..err
+        }))
+        .chain(
+            previous
+                .errors
+                .iter()
+                .filter(|err| err.range.start() >= old_end)
+                .map(|err| Error {
+                    range: err.range - old_end + new_end,
+                    message: err.message.clone(),
+                }),
+        )
+        .collect();
+
+    Some(Parse { green_node, errors })
+}
+
+/// Child indices and offsets where the parser starts from a clean state,
+/// these are the start of the document and the ends of top-level newlines.
+fn sync_points<'c>(children: &'c [Child]) -> impl Iterator<Item = (usize, TextSize)> + 'c {
+    std::iter::once((0, TextSize::from(0))).chain(children.iter().enumerate().filter_map(
+        |(idx, (range, child))| match child {
+            NodeOrToken::Token(t) if t.kind() == NEWLINE.into() => Some((idx + 1, range.end())),
+            _ => None,
+        },
+    ))
+}
+
+/// Checks whether lexing the new source as a whole produces token
+/// boundaries at both the start and the end of the reparsed region,
+/// so that no token of the region would continue outside of it.
+fn tokens_align(
+    source: &str,
+    children: &[Child],
+    start_idx: usize,
+    start: TextSize,
+    end: TextSize,
+) -> bool {
+    // The newline before the region must stay a single token.
+    let lex_from = if start_idx == 0 {
+        start
+    } else {
+        children[start_idx - 1].0.start()
+    };
+
+    let mut lexer = SyntaxKind::lexer(&source[lex_from.into()..]);
+    let lex_from = usize::from(lex_from);
+
+    if start_idx != 0 {
+        match lexer.next() {
+            Some(NEWLINE) if lex_from + lexer.span().end == usize::from(start) => {}
+            _ => return false,
+        }
+    }
+
+    if start == end {
+        return true;
+    }
+
+    while lexer.next().is_some() {
+        let token_end = lex_from + lexer.span().end;
+
+        if token_end >= usize::from(end) {
+            return token_end == usize::from(end);
+        }
+    }
+
+    false
+}
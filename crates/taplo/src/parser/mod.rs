@@ -3,15 +3,23 @@
 use crate::{
     dom::{self, FromSyntax},
     syntax::{SyntaxKind, SyntaxKind::*, SyntaxNode},
-    util::{allowed_chars, check_escape},
+    util::{allowed_chars, check_escape_with},
 };
 use logos::{Lexer, Logos};
 use rowan::{GreenNode, GreenNodeBuilder, TextRange, TextSize};
-use std::convert::TryInto;
+use std::{convert::TryInto, str::FromStr};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
 
 #[macro_use]
 mod macros;
 
+mod incremental;
+
 /// A syntax error that can occur during parsing.
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct Error {
@@ -29,6 +37,58 @@ impl core::fmt::Display for Error {
 }
 impl std::error::Error for Error {}
 
+/// A version of the TOML specification.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub enum TomlVersion {
+    /// [TOML v1.0.0](https://toml.io/en/v1.0.0).
+    ///
+    /// Newlines and trailing commas in inline tables are still accepted
+    /// like they have always been, only the formatter writes inline tables
+    /// on a single line for this version.
+    #[default]
+    #[cfg_attr(feature = "serde", serde(rename = "1.0"))]
+    V1_0,
+    /// TOML v1.1.0, it allows `\e` and `\xHH` escapes in strings
+    /// and omitting seconds in times.
+    #[cfg_attr(feature = "serde", serde(rename = "1.1"))]
+    V1_1,
+}
+
+impl core::fmt::Display for TomlVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TomlVersion::V1_0 => f.write_str("1.0"),
+            TomlVersion::V1_1 => f.write_str("1.1"),
+        }
+    }
+}
+
+impl FromStr for TomlVersion {
+    type Err = UnknownTomlVersion;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1.0" | "1.0.0" => Ok(TomlVersion::V1_0),
+            "1.1" | "1.1.0" => Ok(TomlVersion::V1_1),
+            _ => Err(UnknownTomlVersion(s.into())),
+        }
+    }
+}
+
+/// An unknown or unsupported TOML version.
+#[derive(Debug, Clone, Eq, PartialEq, thiserror::Error)]
+#[error(r#"unsupported TOML version "{0}", expected "1.0" or "1.1""#)]
+pub struct UnknownTomlVersion(pub String);
+
+/// Options that affect the syntax accepted by the parser.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ParseOptions {
+    /// The TOML version the document is expected to conform to.
+    pub toml_version: TomlVersion,
+}
+
 /// Parse a TOML document into a [Rowan green tree](rowan::GreenNode).
 ///
 /// The parsing will not stop at unexpected or invalid tokens.
@@ -40,14 +100,52 @@ impl std::error::Error for Error {}
 /// These will also be reported as syntax errors.
 ///
 /// This does not check for semantic errors such as duplicate keys.
+///
+/// The document is parsed as TOML 1.0, use [`parse_with`] for other versions.
 pub fn parse(source: &str) -> Parse {
-    Parser::new(source).parse()
+    parse_with(source, ParseOptions::default())
+}
+
+/// Parse a TOML document with the given options.
+///
+/// See [`parse`] for details.
+pub fn parse_with(source: &str, options: ParseOptions) -> Parse {
+    Parser::new(source).with_options(options).parse()
+}
+
+/// Parse an edited TOML document, reusing the unchanged parts of a previous parse.
+///
+/// The `edit` is the range in the previous source that was replaced,
+/// and `source` is the entire document after the edit.
+///
+/// Only the top-level entries and headers on the lines affected by the edit
+/// are parsed again, the rest of the green tree is shared with `previous`.
+/// The result is always identical to calling [`parse`] with `source`,
+/// if the unchanged parts cannot be reused, the whole document is parsed.
+pub fn reparse(previous: &Parse, source: &str, edit: TextRange) -> Parse {
+    reparse_with(previous, source, edit, ParseOptions::default())
+}
+
+/// Parse an edited TOML document with the given options,
+/// reusing the unchanged parts of a previous parse.
+///
+/// The previous parse must have been created with the same options,
+/// see [`reparse`] for details.
+pub fn reparse_with(
+    previous: &Parse,
+    source: &str,
+    edit: TextRange,
+    options: ParseOptions,
+) -> Parse {
+    incremental::reparse(previous, source, edit, options)
+        .unwrap_or_else(|| parse_with(source, options))
 }
 
 /// A hand-written parser that uses the Logos lexer
 /// to tokenize the source, then constructs
 /// a Rowan green tree from them.
 pub(crate) struct Parser<'p> {
+    options: ParseOptions,
     skip_whitespace: bool,
     // Allow glob patterns as keys and using [] instead of dots.
     key_pattern_syntax: bool,
@@ -101,6 +199,7 @@ type ParserResult<T> = Result<T, ()>;
 impl<'p> Parser<'p> {
     pub(crate) fn new(source: &'p str) -> Self {
         Parser {
+            options: ParseOptions::default(),
             current_token: None,
             skip_whitespace: true,
             key_pattern_syntax: false,
@@ -111,6 +210,11 @@ impl<'p> Parser<'p> {
         }
     }
 
+    pub(crate) fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
     fn parse(mut self) -> Parse {
         let _ = with_node!(self.builder, ROOT, self.parse_root());
 
@@ -283,13 +387,8 @@ impl<'p> Parser<'p> {
 
                     self.insert_token(token, self.lexer.slice());
                 }
-                WHITESPACE => {
-                    if self.skip_whitespace {
-                        self.insert_token(token, self.lexer.slice());
-                    } else {
-                        self.current_token = Some(token);
-                        break;
-                    }
+                WHITESPACE if self.skip_whitespace => {
+                    self.insert_token(token, self.lexer.slice());
                 }
                 ERROR => {
                     self.insert_token(token, self.lexer.slice());
@@ -351,6 +450,10 @@ impl<'p> Parser<'p> {
                                 self.parse_table_array_header()
                             )
                         );
+
+                        // Whitespace is not skipped inside the brackets,
+                        // restore it in case the header was cut short by an error.
+                        self.skip_whitespace = true;
                     } else {
                         let _ = whitelisted!(
                             self,
@@ -546,7 +649,7 @@ impl<'p> Parser<'p> {
                     }
                 };
 
-                match check_escape(self.lexer.slice()) {
+                match check_escape_with(self.lexer.slice(), self.options.toml_version) {
                     Ok(_) => self.token_as(IDENT),
                     Err(err_indices) => {
                         for e in err_indices {
@@ -589,6 +692,17 @@ impl<'p> Parser<'p> {
         }
     }
 
+    /// Date-times and times, seconds are optional since TOML 1.1.
+    fn time_token(&mut self, kind: SyntaxKind) -> ParserResult<()> {
+        if self.options.toml_version < TomlVersion::V1_1 && !has_seconds(self.lexer.slice()) {
+            // The value is still well-formed, so it shouldn't
+            // affect the rest of the parsing.
+            let _ = self.report_error("omitting seconds requires TOML 1.1");
+        }
+
+        self.token_as(kind)
+    }
+
     fn parse_value(&mut self) -> ParserResult<()> {
         let t = match self.get_token() {
             Ok(t) => t,
@@ -596,7 +710,8 @@ impl<'p> Parser<'p> {
         };
 
         match t {
-            BOOL | DATE_TIME_OFFSET | DATE_TIME_LOCAL | DATE | TIME => self.token(),
+            BOOL | DATE => self.token(),
+            DATE_TIME_OFFSET | DATE_TIME_LOCAL | TIME => self.time_token(t),
             INTEGER => {
                 // This is probably a logos bug or a priority issue,
                 // for some reason "1979-05-27" gets lexed as INTEGER.
@@ -606,7 +721,7 @@ impl<'p> Parser<'p> {
 
                 // FIXME: probably another logos bug.
                 if self.lexer.slice().contains(':') {
-                    return self.token_as(TIME);
+                    return self.time_token(TIME);
                 }
 
                 // This could've been done more elegantly probably.
@@ -645,7 +760,7 @@ impl<'p> Parser<'p> {
             FLOAT => {
                 // FIXME: probably another logos bug.
                 if self.lexer.slice().contains(':') {
-                    return self.token_as(TIME);
+                    return self.time_token(TIME);
                 }
 
                 let int_slice = if self.lexer.slice().contains('.') {
@@ -715,7 +830,7 @@ impl<'p> Parser<'p> {
                     }
                 };
 
-                match check_escape(self.lexer.slice()) {
+                match check_escape_with(self.lexer.slice(), self.options.toml_version) {
                     Ok(_) => self.token(),
                     Err(err_indices) => {
                         for e in err_indices {
@@ -751,7 +866,7 @@ impl<'p> Parser<'p> {
                     }
                 };
 
-                match check_escape(self.lexer.slice()) {
+                match check_escape_with(self.lexer.slice(), self.options.toml_version) {
                     Ok(_) => self.token(),
                     Err(err_indices) => {
                         for e in err_indices {
@@ -875,6 +990,14 @@ impl<'p> Parser<'p> {
     }
 }
 
+/// Whether the time part of a date-time or time token
+/// has seconds, it is always `HH:MM` followed by `:SS` if it does.
+fn has_seconds(s: &str) -> bool {
+    let time_start = s.find(['T', 't', ' ']).map_or(0, |idx| idx + 1);
+
+    s.as_bytes().get(time_start + 5) == Some(&b':')
+}
+
 fn check_underscores(s: &str, radix: u32) -> bool {
     if s.starts_with('_') || s.ends_with('_') {
         return false;
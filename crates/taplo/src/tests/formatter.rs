@@ -1,6 +1,11 @@
 use difference::Changeset;
 
-use crate::formatter::{self, Options, OptionsIncomplete};
+use crate::{
+    dom::Node,
+    formatter::{self, Options, OptionsIncomplete},
+    parser::TomlVersion,
+};
+use rowan::{TextRange, TextSize};
 
 macro_rules! assert_format {
     ($expected:expr, $actual:expr) => {
@@ -1203,3 +1208,1054 @@ sort_me_not = ["3", "2", "1"]
 
     assert_format!(expected, &formatted);
 }
+
+#[test]
+fn test_multiline_inline_table_toml_1_1() {
+    let src = r#"
+table = {   # first
+    a = 1,   b = "b",
+
+
+    # standalone
+    c = [1, 2], # trailing
+    d = { e = 3 }
+}
+"#;
+
+    let expected = r#"
+table = { # first
+  a = 1,
+  b = "b",
+  # standalone
+  c = [1, 2],    # trailing
+  d = { e = 3 },
+}
+"#;
+
+    let formatted = crate::formatter::format(
+        src,
+        Options {
+            toml_version: TomlVersion::V1_1,
+            ..Default::default()
+        },
+    );
+
+    assert_format!(expected, &formatted);
+}
+
+#[test]
+fn test_multiline_inline_table_without_trailing_comma() {
+    let src = r#"
+table = {
+    a = 1,
+    b = [
+      1,
+      2,
+    ],
+}
+"#;
+
+    let expected = r#"
+table = {
+  a = 1,
+  b = [
+    1,
+    2,
+  ]
+}
+"#;
+
+    let formatted = crate::formatter::format(
+        src,
+        Options {
+            toml_version: TomlVersion::V1_1,
+            inline_table_trailing_comma: false,
+            array_auto_collapse: false,
+            ..Default::default()
+        },
+    );
+
+    assert_format!(expected, &formatted);
+}
+
+#[test]
+fn test_multiline_inline_table_toml_1_0() {
+    let src = r#"
+table = {
+    a = 1,
+    b = "b",
+}
+"#;
+
+    let expected = r#"
+table = { a = 1, b = "b" }
+"#;
+
+    let formatted = crate::formatter::format(src, Options::default());
+
+    assert_format!(expected, &formatted);
+}
+
+#[test]
+fn test_fmt_skip_entry() {
+    let src = r#"
+a   =   1
+# taplo:fmt skip
+matrix = [
+  [1, 0, 0],
+  [0, 1, 0],
+  [0, 0, 1],
+]
+b   =   2
+"#;
+
+    let expected = r#"
+a = 1
+# taplo:fmt skip
+matrix = [
+  [1, 0, 0],
+  [0, 1, 0],
+  [0, 0, 1],
+]
+b = 2
+"#;
+
+    let formatted = crate::formatter::format(src, Options::default());
+
+    assert_format!(expected, &formatted);
+}
+
+#[test]
+fn test_fmt_off_on() {
+    let src = r#"
+a   =   1
+
+# taplo:fmt off
+x   = 1   # x
+yy  = 22  # y
+zzz = 333 # z
+# taplo:fmt on
+
+b   =   2
+"#;
+
+    let expected = r#"
+a = 1
+
+# taplo:fmt off
+x   = 1   # x
+yy  = 22  # y
+zzz = 333 # z
+# taplo:fmt on
+
+b = 2
+"#;
+
+    let formatted = crate::formatter::format(src, Options::default());
+
+    assert_format!(expected, &formatted);
+
+    let src = r#"
+a   =   1
+# taplo:fmt off
+b   =   2
+[table]
+c   =   3
+"#;
+
+    let expected = r#"
+a = 1
+# taplo:fmt off
+b   =   2
+[table]
+c   =   3
+"#;
+
+    let formatted = crate::formatter::format(src, Options::default());
+
+    assert_format!(expected, &formatted);
+}
+
+#[test]
+fn test_fmt_directive_syntax() {
+    let src = r#"
+a   =   1
+#:fmt off
+x   = 1
+#:  fmt   on
+b   =   2
+#taplo:fmt skip
+c   =   3
+# taplo:FMT skip
+d   =   4
+"#;
+
+    let expected = r#"
+a = 1
+#:fmt off
+x   = 1
+#:  fmt   on
+b = 2
+#taplo:fmt skip
+c   =   3
+# taplo:FMT skip
+d = 4
+"#;
+
+    let formatted = crate::formatter::format(src, Options::default());
+
+    assert_format!(expected, &formatted);
+}
+
+#[test]
+fn test_fmt_skip_table() {
+    let src = r#"
+[before]
+a   =   1
+
+# taplo:fmt skip
+[matrix]
+row1 = [1, 0]
+row2 = [0,     1]
+
+[after]   # taplo:fmt skip
+b   =   2
+[last]
+c   =   3
+"#;
+
+    let expected = r#"
+[before]
+a = 1
+
+# taplo:fmt skip
+[matrix]
+row1 = [1, 0]
+row2 = [0,     1]
+
+[after]   # taplo:fmt skip
+b   =   2
+[last]
+c = 3
+"#;
+
+    let formatted = crate::formatter::format(src, Options::default());
+
+    assert_format!(expected, &formatted);
+}
+
+#[test]
+fn test_fmt_directives_nested() {
+    let src = r#"
+[a]
+x   =   1
+
+# taplo:fmt off
+[a.b]
+y   =   [ 1,2 ]
+[a.b.c]
+# taplo:fmt on
+z   =   [ 1,2 ]
+matrix = [
+  # taplo:fmt off
+  1, 0,
+  0, 1,
+  # taplo:fmt on
+]
+inline = { a   =   1 } # taplo:fmt skip
+
+[a.b.c.d]
+w   =   1
+"#;
+
+    let expected = r#"
+[a]
+  x = 1
+
+# taplo:fmt off
+[a.b]
+y   =   [ 1,2 ]
+[a.b.c]
+# taplo:fmt on
+      z = [1, 2]
+matrix = [
+  # taplo:fmt off
+  1, 0,
+  0, 1,
+  # taplo:fmt on
+]
+inline = { a   =   1 } # taplo:fmt skip
+
+      [a.b.c.d]
+        w = 1
+"#;
+
+    let formatted = crate::formatter::format(
+        src,
+        Options {
+            indent_tables: true,
+            indent_entries: true,
+            ..Default::default()
+        },
+    );
+
+    assert_format!(expected, &formatted);
+}
+
+/// Formats the selected text, or the syntax at the cursor after the selected text.
+fn format_range(src: &str, selected: &str, cursor: bool, options: Options) -> String {
+    let start = src.find(selected).unwrap();
+    let range = if cursor {
+        TextRange::empty(TextSize::from((start + selected.len()) as u32))
+    } else {
+        TextRange::new(
+            TextSize::from(start as u32),
+            TextSize::from((start + selected.len()) as u32),
+        )
+    };
+
+    let dom = crate::parser::parse(src).into_dom();
+    let edits = crate::formatter::format_range(
+        dom,
+        range,
+        options,
+        &[],
+        Vec::<(&str, OptionsIncomplete)>::new(),
+    )
+    .unwrap();
+
+    let mut formatted = src.to_string();
+    for (range, text) in edits.into_iter().rev() {
+        formatted.replace_range(usize::from(range.start())..usize::from(range.end()), &text);
+    }
+    formatted
+}
+
+#[test]
+fn test_format_range() {
+    let src = r#"
+a   =   1
+b   =   [ 1,2 ]
+c   =   { x=1 }
+d   =   4
+"#;
+
+    let expected = r#"
+a   =   1
+b = [1, 2]
+c = { x = 1 }
+d   =   4
+"#;
+
+    assert_format!(
+        expected,
+        &format_range(src, "[ 1,2 ]\nc   =", false, Options::default())
+    );
+
+    // Only the syntax under the cursor is formatted.
+    let expected = r#"
+a   =   1
+b   =   [ 1,2 ]
+c = { x = 1 }
+d   =   4
+"#;
+
+    assert_format!(expected, &format_range(src, "c ", true, Options::default()));
+    assert_format!(
+        expected,
+        &format_range(src, "x=1", false, Options::default())
+    );
+    let expected = r#"
+a   =   1
+b   =   [ 1,2 ]
+c   =   { x=1 }
+d = 4
+"#;
+
+    assert_format!(expected, &format_range(src, "d", false, Options::default()));
+}
+
+#[test]
+fn test_format_range_tables() {
+    let src = r#"
+[a]
+x   =   1
+# comment
+  [a.b]
+y   =   2
+z   =   3 # z
+
+[c]
+w   =   4
+"#;
+
+    let expected = r#"
+[a]
+x   =   1
+  # comment
+  [a.b]
+    y = 2
+    z = 3 # z
+
+[c]
+w   =   4
+"#;
+
+    let options = Options {
+        indent_tables: true,
+        indent_entries: true,
+        ..Default::default()
+    };
+
+    assert_format!(
+        expected,
+        &format_range(
+            src,
+            "# comment\n  [a.b]\ny   =   2\nz",
+            false,
+            options.clone()
+        )
+    );
+
+    let src = expected;
+    let dom = crate::parser::parse(src).into_dom();
+    let start = TextSize::from(src.find("[a.b]").unwrap() as u32);
+    let edits = crate::formatter::format_range(
+        dom,
+        TextRange::new(start, start + TextSize::from(20)),
+        options,
+        &[],
+        Vec::<(&str, OptionsIncomplete)>::new(),
+    )
+    .unwrap();
+    assert!(edits.is_empty());
+}
+
+#[test]
+fn test_diff_text_edits() {
+    let src = r#"
+a   =   1
+b = 2
+
+[tbl]
+c   =   [ 1,2 ]
+d = "é"
+"#;
+
+    let formatted = crate::formatter::format(src, Options::default());
+    let diff = formatter::diff::Diff::new(src, &formatted);
+    let edits = diff.text_edits();
+
+    // Only the lines that changed are edited.
+    assert_eq!(edits.len(), 2);
+    let first = src.find("a   =").unwrap();
+    assert!(usize::from(edits[0].0.start()) > first);
+    assert!(usize::from(edits[0].0.end()) < src.find("b = 2").unwrap());
+
+    let mut edited = src.to_string();
+    for (range, text) in edits.into_iter().rev() {
+        edited.replace_range(usize::from(range.start())..usize::from(range.end()), &text);
+    }
+    assert_format!(&formatted, &edited);
+
+    assert!(formatter::diff::Diff::new(src, src).text_edits().is_empty());
+}
+
+#[test]
+fn test_diff_inserted_and_removed_lines() {
+    let original = "a = 1\n\n\n\nb = 2\nc = 3\n";
+    let modified = "a = 1\n\nb = 2\nx = 0\nc = 3\n";
+
+    let diff = formatter::diff::Diff::new(original, modified);
+
+    let mut edited = original.to_string();
+    for (range, text) in diff.text_edits().into_iter().rev() {
+        edited.replace_range(usize::from(range.start())..usize::from(range.end()), &text);
+    }
+    assert_format!(modified, &edited);
+
+    let hunks = diff.hunks(0);
+    assert_eq!(hunks.len(), 2);
+    assert_eq!(hunks[0].original, 2..4);
+    assert_eq!(hunks[0].modified, 2..2);
+    assert_eq!(hunks[1].original, 5..5);
+    assert_eq!(hunks[1].modified, 3..4);
+
+    let hunks = diff.hunks(3);
+    assert_eq!(hunks.len(), 1);
+    assert_eq!(hunks[0].original, 0..6);
+    assert_eq!(hunks[0].modified, 0..5);
+}
+
+#[test]
+fn test_reorder_by_schema() {
+    let src = r#"#:schema ./schema.json
+# The manifest.
+
+[dependencies]
+serde = "1"
+anyhow = "1"
+
+# Metadata of the package.
+[package]
+version = "0.1.0"
+readme = "README.md"
+name = "foo"
+authors = []
+edition = "2021"
+
+[package.metadata.docs]
+all-features = true
+"#;
+
+    let expected = r#"#:schema ./schema.json
+# The manifest.
+
+# Metadata of the package.
+[package]
+name = "foo"
+version = "0.1.0"
+edition = "2021"
+authors = []
+readme = "README.md"
+
+[package.metadata.docs]
+all-features = true
+
+[dependencies]
+anyhow = "1"
+serde = "1"
+"#;
+
+    let mut key_order = formatter::KeyOrder::new();
+    key_order.insert(vec![], vec!["package".into(), "dependencies".into()]);
+    key_order.insert(
+        vec!["package".into()],
+        vec!["name".into(), "version".into(), "edition".into()],
+    );
+
+    let dom = crate::parser::parse(src).into_dom();
+    let formatted = crate::formatter::format_with_key_order(
+        dom,
+        Options {
+            reorder_by_schema: true,
+            ..Default::default()
+        },
+        &[],
+        std::iter::empty::<(&str, OptionsIncomplete)>(),
+        key_order,
+    )
+    .unwrap();
+
+    assert_format!(expected, &formatted);
+}
+
+#[test]
+fn test_reorder_by_schema_arrays_of_tables() {
+    let src = r#"[[bin]]
+path = "a.rs"
+name = "a"
+
+[bin.metadata]
+b = 2
+a = 1
+
+[[bin]]
+name = "b"
+
+[alpha]
+x = 1"#;
+
+    let expected = r#"[alpha]
+x = 1
+
+[[bin]]
+name = "a"
+path = "a.rs"
+
+[bin.metadata]
+a = 1
+b = 2
+
+[[bin]]
+name = "b"
+"#;
+
+    let mut key_order = formatter::KeyOrder::new();
+    key_order.insert(vec!["bin".into()], vec!["name".into()]);
+
+    let dom = crate::parser::parse(src).into_dom();
+    let formatted = crate::formatter::format_with_key_order(
+        dom,
+        Options {
+            reorder_by_schema: true,
+            ..Default::default()
+        },
+        &[],
+        std::iter::empty::<(&str, OptionsIncomplete)>(),
+        key_order,
+    )
+    .unwrap();
+
+    assert_format!(expected, &formatted);
+}
+
+#[test]
+fn test_reorder_by_schema_detached_array_subtables() {
+    let src = r#"[[z]]
+x = 1
+
+[b]
+y = 2
+
+[z.c]
+w = 3
+"#;
+
+    // `[z.c]` belongs to the `[[z]]` element even though `[b]` came in between.
+    let expected = r#"[b]
+y = 2
+
+[[z]]
+x = 1
+
+[z.c]
+w = 3
+"#;
+
+    let dom = crate::parser::parse(src).into_dom();
+    let formatted = crate::formatter::format_with_key_order(
+        dom,
+        Options {
+            reorder_by_schema: true,
+            ..Default::default()
+        },
+        &[],
+        std::iter::empty::<(&str, OptionsIncomplete)>(),
+        formatter::KeyOrder::new(),
+    )
+    .unwrap();
+
+    assert_format!(expected, &formatted);
+    assert!(crate::parser::parse(&formatted)
+        .into_dom()
+        .validate()
+        .is_ok());
+}
+
+#[test]
+fn test_reorder_tables() {
+    let src = r#"title = "x"
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+
+# Serialization.
+[dependencies.serde]
+version = "1"
+
+[[bin]]
+name = "b"
+
+[bin.metadata]
+x = 1
+
+[dependencies]
+anyhow = "1"
+
+[[bin]]
+name = "a"
+
+[package]
+name = "foo"
+"#;
+
+    let expected = r#"title = "x"
+
+[package]
+name = "foo"
+
+[dependencies]
+anyhow = "1"
+
+# Serialization.
+[dependencies.serde]
+version = "1"
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+
+[[bin]]
+name = "b"
+
+[bin.metadata]
+x = 1
+
+[[bin]]
+name = "a"
+"#;
+
+    let formatted = crate::formatter::format(
+        src,
+        formatter::Options {
+            reorder_tables: true,
+            table_order: "package, dependencies, target.*.dependencies"
+                .parse()
+                .unwrap(),
+            ..Default::default()
+        },
+    );
+
+    assert_format!(expected, &formatted);
+}
+
+#[test]
+fn test_reorder_tables_alphabetically() {
+    let src = r#"[c]
+x = 1
+[a.b]
+y = 2
+[a]
+z = 3
+"#;
+
+    let expected = r#"[a]
+z = 3
+[a.b]
+y = 2
+[c]
+x = 1
+"#;
+
+    let formatted = crate::formatter::format(
+        src,
+        formatter::Options {
+            reorder_tables: true,
+            ..Default::default()
+        },
+    );
+
+    assert_format!(expected, &formatted);
+}
+
+#[test]
+fn test_reorder_tables_detached_array_subtables() {
+    let src = r#"[[z]]
+x = 1
+
+[b]
+y = 2
+
+[z.c]
+w = 3
+"#;
+
+    let expected = r#"[b]
+y = 2
+
+[[z]]
+x = 1
+
+[z.c]
+w = 3
+"#;
+
+    let formatted = crate::formatter::format(
+        src,
+        formatter::Options {
+            reorder_tables: true,
+            ..Default::default()
+        },
+    );
+
+    assert_format!(expected, &formatted);
+    assert!(crate::parser::parse(&formatted)
+        .into_dom()
+        .validate()
+        .is_ok());
+}
+
+#[test]
+fn test_reorder_tables_invalid_pattern() {
+    let options = formatter::Options {
+        reorder_tables: true,
+        table_order: r#"package, "[a""#.parse().unwrap(),
+        ..Default::default()
+    };
+
+    assert!(options.table_order.patterns().is_err());
+
+    let dom = crate::parser::parse("[b]\n[a]\n").into_dom();
+    let result = crate::formatter::format_with_key_order(
+        dom,
+        options,
+        &[],
+        std::iter::empty::<(&str, OptionsIncomplete)>(),
+        formatter::KeyOrder::new(),
+    );
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_string_quotes() {
+    let src = r#"a = 'plain'
+b = 'C:\path'
+c = "it's"
+d = "tab\there"
+e = "new\nline"
+f = ["one", 'two']
+"#;
+
+    let expected_basic = r#"a = "plain"
+b = 'C:\path'
+c = "it's"
+d = "tab\there"
+e = "new\nline"
+f = ["one", "two"]
+"#;
+
+    let expected_literal = r#"a = 'plain'
+b = 'C:\path'
+c = "it's"
+d = 'tab	here'
+e = "new\nline"
+f = ['one', 'two']
+"#;
+
+    let formatted = crate::formatter::format(
+        src,
+        formatter::Options {
+            string_quotes: formatter::StringQuotes::Basic,
+            ..Default::default()
+        },
+    );
+
+    assert_format!(expected_basic, &formatted);
+
+    let formatted = crate::formatter::format(
+        src,
+        formatter::Options {
+            string_quotes: formatter::StringQuotes::Literal,
+            ..Default::default()
+        },
+    );
+
+    assert_format!(expected_literal, &formatted);
+}
+
+#[test]
+fn test_collapse_multiline_strings() {
+    let src = r#"a = """
+single "quoted" line"""
+b = '''it's'''
+c = """
+two
+lines"""
+d = { e = '''
+x''' }
+"#;
+
+    let expected = r#"a = "single \"quoted\" line"
+b = '''it's'''
+c = """
+two
+lines"""
+d = { e = 'x' }
+"#;
+
+    let formatted = crate::formatter::format(
+        src,
+        formatter::Options {
+            collapse_multiline_strings: true,
+            ..Default::default()
+        },
+    );
+
+    assert_format!(expected, &formatted);
+}
+
+#[test]
+fn test_string_escapes() {
+    let src = r#"a = "caf\u00e9 \u0022\u000A\u001B"
+b = "café 😀"
+c = 'café'
+"#;
+
+    let expected_unicode = r#"a = "café \"\n\u001B"
+b = "café 😀"
+c = 'café'
+"#;
+
+    let expected_ascii = r#"a = "caf\u00e9 \u0022\u000A\u001B"
+b = "caf\u00E9 \U0001F600"
+c = "caf\u00E9"
+"#;
+
+    let formatted = crate::formatter::format(
+        src,
+        formatter::Options {
+            string_escapes: formatter::StringEscapes::Unicode,
+            ..Default::default()
+        },
+    );
+
+    assert_format!(expected_unicode, &formatted);
+
+    let formatted = crate::formatter::format(
+        src,
+        formatter::Options {
+            string_escapes: formatter::StringEscapes::Ascii,
+            ..Default::default()
+        },
+    );
+
+    assert_format!(expected_ascii, &formatted);
+}
+
+#[test]
+fn test_key_quotes() {
+    let src = r#"["package"]
+"name" = "a"
+'version' = "1"
+'with space' = 1
+"it's" = 2
+"1" = 3
+
+['dependencies'."serde"]
+x = { "y" = 1, 'z z' = 2 }
+"#;
+
+    let expected = r#"[package]
+name = "a"
+version = "1"
+"with space" = 1
+"it's" = 2
+"1" = 3
+
+[dependencies.serde]
+x = { y = 1, "z z" = 2 }
+"#;
+
+    let formatted = crate::formatter::format(
+        src,
+        formatter::Options {
+            unquote_keys: true,
+            key_quotes: formatter::StringQuotes::Basic,
+            ..Default::default()
+        },
+    );
+
+    assert_format!(expected, &formatted);
+}
+
+/// Asserts that every value of the formatted document is the same as in the source.
+fn assert_same_values(src: &str, formatted: &str) {
+    let src_dom = crate::parser::parse(src).into_dom();
+    let formatted_dom = crate::parser::parse(formatted).into_dom();
+
+    for (keys, node) in src_dom.flat_iter() {
+        let other = formatted_dom.path(&keys).unwrap();
+
+        match node {
+            Node::Integer(int) => {
+                assert_eq!(int.value(), other.as_integer().unwrap().value(), "{keys}");
+            }
+            Node::Float(float) => {
+                let (a, b) = (float.value(), other.as_float().unwrap().value());
+                assert!(
+                    a.to_bits() == b.to_bits() || (a.is_nan() && b.is_nan()),
+                    "{keys}"
+                );
+            }
+            Node::Date(date) => {
+                assert_eq!(date.value(), other.as_date().unwrap().value(), "{keys}");
+            }
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn test_number_literals() {
+    let src = r#"a = 1_000
+b = 1234567
+c = -12345
+d = +5
+e = 0xDEAD_beef
+f = 0o755
+g = 1E3
+h = +1.5e+10
+i = 1234567.891_011
+j = +inf
+k = -nan
+l = [+1_0, 100_000]
+"#;
+
+    let expected = r#"a = 1000
+b = 1_234_567
+c = -12_345
+d = 5
+e = 0xdead_beef
+f = 0o755
+g = 1e3
+h = 1.5e10
+i = 1_234_567.891_011
+j = inf
+k = -nan
+l = [10, 100_000]
+"#;
+
+    let formatted = crate::formatter::format(
+        src,
+        formatter::Options {
+            digit_separator_threshold: 4,
+            lowercase_numbers: true,
+            remove_plus_signs: true,
+            ..Default::default()
+        },
+    );
+
+    assert_format!(expected, &formatted);
+    assert_same_values(src, &formatted);
+}
+
+#[test]
+fn test_datetime_separator() {
+    let src = r#"a = 1979-05-27t07:32:00Z
+b = 1979-05-27 07:32:00.999
+c = 1979-05-27T00:32:00-07:00
+d = 1979-05-27
+e = 07:32:00
+"#;
+
+    let expected_t = r#"a = 1979-05-27T07:32:00Z
+b = 1979-05-27T07:32:00.999
+c = 1979-05-27T00:32:00-07:00
+d = 1979-05-27
+e = 07:32:00
+"#;
+
+    let expected_space = r#"a = 1979-05-27 07:32:00Z
+b = 1979-05-27 07:32:00.999
+c = 1979-05-27 00:32:00-07:00
+d = 1979-05-27
+e = 07:32:00
+"#;
+
+    for (separator, expected) in [
+        (formatter::DateTimeSeparator::T, expected_t),
+        (formatter::DateTimeSeparator::Space, expected_space),
+    ] {
+        let formatted = crate::formatter::format(
+            src,
+            formatter::Options {
+                datetime_separator: separator,
+                ..Default::default()
+            },
+        );
+
+        assert_format!(expected, &formatted);
+        assert_same_values(src, &formatted);
+    }
+}
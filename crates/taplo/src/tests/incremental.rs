@@ -0,0 +1,130 @@
+use crate::parser::{parse, reparse, Parse};
+use rowan::{TextRange, TextSize};
+
+const SOURCE: &str = r#"# comment
+title = "TOML"
+
+[owner]
+name = "Tom" # trailing
+dob = 1979-05-27T07:32:00-08:00
+
+[database]
+ports = [ 8000,
+  8001,
+  8002 ]
+data = { a = 1, b = "2" }
+text = """
+multi
+line = 1
+[not.a.table]
+"""
+literal = '''
+also
+multi'''
+
+[[products]]
+name = "Hammer"
+sku = 738594937
+
+[[products]]
+
+bad key = ] 2
+
+[[products]]
+name = "Nail"
+"#;
+
+const SNIPPETS: &[&str] = &[
+    "",
+    "x",
+    " ",
+    "\n",
+    "\r\n",
+    "=",
+    "#",
+    "[",
+    "]",
+    "[[",
+    "\"",
+    "'",
+    "\"\"\"",
+    "'''",
+    "{",
+    "}",
+    ",",
+    "key = 1\n",
+    "\n[table]\n",
+    "\n[[array]]\nx = [\n",
+];
+
+fn assert_reparse(source: &str, previous: &Parse, edit: TextRange, insert: &str) {
+    let mut edited = source.to_string();
+    edited.replace_range(usize::from(edit.start())..usize::from(edit.end()), insert);
+
+    let expected = parse(&edited);
+    let actual = reparse(previous, &edited, edit);
+
+    assert_eq!(
+        actual.green_node, expected.green_node,
+        "different tree after replacing {edit:?} with {insert:?} in:\n{source}"
+    );
+    assert_eq!(
+        actual.errors, expected.errors,
+        "different errors after replacing {edit:?} with {insert:?} in:\n{source}"
+    );
+}
+
+fn assert_reparse_all(source: &str, step: usize) {
+    let previous = parse(source);
+
+    let boundaries: Vec<usize> = source
+        .char_indices()
+        .map(|(idx, _)| idx)
+        .chain([source.len()])
+        .collect();
+
+    for (i, &start) in boundaries.iter().enumerate().step_by(step) {
+        let offset = TextSize::try_from(start).unwrap();
+
+        for snippet in SNIPPETS {
+            assert_reparse(source, &previous, TextRange::empty(offset), snippet);
+        }
+
+        for len in [1, 3, 12, 40] {
+            if let Some(&end) = boundaries.get(i + len) {
+                let edit = TextRange::new(offset, TextSize::try_from(end).unwrap());
+                assert_reparse(source, &previous, edit, "");
+                assert_reparse(source, &previous, edit, "y = 2\n");
+            }
+        }
+    }
+}
+
+#[test]
+fn reparse_matches_parse() {
+    assert_reparse_all(SOURCE, 2);
+}
+
+#[test]
+fn reparse_matches_parse_crlf() {
+    assert_reparse_all(&SOURCE.replace('\n', "\r\n"), 3);
+}
+
+#[test]
+fn reparse_matches_parse_example() {
+    assert_reparse_all(include_str!("../../../../test-data/example.toml"), 29);
+}
+
+#[test]
+fn reparse_whole_document() {
+    let previous = parse(SOURCE);
+    let full = TextRange::up_to(previous.green_node.text_len());
+
+    assert_reparse(SOURCE, &previous, full, "");
+    assert_reparse(SOURCE, &previous, full, "a = 1\n");
+
+    let empty = parse("");
+    let actual = reparse(&empty, SOURCE, TextRange::empty(0.into()));
+    assert_eq!(actual.green_node, previous.green_node);
+    assert_eq!(actual.errors, previous.errors);
+}
@@ -0,0 +1,545 @@
+use crate::{
+    dom::{
+        node::{Array, Table},
+        KeyOrIndex, Keys, Node,
+    },
+    parser::parse,
+};
+
+fn root(src: &str) -> Table {
+    parse(src).into_dom().try_into_table().unwrap()
+}
+
+fn table(node: Option<Node>) -> Table {
+    node.unwrap().try_into_table().unwrap()
+}
+
+fn array(node: Option<Node>) -> Array {
+    node.unwrap().try_into_array().unwrap()
+}
+
+fn assert_lossless(root: &Table, expected: &str) {
+    let output = Node::from(root.clone()).to_toml_lossless();
+    assert_eq!(output, expected);
+
+    let errors = parse(&output).errors;
+    assert!(errors.is_empty(), "{errors:#?}");
+}
+
+#[test]
+fn lossless_unchanged() {
+    let src = include_str!("../../../../test-data/example.toml");
+    assert_lossless(&root(src), src);
+}
+
+#[test]
+fn lossless_replace_value() {
+    let src = r#"# package
+[package]
+name = "taplo" # the name
+version = "0.1.0"
+"#;
+
+    let root = root(src);
+    table(root.get("package")).insert("version", "0.2.0");
+
+    assert_lossless(
+        &root,
+        r#"# package
+[package]
+name = "taplo" # the name
+version = "0.2.0"
+"#,
+    );
+}
+
+#[test]
+fn lossless_insert_entries() {
+    let src = r#"title = "example"
+
+# owner
+[owner]
+  name = "Tom"
+
+[database]
+"#;
+
+    let root = root(src);
+    root.insert("version", 2);
+    table(root.get("owner")).insert("age", 42);
+    table(root.get("database")).insert("enabled", true);
+
+    let servers = Table::new();
+    servers.insert("ip", "10.0.0.1");
+    root.insert("servers", servers);
+
+    assert_lossless(
+        &root,
+        r#"title = "example"
+version = 2
+
+# owner
+[owner]
+  name = "Tom"
+  age = 42
+
+[database]
+enabled = true
+
+[servers]
+ip = "10.0.0.1"
+"#,
+    );
+}
+
+#[test]
+fn lossless_insert_before_tables() {
+    let src = r#"# comment
+[table]
+a = 1"#;
+
+    let root = root(src);
+    root.insert("b", 2.0);
+    table(root.get("table")).insert("c", "3");
+
+    assert_lossless(
+        &root,
+        r#"b = 2.0
+
+# comment
+[table]
+a = 1
+c = "3"
+"#,
+    );
+}
+
+#[test]
+fn lossless_remove_entries() {
+    let src = r#"a = 1
+b = 2 # comment
+
+[table]
+c = 3
+
+[other]
+d = 4
+"#;
+
+    let root = root(src);
+    root.remove("b");
+    root.remove("table");
+
+    assert_lossless(
+        &root,
+        r#"a = 1
+
+[other]
+d = 4
+"#,
+    );
+}
+
+#[test]
+fn lossless_dotted_keys() {
+    let src = r#"a.b = 1
+a.c = 2
+x = 0
+
+[table]
+d.e = 3
+"#;
+
+    let root = root(src);
+    let a = table(root.get("a"));
+    a.remove("c");
+    a.insert("f", 4);
+
+    let d = table(table(root.get("table")).get("d"));
+    let g = Table::new();
+    g.insert("h", 5);
+    d.insert("g", g);
+
+    assert_lossless(
+        &root,
+        r#"a.b = 1
+a.f = 4
+x = 0
+
+[table]
+d.e = 3
+
+[table.d.g]
+h = 5
+"#,
+    );
+}
+
+#[test]
+fn lossless_header_dotted_keys() {
+    let src = r#"[a.b]
+c = 1
+
+[x]
+"#;
+
+    let root = root(src);
+    let a = table(root.get("a"));
+    a.insert("d", 2);
+
+    assert_lossless(
+        &root,
+        r#"[a.b]
+c = 1
+
+[a]
+d = 2
+
+[x]
+"#,
+    );
+}
+
+#[test]
+fn lossless_inline_tables() {
+    let src = r#"point = { x = 1, y = 2 } # comment
+empty = {}
+"#;
+
+    let root = root(src);
+    let point = table(root.get("point"));
+    point.remove("y");
+    point.insert("z", 3);
+    point.insert("x", 0);
+
+    table(root.get("empty")).insert("a", "b");
+
+    assert_lossless(
+        &root,
+        r#"point = { x = 0, z = 3 } # comment
+empty = { a = "b" }
+"#,
+    );
+}
+
+#[test]
+fn lossless_inline_table_remove_first() {
+    let src = r#"t = { a.b = 1, c = 2, d = { e = 3 } }
+"#;
+
+    let root = root(src);
+    let t = table(root.get("t"));
+    t.remove("c");
+    table(t.get("a")).insert("f", 4);
+    table(t.get("d")).remove("e");
+
+    assert_lossless(
+        &root,
+        r#"t = { a.b = 1, d = {}, a.f = 4 }
+"#,
+    );
+}
+
+#[test]
+fn lossless_arrays() {
+    let src = r#"a = [1, 2, 3]
+b = [
+  "x", # first
+  "y",
+]
+c = [
+  1,
+  2
+]
+"#;
+
+    let root = root(src);
+    let a = array(root.get("a"));
+    a.remove(1);
+    a.push(4);
+    a.insert(0, 0);
+
+    let b = array(root.get("b"));
+    b.remove(0);
+    b.push("z");
+
+    let c = array(root.get("c"));
+    c.remove(1);
+
+    assert_lossless(
+        &root,
+        r#"a = [0, 1, 3, 4]
+b = [
+  "y",
+  "z",
+]
+c = [
+  1
+]
+"#,
+    );
+}
+
+#[test]
+fn lossless_array_of_tables() {
+    let src = r#"[[products]]
+name = "Hammer"
+
+[products.details]
+weight = 1
+
+[[products]]
+name = "Nail"
+
+[other]
+"#;
+
+    let root = root(src);
+    let products = array(root.get("products"));
+
+    let item = Table::new();
+    item.insert("name", "Screw");
+    products.insert(1, item);
+    products.remove(2);
+
+    table(products.items().read().first().cloned()).insert("sku", 738594937);
+
+    assert_lossless(
+        &root,
+        r#"[[products]]
+name = "Hammer"
+sku = 738594937
+
+[products.details]
+weight = 1
+
+[[products]]
+name = "Screw"
+
+[other]
+"#,
+    );
+}
+
+#[test]
+fn lossless_new_array_of_tables() {
+    let src = r#"a = 1
+"#;
+
+    let root = root(src);
+    let items = Array::new_tables();
+    for name in ["x", "y"] {
+        let item = Table::new();
+        item.insert("name", name);
+        items.push(item);
+    }
+    root.insert("items", items);
+    root.insert("a", Array::new());
+
+    assert_lossless(
+        &root,
+        r#"a = []
+
+[[items]]
+name = "x"
+
+[[items]]
+name = "y"
+"#,
+    );
+}
+
+#[test]
+fn lossless_replace_table_with_value() {
+    let src = r#"a = 1
+
+[table]
+b = 2
+"#;
+
+    let root = root(src);
+    root.insert("table", "value");
+
+    assert_lossless(
+        &root,
+        r#"a = 1
+table = "value"
+"#,
+    );
+}
+
+#[test]
+fn lossless_new_nested_tables() {
+    let src = r#"a = 1
+"#;
+
+    let root = root(src);
+    let inner = Table::new();
+    inner.insert("b", 2);
+    let outer = Table::new();
+    outer.insert("inner", inner);
+    outer.insert("empty", Table::new());
+    root.insert("outer", outer);
+
+    assert_lossless(
+        &root,
+        r#"a = 1
+
+[outer.inner]
+b = 2
+
+[outer.empty]
+"#,
+    );
+}
+
+#[test]
+fn lossless_no_empty_lines_after_removals() {
+    let standard = Table::new();
+    standard.insert("b", 1);
+    let inline_root = root("a = { b = 1 }\n");
+    inline_root.insert("a", standard);
+    assert_lossless(&inline_root, "[a]\nb = 1\n");
+
+    let inline = Table::new_inline();
+    inline.insert("b", 1);
+    let standard_root = root("[a]\nb = 1\n");
+    standard_root.insert("a", inline);
+    assert_lossless(&standard_root, "a = { b = 1 }\n");
+
+    // The header is kept but the section after it needs no separator.
+    let header_root = root("[b]\nx = 1\n");
+    let b = table(header_root.get("b"));
+    b.remove("x");
+    let a = Table::new();
+    a.insert("x", 1);
+    b.insert("a", a);
+    assert_lossless(&header_root, "[b]\n[b.a]\nx = 1\n");
+}
+
+#[test]
+fn lossless_keep_crlf_newlines() {
+    let src = "title = \"example\"\r\n\r\n[owner]\r\nname = \"Tom\"\r\n";
+
+    let root = root(src);
+    root.insert("version", 2);
+    table(root.get("owner")).insert("age", 42);
+
+    let servers = Table::new();
+    servers.insert("ip", "10.0.0.1");
+    root.insert("servers", servers);
+
+    assert_lossless(
+        &root,
+        "title = \"example\"\r\nversion = 2\r\n\r\n[owner]\r\nname = \"Tom\"\r\nage = 42\r\n\r\n[servers]\r\nip = \"10.0.0.1\"\r\n",
+    );
+}
+
+/// Removes, replaces and inserts around every node of the source,
+/// the output must always have the same values as the modified tree.
+fn assert_edits_preserve_values(src: &str) {
+    let new_table = || {
+        let table = Table::new();
+        table.insert("a", 1);
+        table.insert("b", Table::new());
+        table
+    };
+
+    for (path, _) in parse(src).into_dom().flat_iter() {
+        let keys: Vec<KeyOrIndex> = path.iter().cloned().collect();
+        let (last, parent_keys) = keys.split_last().unwrap();
+
+        for edit in 0..3 {
+            let root = root(src);
+            let parent = Node::from(root.clone())
+                .path(&Keys::new(parent_keys.iter().cloned()))
+                .unwrap();
+
+            match (last, &parent, edit) {
+                (KeyOrIndex::Key(key), Node::Table(table), 0) => {
+                    table.remove(key.clone());
+                }
+                (KeyOrIndex::Key(key), Node::Table(table), 1) => {
+                    table.insert(key.clone(), new_table());
+                    table.insert("new", "value");
+                }
+                (KeyOrIndex::Key(_), Node::Table(table), _) => {
+                    let tables = Array::new_tables();
+                    tables.push(new_table());
+                    table.insert("new_table", new_table());
+                    table.insert("new_array", tables);
+                }
+                (KeyOrIndex::Index(idx), Node::Array(array), 0) => {
+                    array.remove(*idx);
+                }
+                (KeyOrIndex::Index(idx), Node::Array(array), 1) => {
+                    if array.items().read()[*idx].is_table() {
+                        array.replace(*idx, new_table());
+                    } else {
+                        array.replace(*idx, 7);
+                    }
+                }
+                (KeyOrIndex::Index(idx), Node::Array(array), _) => {
+                    if array.items().read()[*idx].is_table() {
+                        array.insert(*idx, new_table());
+                        array.push(new_table());
+                    } else {
+                        array.insert(*idx, 8);
+                        array.push(9);
+                    }
+                }
+                _ => continue,
+            }
+
+            let output = Node::from(root.clone()).to_toml_lossless();
+            let parse = parse(&output);
+            assert!(
+                parse.errors.is_empty(),
+                "edit {edit} of {path}: {:#?}\n{output}",
+                parse.errors
+            );
+
+            assert_eq!(
+                serde_json::to_value(parse.into_dom()).unwrap(),
+                serde_json::to_value(Node::from(root)).unwrap(),
+                "edit {edit} of {path}:\n{output}"
+            );
+        }
+    }
+}
+
+#[test]
+fn lossless_edits_preserve_values() {
+    assert_edits_preserve_values(
+        r#"a.b = 1
+a.c = [1, { x = 2, y.z = 3 }]
+
+[t]
+q = 1
+
+[[t.arr]]
+k = 1
+
+[t.arr.sub]
+m = 1
+
+[[t.arr]]
+
+[u.v]
+w = 1
+
+[u]
+x = { a = [
+  1,
+  2
+], b = 3 }
+"#,
+    );
+}
+
+#[test]
+fn lossless_edits_preserve_values_example() {
+    let src = include_str!("../../../../test-data/example.toml");
+    assert_edits_preserve_values(src);
+    assert_edits_preserve_values(&src.replace('\n', "\r\n"));
+}
@@ -1,10 +1,15 @@
-use crate::parser::parse;
+use crate::{
+    dom::node::DateTimeValue,
+    parser::{parse, parse_with, ParseOptions, TomlVersion},
+};
 
 mod generated {
     mod invalid;
 }
 
 mod formatter;
+mod incremental;
+mod lossless;
 
 #[test]
 fn time_in_arrays() {
@@ -28,6 +33,21 @@ fn comments_after_tables() {
     assert!(errors.is_empty(), "{:#?}", errors);
 }
 
+#[test]
+fn whitespace_after_invalid_table_array_header() {
+    let src = "[[a b]]\nc  =  1\n[[d]]\n  e = 2\n";
+
+    let parse = parse(src);
+
+    // Only the header is invalid, the whitespace after it is skipped again.
+    assert!(!parse.errors.is_empty());
+    assert!(
+        parse.errors.iter().all(|err| err.range.end() <= 7.into()),
+        "{:#?}",
+        parse.errors
+    );
+}
+
 #[test]
 fn dates_in_table_keys() {
     let src = r#"
@@ -54,3 +74,149 @@ cooldowns = {
 
     assert!(errors.is_empty(), "{:#?}", errors);
 }
+
+#[test]
+fn toml_1_1_escapes() {
+    let src = r#"
+esc = "\e[0m"
+hex = "caf\xE9"
+"#;
+
+    assert_eq!(parse(src).errors.len(), 2);
+
+    let p = parse_with(
+        src,
+        ParseOptions {
+            toml_version: TomlVersion::V1_1,
+        },
+    );
+    assert!(p.errors.is_empty(), "{:#?}", p.errors);
+
+    let dom = p.into_dom();
+    assert_eq!(dom.get("esc").as_str().unwrap().value(), "\u{1b}[0m");
+    assert_eq!(dom.get("hex").as_str().unwrap().value(), "café");
+}
+
+#[test]
+fn toml_1_1_times_without_seconds() {
+    let src = r#"
+odt = 1979-05-27T07:32Z
+ldt = 1979-05-27 07:32
+lt = 07:32
+arr = [07:32, 00:00]
+"#;
+
+    let errors = parse(src).errors;
+    assert_eq!(errors.len(), 5, "{:#?}", errors);
+
+    let p = parse_with(
+        src,
+        ParseOptions {
+            toml_version: TomlVersion::V1_1,
+        },
+    );
+    assert!(p.errors.is_empty(), "{:#?}", p.errors);
+
+    let dom = p.into_dom();
+    assert!(dom.validate().is_ok());
+
+    let time = |key: &str| dom.get(key).as_date().unwrap().value();
+    assert!(matches!(time("odt"), DateTimeValue::OffsetDateTime(d) if d.second() == 0));
+    assert!(matches!(time("ldt"), DateTimeValue::LocalDateTime(d) if d.minute() == 32));
+    assert!(matches!(time("lt"), DateTimeValue::Time(t) if t.hour() == 7 && t.second() == 0));
+}
+
+#[test]
+fn dotted_key_text_ranges() {
+    let src = r#"
+d.e = 1
+d.f = 2
+
+[d.g]
+"#;
+
+    let dom = parse(src).into_dom();
+    assert!(dom.validate().is_ok());
+
+    let root = dom.as_table().unwrap().entries().read();
+    let (key, _) = root.iter().find(|(k, _)| k.value() == "d").unwrap();
+
+    let texts = key.text_ranges().map(|r| &src[r]).collect::<Vec<_>>();
+    assert_eq!(texts, ["d", "d", "d"]);
+}
+
+#[test]
+fn inconsistent_key_quotes() {
+    let src = r#"
+a.b = 1
+"a".c = 2
+'a'.d = 3
+
+[e]
+[["f"]]
+[[f]]
+"#;
+
+    let dom = parse(src).into_dom();
+    assert!(dom.validate().is_ok());
+
+    let inconsistent = dom
+        .inconsistent_keys()
+        .into_iter()
+        .map(|key| (key.text, key.first_text, &src[key.first_range]))
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        inconsistent,
+        [
+            (r#""a""#.into(), "a".into(), "a"),
+            ("'a'".into(), "a".into(), "a"),
+            ("f".into(), r#""f""#.into(), r#""f""#),
+        ]
+    );
+}
+
+#[test]
+fn escape_control_characters() {
+    use crate::dom::{node::Key, Node};
+
+    let key = Key::new("a\u{1}\u{7f}b");
+    assert_eq!(key.to_string(), r#""a\u0001\u007Fb""#);
+
+    let value = Node::from("tab\there\u{1b}[0m");
+    let toml = format!("{key} = {}", value.to_toml(true, false));
+    assert_eq!(toml, r#""a\u0001\u007Fb" = "tab\there\u001B[0m""#);
+
+    let dom = parse(&toml).into_dom();
+    assert!(dom.validate().is_ok());
+    let (parsed_key, parsed_value) = dom
+        .as_table()
+        .unwrap()
+        .entries()
+        .read()
+        .iter()
+        .next()
+        .cloned()
+        .unwrap();
+    assert_eq!(parsed_key.value(), key.value());
+    assert_eq!(parsed_value.as_str().unwrap().value(), "tab\there\u{1b}[0m");
+}
+
+#[test]
+fn floats_stay_floats() {
+    use crate::dom::Node;
+
+    for value in [
+        0.0, -0.0, 1.0, 2.5, -3.0, 1e15, 1e16, 1e20, -1e300, 1.5e17, 1e-7, 5e-324, 0.1,
+    ] {
+        let toml = format!("a = {}", Node::from(value).to_toml(false, false));
+
+        let dom = parse(&toml).into_dom();
+        let parsed = dom.get("a");
+        let float = match &parsed {
+            Node::Float(float) => float.value(),
+            _ => panic!("{toml} is not a float"),
+        };
+        assert_eq!(float.to_bits(), value.to_bits(), "{toml}");
+    }
+}
@@ -1,3 +1,4 @@
+use crate::parser::TomlVersion;
 use logos::{Lexer, Logos};
 
 /// Escaping based on:
@@ -7,8 +8,10 @@ use logos::{Lexer, Logos};
 /// \n         - linefeed        (U+000A)
 /// \f         - form feed       (U+000C)
 /// \r         - carriage return (U+000D)
+/// \e         - escape          (U+001B, TOML 1.1)
 /// \"         - quote           (U+0022)
 /// \\         - backslash       (U+005C)
+/// \xHH       - unicode         (U+00HH, TOML 1.1)
 /// \uXXXX     - unicode         (U+XXXX)
 /// \UXXXXXXXX - unicode         (U+XXXXXXXX)
 #[derive(Logos, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -31,12 +34,18 @@ pub enum Escape {
     #[token(r#"\r"#)]
     CarriageReturn,
 
+    #[token(r#"\e"#)]
+    Esc,
+
     #[token(r#"\""#)]
     Quote,
 
     #[token(r#"\\"#)]
     Backslash,
 
+    #[regex(r#"\\x[0-9A-Fa-f][0-9A-Fa-f]"#)]
+    Hex,
+
     // Same thing repeated 4 times, but the {n} repetition syntax is not supported by Logos
     #[regex(r#"\\u[0-9A-Fa-f_][0-9A-Fa-f_][0-9A-Fa-f_][0-9A-Fa-f_]"#)]
     Unicode,
@@ -54,6 +63,9 @@ pub enum Escape {
 use Escape::*;
 
 /// Escape values in a given string.
+///
+/// Control characters without a short escape are written as `\uXXXX`,
+/// as they are not allowed in basic strings.
 pub fn escape(s: &str) -> String {
     let mut escaped = String::with_capacity(s.len());
 
@@ -66,6 +78,7 @@ pub fn escape(s: &str) -> String {
             '\u{000D}' => escaped.push_str(r#"\r"#),
             '\u{0022}' => escaped.push_str(r#"\""#),
             '\u{005C}' => escaped.push_str(r#"\\"#),
+            _ if c.is_control() => escaped.push_str(&format!(r"\u{:04X}", c as u32)),
             _ => {
                 escaped.push(c);
             }
@@ -89,9 +102,15 @@ pub fn unescape(s: &str) -> Result<String, usize> {
             LineFeed => new_s += "\u{000A}",
             FormFeed => new_s += "\u{000C}",
             CarriageReturn => new_s += "\u{000D}",
+            Esc => new_s += "\u{001B}",
             Quote => new_s += "\u{0022}",
             Backslash => new_s += "\u{005C}",
             Newline => {}
+            Hex => {
+                new_s.push(char::from(
+                    u8::from_str_radix(&lexer.slice()[2..], 16).map_err(|_| lexer.span().start)?,
+                ));
+            }
             Unicode => {
                 new_s += &std::char::from_u32(
                     u32::from_str_radix(&lexer.slice()[2..], 16).map_err(|_| lexer.span().start)?,
@@ -118,7 +137,16 @@ pub fn unescape(s: &str) -> Result<String, usize> {
 
 /// Same as unescape, but doesn't create a new
 /// unescaped string, and returns all invalid escape indices.
+///
+/// Escapes introduced in TOML 1.1 are treated as invalid,
+/// use [`check_escape_with`] to allow them.
 pub fn check_escape(s: &str) -> Result<(), Vec<usize>> {
+    check_escape_with(s, TomlVersion::default())
+}
+
+/// Same as [`check_escape`], but accepts the escapes
+/// that are valid in the given TOML version.
+pub fn check_escape_with(s: &str, toml_version: TomlVersion) -> Result<(), Vec<usize>> {
     let mut lexer: Lexer<Escape> = Lexer::new(s);
     let mut invalid = Vec::new();
 
@@ -129,6 +157,11 @@ pub fn check_escape(s: &str) -> Result<(), Vec<usize>> {
             LineFeed => {}
             FormFeed => {}
             CarriageReturn => {}
+            Esc | Hex => {
+                if toml_version < TomlVersion::V1_1 {
+                    invalid.push(lexer.span().start);
+                }
+            }
             Quote => {}
             Backslash => {}
             Newline => {}
@@ -5,12 +5,21 @@ use rowan::TextSize;
 pub(crate) mod iter;
 pub(crate) mod shared;
 
-mod escape;
+pub(crate) mod escape;
 pub mod syntax;
 
-pub use escape::check_escape;
+pub use escape::{check_escape, check_escape_with};
 pub use escape::{escape, unescape};
 
+/// Whether the key can be written without quotes.
+///
+/// This is stricter than TOML, e.g. keys that look like integers
+/// or booleans are always quoted.
+pub fn is_bare_key(key: &str) -> bool {
+    let mut lexer = logos::Lexer::<SyntaxKind>::new(key);
+    matches!(lexer.next(), Some(SyntaxKind::IDENT)) && lexer.remainder().is_empty()
+}
+
 pub(crate) mod allowed_chars {
     pub(crate) fn comment(s: &str) -> Result<(), Vec<usize>> {
         let mut err_indices = Vec::new();
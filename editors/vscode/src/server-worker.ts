@@ -24,6 +24,7 @@ reader.listen(async message => {
         now: () => new Date(),
         readFile: () => Promise.reject("not implemented"),
         writeFile: () => Promise.reject("not implemented"),
+        removeFile: () => Promise.reject("not implemented"),
         stderr: async (bytes: Uint8Array) => {
           console.log(new TextDecoder().decode(bytes));
           return bytes.length;
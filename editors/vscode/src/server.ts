@@ -35,6 +35,7 @@ process.on("message", async (d: RpcMessage) => {
         now: () => new Date(),
         readFile: path => fsPromise.readFile(path),
         writeFile: (path, content) => fsPromise.writeFile(path, content),
+        removeFile: path => fsPromise.rm(path),
         stderr: process.stderr,
         stdErrAtty: () => process.stderr.isTTY,
         stdin: process.stdin,
@@ -31,6 +31,7 @@ import { convertEnv, Environment, prepareEnv } from "@taplo/core";
     now: () => new Date(),
     readFile: path => fsPromise.readFile(path),
     writeFile: (path, content) => fsPromise.writeFile(path, content),
+    removeFile: path => fsPromise.rm(path),
     stderr: process.stderr,
     stdErrAtty: () => process.stderr.isTTY,
     stdin: process.stdin,
@@ -52,6 +52,10 @@ export interface Environment {
    * Write and overwrite a file at the given path.
    */
   writeFile: (path: string, bytes: Uint8Array) => Promise<void>;
+  /**
+   * Remove the file at the given path.
+   */
+  removeFile: (path: string) => Promise<void>;
   /**
    * Turn an URL into a file path.
    */
@@ -129,6 +133,7 @@ export function convertEnv(env: Environment): any {
     js_glob_files: env.glob,
     js_read_file: env.readFile,
     js_write_file: env.writeFile,
+    js_remove_file: env.removeFile,
     js_to_file_path: env.urlToFilePath,
     js_is_absolute: env.isAbsolute,
     js_cwd: env.cwd,
@@ -227,6 +227,7 @@ function browserEnvironment(): Environment {
     now: () => new Date(),
     readFile: () => Promise.reject("not implemented"),
     writeFile: () => Promise.reject("not implemented"),
+    removeFile: () => Promise.reject("not implemented"),
     stderr: async bytes => {
       console.error(new TextDecoder().decode(bytes));
       return bytes.length;
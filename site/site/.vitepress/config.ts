@@ -97,6 +97,10 @@ export default defineConfigWithTheme<DefaultTheme.Config>({
               text: "Conversion and Extraction",
               link: "/cli/usage/conversion-and-extraction",
             },
+            {
+              text: "Editing",
+              link: "/cli/usage/editing",
+            },
             {
               text: "Language Server",
               link: "/cli/usage/language-server",
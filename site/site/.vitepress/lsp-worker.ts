@@ -20,6 +20,7 @@ const lsp = await TaploLsp.initialize(
     now: () => new Date(),
     readFile: () => Promise.reject("not implemented"),
     writeFile: () => Promise.reject("not implemented"),
+    removeFile: () => Promise.reject("not implemented"),
     stderr: async (bytes: Uint8Array) => {
       console.error(new TextDecoder().decode(bytes));
       return bytes.length;